@@ -0,0 +1,293 @@
+use super::{
+    Format, changeable, connected_phone, fatal_error, option_value, phone_packages, selected_users,
+    usage_error,
+};
+use crate::core::config::Config;
+use crate::core::journal::{Journal, JournalAction, JournalEntry};
+use crate::core::profile::{Profile, list_profiles, reconcile};
+use crate::core::save::{BackupTrigger, save_backup};
+use crate::core::sync::{Phone, User, run_pkg_state_commands};
+use crate::core::uad_lists::{PackageState, Removal};
+use crate::gui::widgets::package_row::PackageRow;
+use serde_json::{Value, json};
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+/// What `--packages` are changed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Mode {
+    #[default]
+    Uninstall,
+    Disable,
+}
+
+impl std::str::FromStr for Mode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uninstall" => Ok(Self::Uninstall),
+            "disable" => Ok(Self::Disable),
+            _ => Err(format!("unknown mode `{s}`")),
+        }
+    }
+}
+
+enum Source {
+    Profile(String),
+    Packages(String),
+}
+
+/// `apply`: exits with `1` if a change failed
+pub fn run(args: &[String]) -> ExitCode {
+    let mut format = Format::default();
+    let mut serial: Option<String> = None;
+    let mut user_ids = vec![];
+    let mut mode = Mode::default();
+    let mut dry_run = false;
+    let mut source = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let parsed =
+            match arg.as_str() {
+                "--format" => option_value("--format", args.next()).map(|f| format = f),
+                "--device" => option_value("--device", args.next()).map(|s| serial = Some(s)),
+                "--user" => option_value("--user", args.next()).map(|u| user_ids.push(u)),
+                "--mode" => option_value("--mode", args.next()).map(|m| mode = m),
+                "--dry-run" => {
+                    dry_run = true;
+                    Ok(())
+                }
+                "--profile" | "--packages" if source.is_some() => Err(usage_error(
+                    "only one of `--profile` and `--packages` can be given",
+                )),
+                "--profile" => option_value("--profile", args.next())
+                    .map(|p| source = Some(Source::Profile(p))),
+                "--packages" => option_value("--packages", args.next())
+                    .map(|p| source = Some(Source::Packages(p))),
+                a => Err(usage_error(&format!("unexpected argument `{a}`"))),
+            };
+        if let Err(code) = parsed {
+            return code;
+        }
+    }
+    let Some(source) = source else {
+        return usage_error("`--profile` or `--packages` is needed");
+    };
+
+    let phone = match connected_phone(serial.as_deref()) {
+        Ok(p) => p,
+        Err(code) => return code,
+    };
+    if let Err(code) = changeable(&phone) {
+        return code;
+    }
+    let users = match selected_users(&phone, &user_ids) {
+        Ok(u) => u,
+        Err(code) => return code,
+    };
+    let packages = phone_packages(&phone);
+    let expert_mode = Config::load_configuration_file().general.expert_mode;
+
+    let (mut changes, skipped) = match &source {
+        Source::Profile(profile) => {
+            let profile = match load_profile(profile) {
+                Ok(p) => p,
+                Err(e) => return fatal_error(&format!("can't load profile {profile}: {e}")),
+            };
+            // Only the selected users are reconciled, so their skipped packages alone are reported
+            let selected = Phone {
+                user_list: users.clone(),
+                ..phone.clone()
+            };
+            let plan = reconcile(&profile, &selected, &packages, expert_mode);
+            let changes: Vec<JournalEntry> = plan.changes.into_iter().map(|c| c.entry).collect();
+            let skipped = plan
+                .unsafe_skipped
+                .into_iter()
+                .map(|p| json!({ "package": p, "reason": UNSAFE_SKIPPED }))
+                .collect();
+            (changes, skipped)
+        }
+        Source::Packages(file) => match fs::read_to_string(file) {
+            Ok(list) => {
+                let names: Vec<&str> = list
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .collect();
+                let wanted = match mode {
+                    Mode::Uninstall => PackageState::Uninstalled,
+                    Mode::Disable => PackageState::Disabled,
+                };
+                list_changes(&phone, &packages, &users, &names, wanted, expert_mode)
+            }
+            Err(e) => return fatal_error(&format!("can't read {file}: {e}")),
+        },
+    };
+
+    if !dry_run && !changes.is_empty() {
+        apply(&phone, &packages, &mut changes);
+    }
+    print_report(format, &phone, dry_run, &changes, &skipped);
+
+    if changes.iter().any(|c| c.error.is_some()) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn print_report(
+    format: Format,
+    phone: &Phone,
+    dry_run: bool,
+    changes: &[JournalEntry],
+    skipped: &[Value],
+) {
+    let failed = changes.iter().filter(|c| c.error.is_some()).count();
+    match format {
+        Format::Json => println!(
+            "{:#}",
+            json!({
+                "device": phone.adb_id,
+                "dry_run": dry_run,
+                "changes": changes,
+                "skipped": skipped,
+                "failed": failed,
+            })
+        ),
+        Format::Text => {
+            for c in changes {
+                match &c.error {
+                    Some(e) => println!("{c}: FAILED ({e})"),
+                    None => println!("{c}"),
+                }
+            }
+            for s in skipped {
+                println!(
+                    "skipped {}: {}",
+                    s["package"].as_str().unwrap_or_default(),
+                    s["reason"].as_str().unwrap_or_default()
+                );
+            }
+            println!(
+                "{} change(s){}, {failed} failed",
+                changes.len(),
+                if dry_run { " planned" } else { "" }
+            );
+        }
+    }
+}
+
+const UNSAFE_SKIPPED: &str = "Unsafe package, expert mode is needed";
+
+/// A profile file, or the name of a profile of the config dir
+fn load_profile(profile: &str) -> Result<Profile, String> {
+    let path = Path::new(profile);
+    if path.is_file() {
+        return Profile::load(path);
+    }
+    list_profiles()
+        .into_iter()
+        .find(|p| p.name == profile)
+        .ok_or_else(|| "no such file or profile".to_string())
+}
+
+/// Changes bringing `names` to `wanted` for `users`,
+/// and the packages which can't be changed
+fn list_changes(
+    phone: &Phone,
+    packages: &[Vec<PackageRow>],
+    users: &[User],
+    names: &[&str],
+    wanted: PackageState,
+    expert_mode: bool,
+) -> (Vec<JournalEntry>, Vec<Value>) {
+    let mut changes = vec![];
+    let mut skipped = vec![];
+    for &name in names {
+        for user in users {
+            let skip =
+                |reason: &str| json!({ "package": name, "user_id": user.id, "reason": reason });
+            let Some(row) = packages
+                .get(user.index)
+                .and_then(|rows| rows.iter().find(|p| p.name == name))
+            else {
+                skipped.push(skip("not found on this device"));
+                continue;
+            };
+            // an uninstalled package is already "more" than disabled
+            if row.state == wanted || row.state == PackageState::Uninstalled {
+                continue;
+            }
+            if row.removal == Removal::Unsafe && !expert_mode {
+                skipped.push(skip(UNSAFE_SKIPPED));
+                continue;
+            }
+            match JournalEntry::plan(
+                phone,
+                name,
+                user.id,
+                row.state,
+                wanted,
+                JournalAction::Change,
+            ) {
+                Some(entry) if !entry.commands.is_empty() => changes.push(entry),
+                _ => skipped.push(skip("can't be changed on this Android version")),
+            }
+        }
+    }
+    (changes, skipped)
+}
+
+/// Take a snapshot, then run `changes` and journal them so that they can be undone in the GUI
+fn apply(phone: &Phone, packages: &[Vec<PackageRow>], changes: &mut [JournalEntry]) {
+    // Changes are still applied: they were asked for
+    if let Err(e) = save_backup(phone, packages, BackupTrigger::BeforeApply) {
+        eprintln!("warning: snapshot before applying changes failed: {e}");
+    }
+    let mut journal = Journal::load(&phone.adb_id);
+    for change in changes.iter_mut() {
+        change.error = run_pkg_state_commands(&phone.adb_id, &change.commands).err();
+        journal.record(change.clone());
+    }
+    if let Err(e) = journal.save(&phone.adb_id) {
+        eprintln!("warning: can't save the journal: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::uad_lists::UadList;
+
+    #[test]
+    fn packages_to_change() {
+        let phone = Phone {
+            model: "Model".into(),
+            android_sdk: 34,
+            user_list: vec![User::default()],
+            adb_id: "abc123".into(),
+        };
+        let row = |name, state, removal| PackageRow::test(name, state, UadList::Oem, removal);
+        let packages = vec![vec![
+            row("com.a", PackageState::Enabled, Removal::Recommended),
+            row("com.b", PackageState::Uninstalled, Removal::Recommended),
+            row("com.c", PackageState::Enabled, Removal::Unsafe),
+        ]];
+        let names = ["com.a", "com.b", "com.c", "com.missing"];
+        let (changes, skipped) = list_changes(
+            &phone,
+            &packages,
+            &phone.user_list,
+            &names,
+            PackageState::Disabled,
+            false,
+        );
+        let changes: Vec<_> = changes.iter().map(ToString::to_string).collect();
+        assert_eq!(changes, ["com.a (user 0): Enabled -> Disabled"]);
+        let skipped: Vec<_> = skipped.iter().map(|s| s["package"].clone()).collect();
+        assert_eq!(skipped, ["com.c", "com.missing"]);
+    }
+}
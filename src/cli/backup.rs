@@ -0,0 +1,199 @@
+use super::{
+    Format, changeable, connected_phone, fatal_error, option_value, phone_packages, usage_error,
+};
+use crate::core::archive::reinstall;
+use crate::core::config::DeviceSettings;
+use crate::core::fleet::journal_changes;
+use crate::core::save::{
+    BackupFile, BackupTrigger, RestorePlan, dry_run as plan_commands, extract_reinstalls,
+    restore_backup, restore_commands, save_backup,
+};
+use crate::core::sync::{Phone, run_pkg_state_commands, supports_multi_user};
+use crate::gui::widgets::package_row::PackageRow;
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// `backup`
+pub fn backup(args: &[String]) -> ExitCode {
+    let mut format = Format::default();
+    let mut serial: Option<String> = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--format" => option_value("--format", args.next()).map(|f| format = f),
+            "--device" => option_value("--device", args.next()).map(|s| serial = Some(s)),
+            a => Err(usage_error(&format!("unexpected argument `{a}`"))),
+        };
+        if let Err(code) = parsed {
+            return code;
+        }
+    }
+
+    let phone = match connected_phone(serial.as_deref()) {
+        Ok(p) => p,
+        Err(code) => return code,
+    };
+    let path = match save_backup(&phone, &phone_packages(&phone), BackupTrigger::Manual) {
+        Ok(p) => p,
+        Err(e) => return fatal_error(&format!("backup failed: {e}")),
+    };
+    match format {
+        Format::Json => println!("{:#}", json!({ "device": phone.adb_id, "path": path })),
+        Format::Text => println!("{}", path.display()),
+    }
+    ExitCode::SUCCESS
+}
+
+/// `restore`: exits with `1` if a package couldn't be restored or was skipped
+pub fn restore(args: &[String]) -> ExitCode {
+    let mut format = Format::default();
+    let mut serial: Option<String> = None;
+    let mut dry_run = false;
+    let mut file = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--format" => option_value("--format", args.next()).map(|f| format = f),
+            "--device" => option_value("--device", args.next()).map(|s| serial = Some(s)),
+            "--dry-run" => {
+                dry_run = true;
+                Ok(())
+            }
+            a if a.starts_with("--") => Err(usage_error(&format!("unknown option `{a}`"))),
+            _ if file.is_some() => Err(usage_error("only one backup can be restored")),
+            _ => {
+                file = Some(PathBuf::from(arg));
+                Ok(())
+            }
+        };
+        if let Err(code) = parsed {
+            return code;
+        }
+    }
+    let Some(file) = file else {
+        return usage_error("missing backup to restore");
+    };
+
+    let phone = match connected_phone(serial.as_deref()) {
+        Ok(p) => p,
+        Err(code) => return code,
+    };
+    if let Err(code) = changeable(&phone) {
+        return code;
+    }
+    let packages = phone_packages(&phone);
+    let backup = BackupFile::new(file);
+    if let Some(e) = &backup.error {
+        return fatal_error(&format!("can't read {}: {e}", backup.path));
+    }
+    let mut settings = DeviceSettings::default();
+    settings.backup.selected = Some(backup);
+    let plan = match restore_backup(&phone, &packages, &settings) {
+        Ok(plan) => plan,
+        Err(e) => return fatal_error(&e),
+    };
+    let steps = plan_commands(&phone, &plan);
+
+    let failed = if dry_run || steps.is_empty() {
+        vec![]
+    } else {
+        run_restore(&phone, &packages, &plan)
+    };
+
+    match format {
+        Format::Json => println!(
+            "{:#}",
+            json!({
+                "device": phone.adb_id,
+                "dry_run": dry_run,
+                "changes": steps,
+                "skipped": plan.skipped.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                "failed": failed,
+            })
+        ),
+        Format::Text => {
+            for step in &steps {
+                let from = step
+                    .from
+                    .map_or_else(|| "not installed".to_string(), |s| s.to_string());
+                println!(
+                    "{} (user {}): {from} -> {}",
+                    step.package, step.user_id, step.to
+                );
+            }
+            for skipped in &plan.skipped {
+                println!("skipped {skipped}");
+            }
+            for f in &failed {
+                println!(
+                    "failed {} (user {}): {}",
+                    f["package"].as_str().unwrap_or_default(),
+                    f["user_id"],
+                    f["error"].as_str().unwrap_or_default()
+                );
+            }
+            println!(
+                "{} change(s){}, {} failed",
+                steps.len(),
+                if dry_run { " planned" } else { "" },
+                failed.len()
+            );
+        }
+    }
+
+    if failed.is_empty() && plan.skipped.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Take a snapshot, then restore `plan` and journal it so that it can be undone in the GUI.
+/// Returns the packages which couldn't be restored.
+fn run_restore(phone: &Phone, packages: &[Vec<PackageRow>], plan: &RestorePlan) -> Vec<Value> {
+    // The restore still happens: it was asked for
+    if let Err(e) = save_backup(phone, packages, BackupTrigger::BeforeRestore) {
+        eprintln!("warning: snapshot before restoring failed: {e}");
+    }
+    let mut changes = vec![];
+    for p in restore_commands(phone, &plan.items) {
+        let mut entry = p.entry;
+        entry.error = run_pkg_state_commands(&phone.adb_id, &entry.commands).err();
+        changes.push(entry);
+    }
+    let extracted = match &plan.archive {
+        Some(archive) => extract_reinstalls(archive, phone, &plan.reinstalls),
+        None => Ok(()),
+    };
+    for item in plan.reinstalls.iter().filter(|r| r.selected) {
+        let user_id = supports_multi_user(phone).then_some(item.user.id);
+        let mut entry = item.entry(phone);
+        entry.error = extracted
+            .clone()
+            .and_then(|()| {
+                reinstall(
+                    &phone.adb_id,
+                    &item.package,
+                    user_id,
+                    &item.apks,
+                    item.wanted,
+                )
+            })
+            .err();
+        changes.push(entry);
+    }
+    if let Err(e) = journal_changes(&phone.adb_id, &changes) {
+        eprintln!("warning: can't save the journal: {e}");
+    }
+    changes
+        .iter()
+        .filter_map(|entry| {
+            Some(json!({
+                "package": entry.package,
+                "user_id": entry.user_id,
+                "error": entry.error.as_ref()?,
+            }))
+        })
+        .collect()
+}
@@ -0,0 +1,136 @@
+use super::{
+    Format, connected_phone, fatal_error, option_value, phone_packages, selected_users, usage_error,
+};
+use crate::core::adb::ACommand as AdbCommand;
+use crate::core::sync::device_info;
+use serde_json::json;
+use std::process::ExitCode;
+
+/// `devices`: exits with `1` if no device is authorized
+pub fn devices(args: &[String]) -> ExitCode {
+    let mut format = Format::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match option_value("--format", args.next()) {
+                Ok(f) => format = f,
+                Err(code) => return code,
+            },
+            a => return usage_error(&format!("unexpected argument `{a}`")),
+        }
+    }
+
+    let devices = match AdbCommand::new().devices() {
+        Ok(d) => d,
+        Err(e) => return fatal_error(&e),
+    };
+    let ready = devices.iter().filter(|(_, s)| s == "device").count();
+    let devices: Vec<_> = devices
+        .into_iter()
+        .map(|(serial, status)| {
+            if status != "device" {
+                return json!({ "serial": serial, "status": status });
+            }
+            let phone = device_info(&serial);
+            json!({
+                "serial": serial,
+                "status": status,
+                "model": phone.model,
+                "android_sdk": phone.android_sdk,
+                "users": phone.user_list.iter().map(|u| json!({
+                    "id": u.id,
+                    "kind": u.kind,
+                    "protected": u.protected,
+                })).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    match format {
+        Format::Json => println!("{:#}", json!(devices)),
+        Format::Text => {
+            for d in &devices {
+                let users = d["users"].as_array().map_or_else(String::new, |users| {
+                    let ids: Vec<String> = users.iter().map(|u| u["id"].to_string()).collect();
+                    format!(", users {}", ids.join(" "))
+                });
+                println!(
+                    "{}\t{}\t{}{users}",
+                    d["serial"].as_str().unwrap_or_default(),
+                    d["status"].as_str().unwrap_or_default(),
+                    d["model"].as_str().unwrap_or_default(),
+                );
+            }
+        }
+    }
+
+    if ready == 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// `packages`
+pub fn packages(args: &[String]) -> ExitCode {
+    let mut format = Format::default();
+    let mut serial: Option<String> = None;
+    let mut user_ids = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--format" => option_value("--format", args.next()).map(|f| format = f),
+            "--device" => option_value("--device", args.next()).map(|s| serial = Some(s)),
+            "--user" => option_value("--user", args.next()).map(|u| user_ids.push(u)),
+            a => Err(usage_error(&format!("unexpected argument `{a}`"))),
+        };
+        if let Err(code) = parsed {
+            return code;
+        }
+    }
+
+    let phone = match connected_phone(serial.as_deref()) {
+        Ok(p) => p,
+        Err(code) => return code,
+    };
+    let users = match selected_users(&phone, &user_ids) {
+        Ok(u) => u,
+        Err(code) => return code,
+    };
+    let packages = phone_packages(&phone);
+
+    let users: Vec<_> = users
+        .iter()
+        .map(|u| {
+            let rows = packages.get(u.index).map_or(&[][..], Vec::as_slice);
+            json!({
+                "id": u.id,
+                "packages": rows.iter().map(|p| json!({
+                    "name": p.name,
+                    "state": p.state,
+                    "list": p.uad_list.to_string(),
+                    "removal": p.removal.to_string(),
+                })).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    match format {
+        Format::Json => println!("{:#}", json!({ "device": phone.adb_id, "users": users })),
+        Format::Text => {
+            for user in &users {
+                for p in user["packages"].as_array().into_iter().flatten() {
+                    println!(
+                        "{}\t{}\t{}\t{}\t{}",
+                        user["id"],
+                        p["name"].as_str().unwrap_or_default(),
+                        p["state"].as_str().unwrap_or_default(),
+                        p["list"].as_str().unwrap_or_default(),
+                        p["removal"].as_str().unwrap_or_default(),
+                    );
+                }
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
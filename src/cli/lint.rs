@@ -0,0 +1,85 @@
+use super::{Format, USAGE_ERROR, usage_error};
+use crate::core::lint::{Severity, lint_list};
+use serde_json::json;
+use std::fs;
+use std::process::ExitCode;
+
+pub fn run(args: &[String]) -> ExitCode {
+    let mut format = Format::default();
+    let mut deny_warnings = false;
+    let mut references = vec![];
+    let mut file = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(|f| f.parse()) {
+                Some(Ok(f)) => format = f,
+                Some(Err(e)) => return usage_error(&e),
+                None => return usage_error("`--format` needs a value"),
+            },
+            "--reference" => match args.next() {
+                Some(r) => references.push(r),
+                None => return usage_error("`--reference` needs a file"),
+            },
+            "--deny-warnings" => deny_warnings = true,
+            a if a.starts_with("--") => return usage_error(&format!("unknown option `{a}`")),
+            _ if file.is_some() => return usage_error("only one file can be linted"),
+            _ => file = Some(arg),
+        }
+    }
+    let Some(file) = file else {
+        return usage_error("missing file to lint");
+    };
+
+    let read = |path: &String| {
+        fs::read_to_string(path).map_err(|e| {
+            eprintln!("error: can't read {path}: {e}");
+            ExitCode::from(USAGE_ERROR)
+        })
+    };
+    let text = match read(file) {
+        Ok(t) => t,
+        Err(code) => return code,
+    };
+    let references = match references
+        .into_iter()
+        .map(read)
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(r) => r,
+        Err(code) => return code,
+    };
+    let references: Vec<&str> = references.iter().map(String::as_str).collect();
+
+    let diagnostics = lint_list(&text, &references);
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+
+    match format {
+        Format::Json => println!(
+            "{:#}",
+            json!({
+                "file": file,
+                "errors": errors,
+                "warnings": warnings,
+                "diagnostics": diagnostics,
+            })
+        ),
+        Format::Text => {
+            for d in &diagnostics {
+                println!("{file}: {d}");
+            }
+            println!("{errors} error(s), {warnings} warning(s)");
+        }
+    }
+
+    if errors > 0 || (deny_warnings && warnings > 0) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,199 @@
+//! Command-line interface, for tasks that don't need the GUI.
+//!
+//! Exit codes are shared by all sub-commands:
+//! - `0`: success
+//! - `1`: the task ran, but reported problems
+//! - `2`: invalid usage, or the task couldn't run at all
+
+use crate::core::adb::ACommand as AdbCommand;
+use crate::core::config::Config;
+use crate::core::sync::{Phone, User, device_info};
+use crate::core::uad_lists::load_debloat_lists;
+use crate::core::utils::fetch_phone_packages;
+use crate::gui::widgets::package_row::PackageRow;
+use std::process::ExitCode;
+
+mod apply;
+mod backup;
+mod device;
+mod lint;
+
+const USAGE: &str = "\
+Usage: uad-ng [COMMAND]
+
+Without a command, the graphical interface is started.
+`--device` can be omitted when a single device is connected,
+and `--user` defaults to all users ADB can access.
+
+Commands:
+  lint [--format json|text] [--reference FILE]... [--deny-warnings] FILE
+      Validate a `uad_lists.json`-format file.
+      `--reference` lists (e.g. upstream) may be referenced by `FILE`.
+  devices [--format json|text]
+      List connected devices, with their users.
+  packages [--device SERIAL] [--user ID]... [--format json|text]
+      List the packages of a device, with their list, removal and state.
+  apply [--device SERIAL] [--user ID]... [--mode uninstall|disable] [--dry-run]
+        [--format json|text] (--profile NAME|FILE | --packages FILE)
+      Converge a device to a profile, or remove (or disable) the packages
+      of a file (one per line). A snapshot of the device is taken first.
+  backup [--device SERIAL] [--format json|text]
+      Backup the state of the packages of a device.
+  restore [--device SERIAL] [--dry-run] [--format json|text] FILE
+      Restore a backup. A snapshot of the device is taken first.
+  help
+      Print this message.
+";
+
+/// Exit code for invalid usage
+const USAGE_ERROR: u8 = 2;
+
+/// Run the sub-command in `args` (without the program name).
+///
+/// `None` if `args` are empty or GUI flags: the GUI should be started.
+pub fn run(args: &[String]) -> Option<ExitCode> {
+    let (command, args) = args.split_first()?;
+    Some(match command.as_str() {
+        "lint" => lint::run(args),
+        "devices" => device::devices(args),
+        "packages" => device::packages(args),
+        "apply" => apply::run(args),
+        "backup" => backup::backup(args),
+        "restore" => backup::restore(args),
+        "help" | "--help" | "-h" => {
+            print!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+        // Passed by the GUI to the updated binary it relaunches
+        "--self-update-temp" => return None,
+        c => usage_error(&format!("unknown command `{c}`")),
+    })
+}
+
+/// Print an error about the command-line, and return the matching exit code
+fn usage_error(msg: &str) -> ExitCode {
+    eprintln!("error: {msg}\n\n{USAGE}");
+    ExitCode::from(USAGE_ERROR)
+}
+
+/// Print an error that prevents a task from running, and return the matching exit code
+fn fatal_error(msg: &str) -> ExitCode {
+    eprintln!("error: {msg}");
+    ExitCode::from(USAGE_ERROR)
+}
+
+/// The authorized device `serial`, or the only one if `None`
+fn connected_phone(serial: Option<&str>) -> Result<Phone, ExitCode> {
+    let devices = AdbCommand::new().devices().map_err(|e| fatal_error(&e))?;
+    let mut ready = devices
+        .iter()
+        .filter(|(_, status)| status == "device")
+        .map(|(s, _)| s.as_str());
+    let found = match serial {
+        Some(serial) => ready
+            .find(|&s| s == serial)
+            .ok_or_else(|| fatal_error(&format!("device {serial} not found, or not authorized")))?,
+        None => match (ready.next(), ready.next()) {
+            (Some(only), None) => only,
+            (None, _) => return Err(fatal_error("no authorized device connected")),
+            (Some(_), Some(_)) => {
+                return Err(fatal_error(
+                    "several devices connected, choose one with `--device`",
+                ));
+            }
+        },
+    };
+    Ok(device_info(found))
+}
+
+/// Oldest Android SDK (4.4) whose packages ADB can change without root
+const MIN_CHANGEABLE_SDK: u8 = 19;
+
+/// Refuse to plan changes for a device too old to have them applied
+fn changeable(phone: &Phone) -> Result<(), ExitCode> {
+    if phone.android_sdk < MIN_CHANGEABLE_SDK {
+        return Err(fatal_error(&format!(
+            "{} runs Android SDK {}, packages can't be changed before SDK {MIN_CHANGEABLE_SDK}",
+            phone.adb_id, phone.android_sdk
+        )));
+    }
+    Ok(())
+}
+
+/// Users of `phone` among `ids` (all of them if empty) that ADB can access
+fn selected_users(phone: &Phone, ids: &[u16]) -> Result<Vec<User>, ExitCode> {
+    for id in ids {
+        match phone.user_list.iter().find(|u| u.id == *id) {
+            None => return Err(fatal_error(&format!("user {id} not found"))),
+            Some(u) if u.protected => {
+                return Err(fatal_error(&format!(
+                    "ADB isn't allowed to access user {id}"
+                )));
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(phone
+        .user_list
+        .iter()
+        .filter(|u| !u.protected && (ids.is_empty() || ids.contains(&u.id)))
+        .copied()
+        .collect())
+}
+
+/// Packages of every user of `phone`, as the GUI loads them:
+/// `User::index` is the index of their packages
+fn phone_packages(phone: &Phone) -> Vec<Vec<PackageRow>> {
+    let uad_lists = load_debloat_lists(
+        &Config::load_configuration_file().general.list_sources,
+        false,
+    )
+    .unwrap_or_else(|(list, e)| {
+        eprintln!("warning: some debloat lists couldn't be loaded: {e}");
+        list
+    });
+    fetch_phone_packages(&uad_lists, &phone.adb_id, &phone.user_list)
+}
+
+/// Parse the value of `option`
+fn option_value<T: std::str::FromStr>(option: &str, value: Option<&String>) -> Result<T, ExitCode> {
+    let value = value.ok_or_else(|| usage_error(&format!("`{option}` needs a value")))?;
+    value
+        .parse()
+        .map_err(|_| usage_error(&format!("invalid value `{value}` for `{option}`")))
+}
+
+/// Output format of sub-commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Format {
+    #[default]
+    Json,
+    Text,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "text" => Ok(Self::Text),
+            _ => Err(format!("unknown format `{s}`")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_command_is_a_usage_error() {
+        let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(run(&[]), None);
+        assert_eq!(run(&args(&["--self-update-temp", "/tmp/uad-ng"])), None);
+        assert_eq!(
+            run(&args(&["lnt", "file.json"])),
+            Some(ExitCode::from(USAGE_ERROR))
+        );
+    }
+}
@@ -129,6 +129,46 @@ impl ACommand {
             .collect())
     }
 
+    /// `pull` sub-command: copy `remote` (a file of the device) to `local`.
+    ///
+    /// If `device_serial` is empty, it lets ADB choose the default device.
+    pub fn pull<S: AsRef<str>>(
+        mut self,
+        device_serial: S,
+        remote: &str,
+        local: &std::path::Path,
+    ) -> Result<String, String> {
+        let serial = device_serial.as_ref();
+        if !serial.is_empty() {
+            self.0.args(["-s", serial]);
+        }
+        self.0.args(["pull", remote]);
+        self.0.arg(local);
+        self.run()
+    }
+
+    /// `install-multiple` sub-command: install a package from its base and split APKs.
+    ///
+    /// If `device_serial` is empty, it lets ADB choose the default device.
+    pub fn install_multiple<S: AsRef<str>>(
+        mut self,
+        device_serial: S,
+        user_id: Option<u16>,
+        apks: &[std::path::PathBuf],
+    ) -> Result<String, String> {
+        let serial = device_serial.as_ref();
+        if !serial.is_empty() {
+            self.0.args(["-s", serial]);
+        }
+        self.0.arg("install-multiple");
+        if let Some(u) = user_id {
+            self.0.arg("--user");
+            self.0.arg(u.to_string());
+        }
+        self.0.args(apks);
+        self.run()
+    }
+
     /// `version` sub-command
     ///
     /// ## Format
@@ -242,6 +282,93 @@ impl ShellCommand {
         self.0.0.arg("reboot");
         self.0.run()
     }
+    /// `dumpsys package <pack>`, parsed.
+    ///
+    /// Only the first section about `pack` is considered,
+    /// so info about "hidden system packages" is ignored.
+    pub fn dumpsys_package(mut self, pack: &PackageId) -> Result<PackageDump, String> {
+        self.0.0.args(["dumpsys", "package", &pack.0]);
+        self.0.run().map(|out| PackageDump::parse(&out))
+    }
+    /// Run a package state change, as built by
+    /// [`apply_pkg_state_commands`](crate::core::sync::apply_pkg_state_commands).
+    ///
+    /// On old devices, a failure can have the `0` exit code,
+    /// so the output is checked too.
+    pub fn pkg_state_change(mut self, command: &str) -> Result<String, String> {
+        // this works because `sh` splits spaces
+        self.0.0.arg(command);
+        let out = self.0.run()?;
+        if ["Error", "Failure"].iter().any(|&e| out.contains(e)) {
+            Err(out)
+        } else {
+            Ok(out)
+        }
+    }
+}
+
+/// Subset of `dumpsys package` info about a single package
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageDump {
+    pub version_name: Option<String>,
+    pub version_code: Option<String>,
+    /// `None` if pre-installed (or sideloaded without an installer)
+    pub installer: Option<String>,
+    /// Requested permissions, in the order they're declared
+    pub permissions: Vec<String>,
+}
+impl PackageDump {
+    fn parse(out: &str) -> Self {
+        let mut dump = Self::default();
+        let mut in_package = false;
+        // indentation of the "requested permissions:" header, entries are deeper
+        let mut permissions_indent = None;
+        for ln in out.lines() {
+            let trimmed = ln.trim_ascii();
+            let indent = ln.len() - ln.trim_ascii_start().len();
+            if trimmed.starts_with("Package [") {
+                if in_package {
+                    break;
+                }
+                in_package = true;
+                continue;
+            }
+            if !in_package || trimmed.is_empty() {
+                continue;
+            }
+            // sections such as "Hidden system packages:" aren't indented
+            if indent == 0 {
+                break;
+            }
+            if let Some(header) = permissions_indent {
+                if indent > header {
+                    let perm = trimmed.split(':').next().unwrap_or(trimmed);
+                    dump.permissions.push(perm.to_string());
+                    continue;
+                }
+                permissions_indent = None;
+            }
+            if trimmed == "requested permissions:" {
+                permissions_indent = Some(indent);
+                continue;
+            }
+            for field in trimmed.split_ascii_whitespace() {
+                let Some((key, value)) = field.split_once('=') else {
+                    continue;
+                };
+                let slot = match key {
+                    "versionName" => &mut dump.version_name,
+                    "versionCode" => &mut dump.version_code,
+                    "installerPackageName" => &mut dump.installer,
+                    _ => continue,
+                };
+                if slot.is_none() && value != "null" {
+                    *slot = Some(value.to_string());
+                }
+            }
+        }
+        dump
+    }
 }
 
 #[must_use]
@@ -356,6 +483,35 @@ impl PmCommand {
         })
     }
 
+    /// `path <pack>` sub-command, [`PACK_PREFIX`] stripped.
+    ///
+    /// Split APKs have several paths, the base APK usually being the first.
+    pub fn path(mut self, pack: &PackageId) -> Result<Vec<String>, String> {
+        self.0.0.0.args(["path", &pack.0]);
+        self.0.0.run().map(|out| {
+            out.lines()
+                .filter_map(|ln| ln.strip_prefix(PACK_PREFIX))
+                .map(String::from)
+                .collect()
+        })
+    }
+
+    /// `disable-user <pack>` sub-command
+    pub fn disable_user(
+        mut self,
+        pack: &PackageId,
+        user_id: Option<u16>,
+    ) -> Result<String, String> {
+        let cmd = &mut self.0.0.0;
+        cmd.arg("disable-user");
+        if let Some(u) = user_id {
+            cmd.arg("--user");
+            cmd.arg(u.to_string());
+        }
+        cmd.arg(&*pack.0);
+        self.0.0.run()
+    }
+
     /// `list users` sub-command, deserialized/parsed.
     ///
     /// - <https://source.android.com/docs/devices/admin/multi-user-testing>
@@ -386,27 +542,23 @@ impl PmCommand {
                 // the format seems to be stable across Android versions:
                 // "\tUserInfo{<id>:<name>:<flags>}[ running]"
 
-                let mut comps = ln.split(':');
-
-                let id = comps
-                    .next()
-                    .expect("There must be at least 1 ':'-separated component")
-                    .parse()
-                    .expect("string assumed to be UID numeral");
-                //let name = comps
-                //    .next()
-                //    .expect("There must be at least 2 ':'-separated components. 2nd is user-name");
-                //let flags = u32::from_str_radix(
-                //    comps.next().expect(
-                //        "There must be at least 3 ':'-separated components. 3rd is user bit-flags",
-                //    ),
-                //    16,
-                //)
-                //.expect("string assumed to be hexadecimal bit-flags");
+                // user-names can contain ':', so flags are taken from the end
+                let (id, rest) = ln
+                    .split_once(':')
+                    .expect("There must be at least 1 ':'. 1st component is UID");
+                let id = id.parse().expect("string assumed to be UID numeral");
+                //let name = rest
+                //    .rsplit_once(':')
+                //    .expect("There must be at least 2 ':'. 2nd component is user-name")
+                //    .0;
+                // unexpected flags make an `Unknown` user rather than a crash
+                let flags = rest
+                    .rsplit_once(':')
+                    .map_or(0, |(_, flags)| u32::from_str_radix(flags, 16).unwrap_or(0));
                 UserInfo {
                     id,
                     //name: name.into(),
-                    //flags,
+                    flags,
                     running: run,
                 }
             })
@@ -420,7 +572,7 @@ impl PmCommand {
 pub struct UserInfo {
     id: u16,
     //name: Box<str>,
-    //flags: u32,
+    flags: u32,
     running: bool,
 }
 impl UserInfo {
@@ -428,6 +580,10 @@ impl UserInfo {
     pub const fn get_id(&self) -> u16 {
         self.id
     }
+    #[must_use]
+    pub const fn kind(&self) -> UserKind {
+        UserKind::from_flags(self.flags)
+    }
     /// Check if the user was logged-in
     /// at the time `pm list users` was invoked
     #[must_use]
@@ -436,6 +592,72 @@ impl UserInfo {
     }
 }
 
+/// Type of an Android user, derived from its `UserInfo` bit-flags.
+///
+/// [More info](https://android.googlesource.com/platform/frameworks/base/+/refs/heads/main/core/java/android/content/pm/UserInfo.java)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UserKind {
+    /// Flags couldn't be parsed, or match no other kind
+    #[default]
+    Unknown,
+    /// Owner of the device, typically user 0
+    Primary,
+    /// Headless system user, on devices where the owner is a separate user
+    System,
+    Secondary,
+    Guest,
+    Restricted,
+    /// Work profile
+    Managed,
+    /// Other profiles, such as clone or private spaces
+    Profile,
+}
+impl UserKind {
+    const FLAG_PRIMARY: u32 = 0x1;
+    const FLAG_GUEST: u32 = 0x4;
+    const FLAG_RESTRICTED: u32 = 0x8;
+    const FLAG_MANAGED_PROFILE: u32 = 0x20;
+    const FLAG_FULL: u32 = 0x400;
+    const FLAG_SYSTEM: u32 = 0x800;
+    const FLAG_PROFILE: u32 = 0x1000;
+
+    #[must_use]
+    pub const fn from_flags(flags: u32) -> Self {
+        // order matters: e.g. the owner has both `PRIMARY` and `SYSTEM`
+        if flags & Self::FLAG_MANAGED_PROFILE != 0 {
+            Self::Managed
+        } else if flags & Self::FLAG_PROFILE != 0 {
+            Self::Profile
+        } else if flags & Self::FLAG_GUEST != 0 {
+            Self::Guest
+        } else if flags & Self::FLAG_RESTRICTED != 0 {
+            Self::Restricted
+        } else if flags & Self::FLAG_PRIMARY != 0 {
+            Self::Primary
+        } else if flags & Self::FLAG_SYSTEM != 0 {
+            Self::System
+        } else if flags & Self::FLAG_FULL != 0 {
+            Self::Secondary
+        } else {
+            Self::Unknown
+        }
+    }
+}
+impl std::fmt::Display for UserKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Unknown => "unknown",
+            Self::Primary => "primary",
+            Self::System => "system",
+            Self::Secondary => "secondary",
+            Self::Guest => "guest",
+            Self::Restricted => "restricted",
+            Self::Managed => "work profile",
+            Self::Profile => "profile",
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -458,6 +680,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn user_kinds() {
+        for (flags, kind) in [
+            (0x13, UserKind::Primary),
+            (0xc13, UserKind::Primary),
+            (0x800, UserKind::System),
+            (0x410, UserKind::Secondary),
+            (0x414, UserKind::Guest),
+            (0x1030, UserKind::Managed),
+            (0x1010, UserKind::Profile),
+            (0, UserKind::Unknown),
+        ] {
+            assert_eq!(UserKind::from_flags(flags), kind, "{flags:#x}");
+        }
+    }
+
+    #[test]
+    fn parse_package_dump() {
+        let out = "\
+Activity Resolver Table:
+  Non-Data Actions:
+      android.intent.action.MAIN:
+Packages:
+  Package [com.example.app] (1a2b3c):
+    userId=10123
+    codePath=/system/app/Example
+    versionCode=42 minSdk=28 targetSdk=33
+    versionName=1.2.3
+    installerPackageName=null
+    requested permissions:
+      android.permission.INTERNET
+      android.permission.READ_CONTACTS: restricted=true
+    install permissions:
+      android.permission.INTERNET: granted=true
+    User 0: ceDataInode=1 installed=true hidden=false
+Hidden system packages:
+  Package [com.example.app] (4d5e6f):
+    versionName=1.0.0
+";
+        assert_eq!(
+            PackageDump::parse(out),
+            PackageDump {
+                version_name: Some("1.2.3".into()),
+                version_code: Some("42".into()),
+                installer: None,
+                permissions: vec![
+                    "android.permission.INTERNET".into(),
+                    "android.permission.READ_CONTACTS".into()
+                ],
+            }
+        );
+    }
+
     #[test]
     fn valid_pack_ids() {
         for p_id in [
@@ -0,0 +1,256 @@
+//! Portable backups: a regular backup bundled with the APKs of some packages,
+//! so that apps which were fully removed from the device can be reinstalled.
+//!
+//! Archives are `tar.gz` files stored next to the JSON backups of the device:
+//! - `backup.json`: the [`PhoneBackup`](crate::core::save::PhoneBackup), always the first entry
+//! - `apks/<package>/<file>.apk`: base and split APKs of each archived package
+
+use crate::core::adb::{ACommand as AdbCommand, PackageId};
+use crate::core::config::Config;
+use crate::core::save::{BackupTrigger, new_backup, new_backup_path};
+use crate::core::sync::Phone;
+use crate::core::uad_lists::PackageState;
+use crate::gui::widgets::package_row::PackageRow;
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+const ARCHIVE_SUFFIX: &str = ".tar.gz";
+const BACKUP_ENTRY: &str = "backup.json";
+const APKS_DIR: &str = "apks";
+
+/// `true` if `path` is a portable backup rather than a JSON one
+#[must_use]
+pub fn is_archive(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(ARCHIVE_SUFFIX)
+}
+
+/// What went into an archive
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveReport {
+    pub path: PathBuf,
+    pub archived: Vec<String>,
+    /// Package and why its APKs couldn't be pulled
+    pub failed: Vec<(String, String)>,
+}
+
+/// Backup the state of the device, along with the APKs of `packages`
+pub async fn archive_phone(
+    phone: Phone,
+    phone_packages: Vec<Vec<PackageRow>>,
+    packages: Vec<String>,
+) -> Result<ArchiveReport, String> {
+    let backup = new_backup(&phone, &phone_packages, BackupTrigger::Manual);
+    let json = serde_json::to_string_pretty(&backup).map_err(|e| e.to_string())?;
+
+    let staging = std::env::temp_dir().join(format!(
+        "uad_archive_{}",
+        phone
+            .adb_id
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    ));
+    let _ = fs::remove_dir_all(&staging);
+
+    let mut report = ArchiveReport::default();
+    for package in packages {
+        match pull_apks(
+            &phone.adb_id,
+            &package,
+            &staging.join(APKS_DIR).join(&package),
+        ) {
+            Ok(()) => report.archived.push(package),
+            Err(e) => {
+                warn!("[ARCHIVE] {package}: {e}");
+                report.failed.push((package, e));
+            }
+        }
+    }
+
+    let dir = Config::load_configuration_file()
+        .general
+        .backup_folder
+        .join(&phone.adb_id);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    report.path = new_backup_path(
+        &dir,
+        chrono::Local::now(),
+        BackupTrigger::Manual,
+        ARCHIVE_SUFFIX,
+    )
+    .map_err(|e| e.to_string())?;
+
+    // Written aside, then renamed over the reserved path: an interrupted write leaves no archive
+    let mut tmp = report.path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let written = write_archive(&tmp, &json, &staging.join(APKS_DIR))
+        .and_then(|()| fs::rename(&tmp, &report.path));
+    let _ = fs::remove_dir_all(&staging);
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        let _ = fs::remove_file(&report.path);
+        return Err(e.to_string());
+    }
+    Ok(report)
+}
+
+/// Pull the base and split APKs of `package` into `dest`
+fn pull_apks(serial: &str, package: &str, dest: &Path) -> Result<(), String> {
+    let id = PackageId::new(package.into()).ok_or("invalid package name")?;
+    let remotes = AdbCommand::new().shell(serial).pm().path(&id)?;
+    if remotes.is_empty() {
+        return Err("no APK found on the device".to_string());
+    }
+    fs::create_dir_all(dest).map_err(|e| e.to_string())?;
+    for remote in remotes {
+        let file_name = Path::new(&remote)
+            .file_name()
+            .ok_or_else(|| format!("unexpected APK path: {remote}"))?;
+        AdbCommand::new().pull(serial, &remote, &dest.join(file_name))?;
+    }
+    Ok(())
+}
+
+fn write_archive(path: &Path, backup_json: &str, apks: &Path) -> io::Result<()> {
+    let mut builder =
+        tar::Builder::new(GzEncoder::new(File::create(path)?, Compression::default()));
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(backup_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, BACKUP_ENTRY, backup_json.as_bytes())?;
+
+    if apks.is_dir() {
+        builder.append_dir_all(APKS_DIR, apks)?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// The backup of an archive, as JSON
+pub fn read_archive_backup(path: &Path) -> Result<String, String> {
+    let mut archive =
+        tar::Archive::new(GzDecoder::new(File::open(path).map_err(|e| e.to_string())?));
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        if entry.path().map_err(|e| e.to_string())? == Path::new(BACKUP_ENTRY) {
+            let mut data = String::new();
+            entry.read_to_string(&mut data).map_err(|e| e.to_string())?;
+            return Ok(data);
+        }
+    }
+    Err(format!("{BACKUP_ENTRY} not found in the archive"))
+}
+
+/// Package of an `apks/<package>/<file>` entry
+fn apk_package(entry_path: &Path) -> Option<String> {
+    let components: Vec<Component> = entry_path.components().collect();
+    let [
+        Component::Normal(dir),
+        Component::Normal(package),
+        Component::Normal(_),
+    ] = components[..]
+    else {
+        return None;
+    };
+    (dir == APKS_DIR).then(|| package.to_string_lossy().into_owned())
+}
+
+/// The APKs of an archive, grouped by package, where [`extract_apks`] puts them in `dest`.
+///
+/// Nothing is extracted.
+pub fn list_apks(path: &Path, dest: &Path) -> Result<BTreeMap<String, Vec<PathBuf>>, String> {
+    let mut archive =
+        tar::Archive::new(GzDecoder::new(File::open(path).map_err(|e| e.to_string())?));
+    let mut apks: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let entry_path = entry.path().map_err(|e| e.to_string())?;
+        if let Some(package) = apk_package(&entry_path) {
+            apks.entry(package)
+                .or_default()
+                .push(dest.join(&entry_path));
+        }
+    }
+    Ok(apks)
+}
+
+/// Extract the APKs of `packages` from an archive into `dest`
+pub fn extract_apks(path: &Path, dest: &Path, packages: &[&str]) -> Result<(), String> {
+    let mut archive =
+        tar::Archive::new(GzDecoder::new(File::open(path).map_err(|e| e.to_string())?));
+    fs::create_dir_all(dest).map_err(|e| e.to_string())?;
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        let entry_path = entry.path().map_err(|e| e.to_string())?.into_owned();
+        if apk_package(&entry_path).is_some_and(|p| packages.contains(&p.as_str())) {
+            // `unpack_in` refuses paths escaping `dest`
+            entry.unpack_in(dest).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Reinstall `package` for `user_id` from its APKs, then disable it if that's the `wanted` state
+pub fn reinstall(
+    serial: &str,
+    package: &str,
+    user_id: Option<u16>,
+    apks: &[PathBuf],
+    wanted: PackageState,
+) -> Result<(), String> {
+    AdbCommand::new().install_multiple(serial, user_id, apks)?;
+    if wanted == PackageState::Disabled {
+        let id = PackageId::new(package.into()).ok_or("invalid package name")?;
+        AdbCommand::new()
+            .shell(serial)
+            .pm()
+            .disable_user(&id, user_id)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::save::PhoneBackup;
+
+    #[test]
+    fn archive_round_trip() {
+        let dir = std::env::temp_dir().join("uad_archive_tests");
+        let _ = fs::remove_dir_all(&dir);
+        let apks = dir.join("staging");
+        fs::create_dir_all(apks.join("com.example")).unwrap();
+        fs::write(apks.join("com.example").join("base.apk"), b"base").unwrap();
+        fs::write(
+            apks.join("com.example").join("split_config.en.apk"),
+            b"split",
+        )
+        .unwrap();
+
+        let archive = dir.join(format!("backup{ARCHIVE_SUFFIX}"));
+        assert!(is_archive(&archive));
+        let backup = PhoneBackup {
+            version: 2,
+            device_id: "abc123".into(),
+            metadata: None,
+            users: vec![],
+        };
+        let json = serde_json::to_string(&backup).unwrap();
+        write_archive(&archive, &json, &apks).unwrap();
+
+        assert_eq!(read_archive_backup(&archive).unwrap(), json);
+        let restore = dir.join("restore");
+        let listed = list_apks(&archive, &restore).unwrap();
+        let mut files = listed["com.example"].clone();
+        files.sort();
+        assert_eq!(files.len(), 2);
+        assert!(!restore.exists());
+        extract_apks(&archive, &restore, &["com.example"]).unwrap();
+        assert_eq!(fs::read(&files[0]).unwrap(), b"base");
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,199 @@
+//! Differences between two versions of the debloat lists,
+//! shown to the user after a list update.
+
+use crate::core::uad_lists::{
+    ListSource, ListSourceKind, PackageHashMap, Removal, UadList, load_debloat_lists,
+};
+
+/// What changed about a single package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Removal { from: Removal, to: Removal },
+    List { from: UadList, to: UadList },
+    Description,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListChange {
+    pub package: String,
+    pub kind: ChangeKind,
+}
+
+impl ListChange {
+    /// `true` if the package is now considered dangerous to remove
+    #[must_use]
+    pub fn promoted_to_unsafe(&self) -> bool {
+        matches!(
+            self.kind,
+            ChangeKind::Removal { from, to: Removal::Unsafe } if from != Removal::Unsafe
+        )
+    }
+}
+
+impl std::fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Added => f.write_str("added"),
+            Self::Removed => f.write_str("removed"),
+            Self::Removal { from, to } => write!(f, "{from} -> {to}"),
+            Self::List { from, to } => write!(f, "list {from} -> {to}"),
+            Self::Description => f.write_str("description changed"),
+        }
+    }
+}
+
+/// Sorted by package name, a package can appear several times
+/// (e.g. when both its list and its removal changed).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListChangelog {
+    pub changes: Vec<ListChange>,
+}
+
+impl ListChangelog {
+    #[must_use]
+    pub fn new(old: &PackageHashMap, new: &PackageHashMap) -> Self {
+        let mut changes = vec![];
+        for (name, package) in new {
+            let Some(previous) = old.get(name) else {
+                changes.push(ListChange {
+                    package: name.clone(),
+                    kind: ChangeKind::Added,
+                });
+                continue;
+            };
+            if previous.removal != package.removal {
+                changes.push(ListChange {
+                    package: name.clone(),
+                    kind: ChangeKind::Removal {
+                        from: previous.removal,
+                        to: package.removal,
+                    },
+                });
+            }
+            if previous.list != package.list {
+                changes.push(ListChange {
+                    package: name.clone(),
+                    kind: ChangeKind::List {
+                        from: previous.list,
+                        to: package.list,
+                    },
+                });
+            }
+            if previous.description.trim() != package.description.trim() {
+                changes.push(ListChange {
+                    package: name.clone(),
+                    kind: ChangeKind::Description,
+                });
+            }
+        }
+        changes.extend(
+            old.keys()
+                .filter(|name| !new.contains_key(*name))
+                .map(|name| ListChange {
+                    package: name.clone(),
+                    kind: ChangeKind::Removed,
+                }),
+        );
+        changes.sort_by(|a, b| a.package.cmp(&b.package));
+        Self { changes }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn promoted_to_unsafe(&self) -> impl Iterator<Item = &ListChange> {
+        self.changes.iter().filter(|c| c.promoted_to_unsafe())
+    }
+
+    /// Every change except promotions to `Unsafe`
+    pub fn others(&self) -> impl Iterator<Item = &ListChange> {
+        self.changes.iter().filter(|c| !c.promoted_to_unsafe())
+    }
+}
+
+/// The lists as they were before downloading new ones,
+/// `None` if some remote source was never downloaded:
+/// a diff against the embedded list would be meaningless.
+#[must_use]
+pub fn previous_lists(sources: &[ListSource]) -> Option<PackageHashMap> {
+    if sources
+        .iter()
+        .any(|s| s.enabled && s.kind == ListSourceKind::Url && !s.cache_path().exists())
+    {
+        return None;
+    }
+    Some(load_debloat_lists(sources, false).unwrap_or_else(|(list, _)| list))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> PackageHashMap {
+        serde_json::from_str(json).expect("valid test list")
+    }
+
+    fn entry(list: &str, description: &str, removal: &str) -> String {
+        format!(
+            r#"{{"list": "{list}", "description": "{description}", "dependencies": [], "neededBy": [], "labels": [], "removal": "{removal}"}}"#
+        )
+    }
+
+    #[test]
+    fn diff_lists() {
+        let old = parse(&format!(
+            r#"{{"a.kept": {}, "a.gone": {}, "a.unsafe": {}, "a.moved": {}}}"#,
+            entry("Oem", "same", "Recommended"),
+            entry("Oem", "bye", "Recommended"),
+            entry("Oem", "old text", "Advanced"),
+            entry("Oem", "same", "Expert"),
+        ));
+        let new = parse(&format!(
+            r#"{{"a.kept": {}, "a.new": {}, "a.unsafe": {}, "a.moved": {}}}"#,
+            entry("Oem", "same ", "Recommended"),
+            entry("Misc", "hi", "Recommended"),
+            entry("Oem", "new text", "Unsafe"),
+            entry("Google", "same", "Expert"),
+        ));
+        let changelog = ListChangelog::new(&old, &new);
+
+        assert_eq!(
+            changelog.changes,
+            vec![
+                ListChange {
+                    package: "a.gone".into(),
+                    kind: ChangeKind::Removed
+                },
+                ListChange {
+                    package: "a.moved".into(),
+                    kind: ChangeKind::List {
+                        from: UadList::Oem,
+                        to: UadList::Google
+                    }
+                },
+                ListChange {
+                    package: "a.new".into(),
+                    kind: ChangeKind::Added
+                },
+                ListChange {
+                    package: "a.unsafe".into(),
+                    kind: ChangeKind::Removal {
+                        from: Removal::Advanced,
+                        to: Removal::Unsafe
+                    }
+                },
+                ListChange {
+                    package: "a.unsafe".into(),
+                    kind: ChangeKind::Description
+                },
+            ]
+        );
+        assert_eq!(changelog.promoted_to_unsafe().count(), 1);
+        assert_eq!(changelog.others().count(), 4);
+        assert!(ListChangelog::new(&new, &new).is_empty());
+    }
+}
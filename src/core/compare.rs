@@ -0,0 +1,314 @@
+//! Differences of package states between two backups,
+//! or between a backup and the device, or between two devices.
+
+use crate::core::adb::UserKind;
+use crate::core::profile::{Profile, UserOverride};
+use crate::core::save::{BackupFile, UserBackup, device_users, read_backup};
+use crate::core::sync::{Phone, device_info};
+use crate::core::uad_lists::{PackageHashMap, PackageState, Removal, UadList};
+use crate::core::utils::fetch_phone_packages;
+use crate::gui::widgets::package_row::PackageRow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// What a comparison is made of
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompareSource {
+    /// Current state of the selected device
+    Device,
+    Backup(Box<BackupFile>),
+}
+
+impl CompareSource {
+    pub fn users(
+        &self,
+        phone: &Phone,
+        phone_packages: &[Vec<PackageRow>],
+    ) -> Result<Vec<UserBackup>, String> {
+        match self {
+            Self::Device => Ok(device_users(phone, phone_packages)),
+            Self::Backup(backup) => read_backup(&backup.path.path).map(|b| b.users),
+        }
+    }
+}
+
+impl fmt::Display for CompareSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Device => f.write_str("Current state of the device"),
+            Self::Backup(backup) => backup.fmt(f),
+        }
+    }
+}
+
+/// A side of a comparison between devices: a connected device, or a backup of any device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompareSide {
+    Device(Phone),
+    Backup(Box<BackupFile>),
+}
+
+impl fmt::Display for CompareSide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Device(phone) => write!(f, "{} ({})", phone.model, phone.adb_id),
+            Self::Backup(backup) => write!(f, "Backup: {backup}"),
+        }
+    }
+}
+
+/// Package states of every user of `side`, querying the device if it is one
+pub async fn load_side(
+    side: CompareSide,
+    uad_lists: PackageHashMap,
+) -> Result<Vec<UserBackup>, String> {
+    match side {
+        CompareSide::Device(phone) => {
+            let phone = device_info(&phone.adb_id);
+            if phone.android_sdk == 0 || phone.user_list.is_empty() {
+                return Err(
+                    "the device can't be queried, is it still connected and authorized?".into(),
+                );
+            }
+            let packages = fetch_phone_packages(&uad_lists, &phone.adb_id, &phone.user_list);
+            Ok(device_users(&phone, &packages))
+        }
+        CompareSide::Backup(backup) => read_backup(&backup.path.path).map(|b| b.users),
+    }
+}
+
+/// The states of `users` as a profile, to make another device match them.
+///
+/// Users are matched by id. Packages missing on the other device can't be installed,
+/// and the ones missing from `users` are left as they are.
+#[must_use]
+pub fn match_profile(name: String, users: &[UserBackup]) -> Profile {
+    Profile {
+        name,
+        description: String::new(),
+        rules: vec![],
+        packages: BTreeMap::new(),
+        users: users
+            .iter()
+            .map(|u| UserOverride {
+                id: u.id,
+                packages: u
+                    .packages
+                    .iter()
+                    .map(|p| (p.name.clone(), p.state))
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+/// A package whose state differs, `None` meaning not installed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageChange {
+    pub package: String,
+    pub from: Option<PackageState>,
+    pub to: Option<PackageState>,
+    pub list: UadList,
+    pub removal: Removal,
+}
+
+impl fmt::Display for PackageChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = |s: Option<PackageState>| {
+            s.map_or_else(|| "Not installed".to_string(), |s| s.to_string())
+        };
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.package,
+            state(self.from),
+            state(self.to)
+        )
+    }
+}
+
+/// Which side of the comparison a user is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Presence {
+    Both,
+    OnlyOld,
+    OnlyNew,
+}
+
+/// Changes of a user, only relevant if it is on both sides
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserChanges {
+    pub user_id: u16,
+    pub kind: UserKind,
+    pub presence: Presence,
+    pub changes: Vec<PackageChange>,
+}
+
+/// Compare `old` and `new` user by user (matched by id), packages sorted by name
+#[must_use]
+pub fn compare_users(
+    old: &[UserBackup],
+    new: &[UserBackup],
+    uad_lists: &PackageHashMap,
+) -> Vec<UserChanges> {
+    let states = |user: &UserBackup| -> BTreeMap<String, PackageState> {
+        user.packages
+            .iter()
+            .map(|p| (p.name.clone(), p.state))
+            .collect()
+    };
+    let ids: BTreeSet<u16> = old.iter().chain(new).map(|u| u.id).collect();
+    ids.into_iter()
+        .map(|id| {
+            let old_user = old.iter().find(|u| u.id == id);
+            let new_user = new.iter().find(|u| u.id == id);
+            let (presence, kind, changes) = match (old_user, new_user) {
+                (Some(o), Some(n)) => {
+                    let (o_states, n_states) = (states(o), states(n));
+                    let names: BTreeSet<&String> = o_states.keys().chain(n_states.keys()).collect();
+                    let changes = names
+                        .into_iter()
+                        .filter_map(|name| {
+                            let (from, to) =
+                                (o_states.get(name).copied(), n_states.get(name).copied());
+                            (from != to).then(|| {
+                                let known = uad_lists.get(name);
+                                PackageChange {
+                                    package: name.clone(),
+                                    from,
+                                    to,
+                                    list: known.map_or(UadList::Unlisted, |p| p.list),
+                                    removal: known.map_or(Removal::Unlisted, |p| p.removal),
+                                }
+                            })
+                        })
+                        .collect();
+                    (Presence::Both, n.kind, changes)
+                }
+                (Some(o), None) => (Presence::OnlyOld, o.kind, vec![]),
+                (None, Some(n)) => (Presence::OnlyNew, n.kind, vec![]),
+                (None, None) => unreachable!("ids come from both sides"),
+            };
+            UserChanges {
+                user_id: id,
+                kind,
+                presence,
+                changes,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::profile::reconcile;
+    use crate::core::sync::{CorePackage, User};
+
+    fn user(id: u16, packages: &[(&str, PackageState)]) -> UserBackup {
+        UserBackup {
+            id,
+            kind: UserKind::default(),
+            packages: packages
+                .iter()
+                .map(|&(name, state)| CorePackage {
+                    name: name.into(),
+                    state,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn changes_per_user() {
+        let old = [
+            user(
+                0,
+                &[
+                    ("com.a", PackageState::Enabled),
+                    ("com.b", PackageState::Disabled),
+                    ("com.gone", PackageState::Enabled),
+                ],
+            ),
+            user(10, &[]),
+        ];
+        let new = [
+            user(
+                0,
+                &[
+                    ("com.a", PackageState::Uninstalled),
+                    ("com.b", PackageState::Disabled),
+                    ("com.new", PackageState::Enabled),
+                ],
+            ),
+            user(11, &[("com.a", PackageState::Enabled)]),
+        ];
+        let users = compare_users(&old, &new, &PackageHashMap::new());
+        let presence: Vec<_> = users.iter().map(|u| (u.user_id, u.presence)).collect();
+        assert_eq!(
+            presence,
+            [
+                (0, Presence::Both),
+                (10, Presence::OnlyOld),
+                (11, Presence::OnlyNew)
+            ]
+        );
+        let changes: Vec<_> = users[0].changes.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            changes,
+            [
+                "com.a: Enabled -> Uninstalled",
+                "com.gone: Enabled -> Not installed",
+                "com.new: Not installed -> Enabled",
+            ]
+        );
+        assert_eq!(users[0].changes[0].list, UadList::Unlisted);
+    }
+
+    #[test]
+    fn b_matches_a() {
+        let a = [user(
+            0,
+            &[
+                ("com.a", PackageState::Uninstalled),
+                ("com.b", PackageState::Disabled),
+                ("com.only_a", PackageState::Enabled),
+            ],
+        )];
+        let row = |name, state| PackageRow::test(name, state, UadList::Oem, Removal::Recommended);
+        let b_packages = vec![vec![
+            row("com.a", PackageState::Enabled),
+            row("com.b", PackageState::Disabled),
+            row("com.only_b", PackageState::Enabled),
+        ]];
+        let b_phone = Phone {
+            model: "B".into(),
+            android_sdk: 34,
+            user_list: vec![User::default()],
+            adb_id: "b".into(),
+        };
+        let b = device_users(&b_phone, &b_packages);
+
+        // from B to A
+        let users = compare_users(&b, &a, &PackageHashMap::new());
+        let changes: Vec<_> = users[0].changes.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            changes,
+            [
+                "com.a: Enabled -> Uninstalled",
+                "com.only_a: Not installed -> Enabled",
+                "com.only_b: Enabled -> Not installed",
+            ]
+        );
+
+        // Only what B has can be changed
+        let profile = match_profile("Match A".into(), &a);
+        let plan = reconcile(&profile, &b_phone, &b_packages, false);
+        let commands: Vec<_> = plan
+            .changes
+            .iter()
+            .map(|c| c.entry.commands[0].as_str())
+            .collect();
+        assert_eq!(commands, ["pm uninstall --user 0 com.a"]);
+    }
+}
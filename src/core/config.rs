@@ -1,5 +1,7 @@
+use crate::core::ota::KnownStates;
+use crate::core::save::{BackupFile, BackupScope, SnapshotRetention};
 use crate::core::sync::User;
-use crate::core::utils::DisplayablePath;
+use crate::core::uad_lists::{ListSource, default_list_sources};
 use crate::gui::views::settings::Settings;
 use crate::CACHE_DIR;
 use crate::CONFIG_DIR;
@@ -21,15 +23,22 @@ pub struct GeneralSettings {
     pub theme: String,
     pub expert_mode: bool,
     pub backup_folder: PathBuf,
+    /// Where debloat lists are loaded from, see [`ListSource`]
+    #[serde(default = "default_list_sources")]
+    pub list_sources: Vec<ListSource>,
+    /// How many automatic snapshots are kept
+    #[serde(default)]
+    pub snapshot_retention: SnapshotRetention,
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct BackupSettings {
-    pub backups: Vec<DisplayablePath>,
-    pub selected: Option<DisplayablePath>,
+    pub backups: Vec<BackupFile>,
+    pub selected: Option<BackupFile>,
     pub users: Vec<User>,
-    pub selected_user: Option<User>,
     pub backup_state: String,
+    /// Which devices `backups` come from
+    pub scope: BackupScope,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -38,6 +47,9 @@ pub struct DeviceSettings {
     pub device_id: String,
     pub disable_mode: bool,
     pub multi_user_mode: bool,
+    /// Package states when the device was last loaded, to detect system updates
+    #[serde(default)]
+    pub known_states: KnownStates,
     #[serde(skip)]
     pub backup: BackupSettings,
 }
@@ -48,6 +60,8 @@ impl Default for GeneralSettings {
             theme: Theme::default().to_string(),
             expert_mode: false,
             backup_folder: CACHE_DIR.join("backups"),
+            list_sources: default_list_sources(),
+            snapshot_retention: SnapshotRetention::default(),
         }
     }
 }
@@ -92,6 +106,10 @@ mod tests {
     //! Unit tests
 
     use super::*;
+    use crate::core::adb::UserKind;
+    use crate::core::save::UserBackup;
+    use crate::core::sync::CorePackage;
+    use crate::core::uad_lists::PackageState;
     use std::path::Path;
 
     // create a clean default config file for testing
@@ -135,6 +153,48 @@ mod tests {
         assert_eq!(config.general.backup_folder, CACHE_DIR.join("backups"));
     }
 
+    #[test]
+    fn test_list_sources_roundtrip() {
+        let mut config = Config::default();
+        config
+            .general
+            .list_sources
+            .push(ListSource::new("/tmp/overlay.json", 10));
+        let toml = toml::to_string(&config).unwrap();
+        let parsed: Config = toml::from_str(&toml).unwrap();
+        assert_eq!(parsed.general.list_sources, config.general.list_sources);
+    }
+
+    #[test]
+    fn test_known_states_roundtrip() {
+        let mut config = Config::default();
+        config.devices.push(DeviceSettings {
+            device_id: "test_device".to_string(),
+            known_states: KnownStates::new(
+                "brand/device:14/AP1A/1:user/release-keys".to_string(),
+                vec![UserBackup {
+                    id: 0,
+                    kind: UserKind::default(),
+                    packages: vec![CorePackage {
+                        name: "com.ads".to_string(),
+                        state: PackageState::Uninstalled,
+                    }],
+                }],
+            ),
+            ..DeviceSettings::default()
+        });
+        let toml = toml::to_string(&config).unwrap();
+        let parsed: Config = toml::from_str(&toml).unwrap();
+        assert_eq!(parsed.devices[0].known_states, config.devices[0].known_states);
+    }
+
+    #[test]
+    fn test_missing_list_sources_defaults_to_upstream() {
+        let toml = "[general]\ntheme = \"Dark\"\nexpert_mode = false\nbackup_folder = \"/tmp\"\n";
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.general.list_sources, default_list_sources());
+    }
+
     #[test]
     fn test_config_file_path() {
         assert_eq!(&*CONFIG_FILE, Path::new(&*CONFIG_DIR.join("config.toml")));
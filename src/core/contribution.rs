@@ -0,0 +1,262 @@
+//! Helpers for contributing unlisted packages to the upstream debloat lists.
+//!
+//! Every package of the device that no list knows about is exported as a
+//! draft entry (in the `uad_lists.json` schema), next to a report of
+//! how much of the device the lists cover.
+
+use crate::core::adb::{ACommand as AdbCommand, PackageDump, PackageId};
+use crate::core::sync::Phone;
+use crate::core::uad_lists::UadList;
+use crate::gui::widgets::package_row::PackageRow;
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+
+/// Device info about a package that isn't in any list
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnlistedPackage {
+    pub name: String,
+    pub apk_paths: Vec<String>,
+    pub dump: PackageDump,
+}
+
+/// How much of the packages of a device are known by the lists
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    pub total: usize,
+    /// Number of packages per list, `Unlisted` included
+    pub per_list: BTreeMap<String, usize>,
+}
+
+impl Coverage {
+    #[must_use]
+    pub fn new(packages: &[PackageRow]) -> Self {
+        let mut per_list = BTreeMap::new();
+        for p in packages {
+            *per_list.entry(p.uad_list.to_string()).or_insert(0) += 1;
+        }
+        Self {
+            total: packages.len(),
+            per_list,
+        }
+    }
+
+    #[must_use]
+    pub fn unlisted(&self) -> usize {
+        self.per_list
+            .get(UadList::Unlisted.as_str())
+            .copied()
+            .unwrap_or(0)
+    }
+
+    #[must_use]
+    pub fn listed(&self) -> usize {
+        self.total - self.unlisted()
+    }
+
+    /// Percentage of listed packages, 100 for an empty device
+    #[must_use]
+    #[expect(clippy::cast_precision_loss, reason = "package counts are small")]
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            100.0
+        } else {
+            self.listed() as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+/// Where an export was written
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContributionExport {
+    pub fragment_file: PathBuf,
+    pub report_file: PathBuf,
+    pub coverage: Coverage,
+}
+
+/// Query the device about every unlisted package of `packages`.
+///
+/// A package whose info can't be retrieved is still exported, without it.
+#[must_use]
+pub fn gather_unlisted(device_serial: &str, packages: &[PackageRow]) -> Vec<UnlistedPackage> {
+    packages
+        .iter()
+        .filter(|p| p.uad_list == UadList::Unlisted)
+        .map(|p| {
+            let Some(id) = PackageId::new(p.name.as_str().into()) else {
+                return UnlistedPackage {
+                    name: p.name.clone(),
+                    ..UnlistedPackage::default()
+                };
+            };
+            let apk_paths = AdbCommand::new()
+                .shell(device_serial)
+                .pm()
+                .path(&id)
+                .unwrap_or_else(|e| {
+                    warn!("[CONTRIBUTION] {}: can't get APK path: {e}", p.name);
+                    vec![]
+                });
+            let dump = AdbCommand::new()
+                .shell(device_serial)
+                .dumpsys_package(&id)
+                .unwrap_or_else(|e| {
+                    warn!("[CONTRIBUTION] {}: can't dump package: {e}", p.name);
+                    PackageDump::default()
+                });
+            UnlistedPackage {
+                name: p.name.clone(),
+                apk_paths,
+                dump,
+            }
+        })
+        .collect()
+}
+
+/// Draft list entries, to be reviewed before being submitted upstream.
+///
+/// Entries go to the `Pending` list with the `Expert` removal,
+/// as nobody has assessed them yet.
+#[must_use]
+pub fn list_fragment(unlisted: &[UnlistedPackage]) -> Value {
+    let entries: Map<String, Value> = unlisted
+        .iter()
+        .map(|p| {
+            let mut description = "TODO: what does this package do?\n".to_string();
+            if let Some(path) = p.apk_paths.first() {
+                let _ = write!(description, "\nAPK: {path}");
+            }
+            if let Some(installer) = &p.dump.installer {
+                let _ = write!(description, "\nInstalled by: {installer}");
+            }
+            (
+                p.name.clone(),
+                json!({
+                    "list": "Pending",
+                    "description": description,
+                    "dependencies": [],
+                    "neededBy": [],
+                    "labels": [],
+                    "removal": "Expert",
+                }),
+            )
+        })
+        .collect();
+    Value::Object(entries)
+}
+
+/// Markdown report, meant to be attached to the upstream contribution
+#[must_use]
+pub fn coverage_report(phone: &Phone, coverage: &Coverage, unlisted: &[UnlistedPackage]) -> String {
+    let mut report = format!(
+        "# Debloat list coverage of {} (Android SDK {})\n\n\
+         {} of {} packages are listed ({:.1}%).\n\n\
+         | List | Packages |\n|---|---|\n",
+        phone.model,
+        phone.android_sdk,
+        coverage.listed(),
+        coverage.total,
+        coverage.percent(),
+    );
+    for (list, count) in &coverage.per_list {
+        let _ = writeln!(report, "| {list} | {count} |");
+    }
+
+    let _ = write!(
+        report,
+        "\n## Unlisted packages\n\n\
+         | Package | Version | Installer | APK | Permissions |\n|---|---|---|---|---|\n"
+    );
+    for p in unlisted {
+        let version = match (&p.dump.version_name, &p.dump.version_code) {
+            (Some(name), Some(code)) => format!("{name} ({code})"),
+            (Some(v), None) | (None, Some(v)) => v.clone(),
+            (None, None) => "?".to_string(),
+        };
+        let _ = writeln!(
+            report,
+            "| {} | {version} | {} | {} | {} |",
+            p.name,
+            p.dump.installer.as_deref().unwrap_or("pre-installed"),
+            p.apk_paths.join("<br>"),
+            p.dump.permissions.join("<br>"),
+        );
+    }
+    report
+}
+
+/// Export the unlisted packages of `packages` (a single user)
+/// in the same directory where UAD-ng is located.
+pub async fn export_contribution(
+    phone: Phone,
+    packages: Vec<PackageRow>,
+) -> Result<ContributionExport, String> {
+    let unlisted = gather_unlisted(&phone.adb_id, &packages);
+    let coverage = Coverage::new(&packages);
+
+    let stem = format!(
+        "contribution_{}_{}",
+        phone
+            .model
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_"),
+        chrono::Local::now().format("%Y%m%d"),
+    );
+    let fragment_file = PathBuf::from(format!("{stem}.json"));
+    let report_file = PathBuf::from(format!("{stem}.md"));
+
+    let fragment =
+        serde_json::to_string_pretty(&list_fragment(&unlisted)).map_err(|e| e.to_string())?;
+    fs::write(&fragment_file, fragment).map_err(|e| e.to_string())?;
+    fs::write(&report_file, coverage_report(&phone, &coverage, &unlisted))
+        .map_err(|e| e.to_string())?;
+
+    Ok(ContributionExport {
+        fragment_file,
+        report_file,
+        coverage,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::lint::{Severity, lint_list};
+    use crate::core::uad_lists::{PackageState, Removal};
+
+    fn row(name: &str, list: UadList) -> PackageRow {
+        PackageRow::test(name, PackageState::Enabled, list, Removal::Recommended)
+    }
+
+    #[test]
+    fn fragment_and_coverage() {
+        let rows = [
+            row("com.listed.a", UadList::Oem),
+            row("com.listed.b", UadList::Google),
+            row("com.listed.c", UadList::Oem),
+            row("com.unknown", UadList::Unlisted),
+        ];
+        let coverage = Coverage::new(&rows);
+        assert_eq!(coverage.listed(), 3);
+        assert_eq!(coverage.unlisted(), 1);
+        assert!((coverage.percent() - 75.0).abs() < f64::EPSILON);
+
+        let unlisted = [UnlistedPackage {
+            name: "com.unknown".into(),
+            apk_paths: vec!["/system/app/Unknown/Unknown.apk".into()],
+            dump: PackageDump {
+                installer: Some("com.android.vending".into()),
+                ..PackageDump::default()
+            },
+        }];
+        let fragment = list_fragment(&unlisted).to_string();
+        // drafts must be accepted as-is by the list linter
+        let errors: Vec<_> = lint_list(&fragment, &[])
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .collect();
+        assert_eq!(errors, vec![]);
+        assert!(fragment.contains("/system/app/Unknown/Unknown.apk"));
+    }
+}
@@ -0,0 +1,249 @@
+//! Fleet mode: the same profile applied to several devices at once.
+//!
+//! Each device is planned on its own (its SDK and users decide the commands),
+//! then its changes are run one after the other, so that its progress can be followed.
+
+use crate::core::journal::{Journal, JournalEntry};
+use crate::core::profile::{Profile, UserOverride, reconcile};
+use crate::core::sync::{Phone, device_info, run_pkg_state_commands};
+use crate::core::uad_lists::{PackageHashMap, PackageState};
+use crate::core::utils::fetch_phone_packages;
+use crate::gui::widgets::package_row::PackageRow;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Changes needed to bring a device to a profile
+#[derive(Debug, Clone)]
+pub struct DevicePlan {
+    pub phone: Phone,
+    /// Packages of every user, for the snapshot taken before applying
+    pub packages: Vec<Vec<PackageRow>>,
+    pub changes: Vec<JournalEntry>,
+    /// `Unsafe` packages, which can't be changed outside of expert mode
+    pub unsafe_skipped: Vec<String>,
+}
+
+/// Query the device `serial` and reconcile it with `profile`
+pub async fn plan_device(
+    serial: String,
+    profile: Profile,
+    uad_lists: PackageHashMap,
+    expert_mode: bool,
+) -> Result<DevicePlan, String> {
+    let phone = device_info(&serial);
+    if phone.android_sdk == 0 || phone.user_list.is_empty() {
+        return Err("the device can't be queried, is it still connected and authorized?".into());
+    }
+    let packages = fetch_phone_packages(&uad_lists, &serial, &phone.user_list);
+    let plan = reconcile(&profile, &phone, &packages, expert_mode);
+    Ok(DevicePlan {
+        changes: plan.changes.into_iter().map(|c| c.entry).collect(),
+        unsafe_skipped: plan.unsafe_skipped,
+        phone,
+        packages,
+    })
+}
+
+/// Run the commands of a change
+pub async fn run_change(serial: String, commands: Vec<String>) -> Result<(), String> {
+    run_pkg_state_commands(&serial, &commands)
+}
+
+/// Journal the `changes` made on `serial`, so that they can be undone
+pub fn journal_changes(serial: &str, changes: &[JournalEntry]) -> Result<(), String> {
+    let mut journal = Journal::load(serial);
+    for change in changes {
+        journal.record(change.clone());
+    }
+    journal.save(serial)
+}
+
+/// A selection turned into a profile: the selected `(user_id, package, wanted state)`,
+/// or every user's if `all_users`
+#[must_use]
+pub fn selection_profile(selection: &[(u16, String, PackageState)], all_users: bool) -> Profile {
+    let mut packages = BTreeMap::new();
+    let mut users: BTreeMap<u16, BTreeMap<String, PackageState>> = BTreeMap::new();
+    for (user_id, name, state) in selection {
+        if all_users {
+            packages.insert(name.clone(), *state);
+        } else {
+            users
+                .entry(*user_id)
+                .or_default()
+                .insert(name.clone(), *state);
+        }
+    }
+    Profile {
+        name: "Current selection".to_string(),
+        description: String::new(),
+        rules: vec![],
+        packages,
+        users: users
+            .into_iter()
+            .map(|(id, user_packages)| UserOverride {
+                id,
+                packages: user_packages,
+            })
+            .collect(),
+    }
+}
+
+/// Outcome of a device, in the report of a fleet
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceResult {
+    pub serial: String,
+    pub model: String,
+    pub android_sdk: u8,
+    pub applied: usize,
+    /// Changes which failed
+    pub failed: Vec<JournalEntry>,
+    pub unsafe_skipped: Vec<String>,
+    /// Why the device couldn't be planned or changed at all
+    pub error: Option<String>,
+}
+
+impl DeviceResult {
+    /// Outcome of the applied `plan`, whose changes hold their error
+    #[must_use]
+    pub fn new(plan: &DevicePlan) -> Self {
+        let (failed, applied): (Vec<_>, Vec<_>) =
+            plan.changes.iter().partition(|c| c.error.is_some());
+        Self {
+            serial: plan.phone.adb_id.clone(),
+            model: plan.phone.model.clone(),
+            android_sdk: plan.phone.android_sdk,
+            applied: applied.len(),
+            failed: failed.into_iter().cloned().collect(),
+            unsafe_skipped: plan.unsafe_skipped.clone(),
+            error: None,
+        }
+    }
+
+    /// A device which couldn't be planned or changed
+    #[must_use]
+    pub fn failed(phone: &Phone, error: String) -> Self {
+        Self {
+            serial: phone.adb_id.clone(),
+            model: phone.model.clone(),
+            android_sdk: phone.android_sdk,
+            error: Some(error),
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none() && self.failed.is_empty()
+    }
+}
+
+/// Combined report of a fleet
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FleetReport {
+    pub generated: String,
+    pub profile: String,
+    pub devices: usize,
+    /// Devices without any failure
+    pub succeeded: usize,
+    pub applied: usize,
+    pub failed: usize,
+    pub results: Vec<DeviceResult>,
+}
+
+impl FleetReport {
+    #[must_use]
+    pub fn new(profile: &str, results: Vec<DeviceResult>) -> Self {
+        Self {
+            generated: chrono::Local::now().to_rfc3339(),
+            profile: profile.to_string(),
+            devices: results.len(),
+            succeeded: results.iter().filter(|r| r.is_ok()).count(),
+            applied: results.iter().map(|r| r.applied).sum(),
+            failed: results.iter().map(|r| r.failed.len()).sum(),
+            results,
+        }
+    }
+}
+
+/// Write `report` as JSON in the same directory where UAD-ng is located
+pub async fn export_fleet_report(report: FleetReport) -> Result<PathBuf, String> {
+    let path = PathBuf::from(format!(
+        "fleet_report_{}.json",
+        chrono::Local::now().format("%Y%m%d_%H%M%S")
+    ));
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::journal::JournalAction;
+    use crate::core::sync::User;
+    use crate::core::uad_lists::{Removal, UadList};
+
+    #[test]
+    fn selection_planned_per_device() {
+        let selection = [
+            (0, "com.a".to_string(), PackageState::Uninstalled),
+            (10, "com.b".to_string(), PackageState::Disabled),
+        ];
+        let profile = selection_profile(&selection, false);
+        assert!(profile.packages.is_empty());
+        assert_eq!(profile.users.len(), 2);
+
+        let row = |name, state| PackageRow::test(name, state, UadList::Oem, Removal::Recommended);
+        let device = |adb_id: &str, android_sdk| Phone {
+            model: "Model".into(),
+            android_sdk,
+            user_list: vec![User::default()],
+            adb_id: adb_id.into(),
+        };
+        let packages = vec![vec![
+            row("com.a", PackageState::Enabled),
+            row("com.b", PackageState::Enabled),
+        ]];
+        let new = device("new", 34);
+        let old = device("old", 22);
+        let commands = |phone: &Phone| {
+            reconcile(&profile, phone, &packages, false)
+                .changes
+                .into_iter()
+                .map(|c| c.entry.commands[0].clone())
+                .collect::<Vec<_>>()
+        };
+        // `com.b` is only selected for user 10
+        assert_eq!(commands(&new), ["pm uninstall --user 0 com.a"]);
+        assert_eq!(commands(&old), ["pm hide --user 0 com.a"]);
+
+        let mut failed = JournalEntry::plan(
+            &new,
+            "com.a",
+            0,
+            PackageState::Enabled,
+            PackageState::Uninstalled,
+            JournalAction::Change,
+        )
+        .unwrap();
+        failed.error = Some("Failure".into());
+        let plan = DevicePlan {
+            phone: new.clone(),
+            packages: packages.clone(),
+            changes: vec![failed],
+            unsafe_skipped: vec![],
+        };
+        let report = FleetReport::new(
+            &profile.name,
+            vec![
+                DeviceResult::new(&plan),
+                DeviceResult::failed(&old, "unplugged".into()),
+            ],
+        );
+        assert_eq!((report.devices, report.succeeded), (2, 0));
+        assert_eq!((report.applied, report.failed), (0, 1));
+    }
+}
@@ -0,0 +1,296 @@
+//! Per-device journal of the package state changes made by UAD-ng.
+//!
+//! Successful changes can be undone (and redone) one at a time,
+//! which is finer than restoring a whole backup.
+
+use crate::core::sync::{CorePackage, Phone, apply_pkg_state_commands};
+use crate::core::uad_lists::PackageState;
+use crate::core::utils::write_atomic;
+use crate::{CACHE_DIR, CONFIG_DIR};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// Why a change was made
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum JournalAction {
+    /// Requested from the package list
+    #[default]
+    Change,
+    Undo,
+    Redo,
+    /// Made by restoring a backup
+    Restore,
+}
+
+impl fmt::Display for JournalAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Change => "change",
+            Self::Undo => "undo",
+            Self::Redo => "redo",
+            Self::Restore => "restore",
+        })
+    }
+}
+
+/// A state change of a package, for a single user
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// RFC 3339, local time
+    pub timestamp: String,
+    pub package: String,
+    pub user_id: u16,
+    pub from: PackageState,
+    pub to: PackageState,
+    pub commands: Vec<String>,
+    pub action: JournalAction,
+    /// `None` if the change succeeded
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl JournalEntry {
+    /// Plan the change of `package` from `from` to `to` on `phone`,
+    /// `None` if the user is gone.
+    #[must_use]
+    pub fn plan(
+        phone: &Phone,
+        package: &str,
+        user_id: u16,
+        from: PackageState,
+        to: PackageState,
+        action: JournalAction,
+    ) -> Option<Self> {
+        let user = phone.user_list.iter().find(|u| u.id == user_id)?;
+        let core_package = CorePackage {
+            name: package.to_string(),
+            state: from,
+        };
+        Some(Self {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            package: package.to_string(),
+            user_id,
+            from,
+            to,
+            commands: apply_pkg_state_commands(&core_package, to, *user, phone),
+            action,
+            error: None,
+        })
+    }
+}
+
+impl fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (user {}): {} -> {}",
+            self.package, self.user_id, self.from, self.to
+        )
+    }
+}
+
+/// All changes made on a device, oldest first
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Journal {
+    pub entries: Vec<JournalEntry>,
+    /// `entries` indexes of the changes that can be undone, most recent last
+    undo: Vec<usize>,
+    /// `entries` indexes of the undone changes, most recently undone last
+    redo: Vec<usize>,
+}
+
+impl Journal {
+    /// Kept with the config rather than the cache, which cleaners may wipe
+    fn path(device_id: &str) -> PathBuf {
+        CONFIG_DIR.join("journal").join(format!("{device_id}.json"))
+    }
+
+    /// Where journals were kept before, still read until the journal is saved again
+    fn legacy_path(device_id: &str) -> PathBuf {
+        CACHE_DIR.join("journal").join(format!("{device_id}.json"))
+    }
+
+    /// Journal of `device_id`, empty if there is none (yet)
+    #[must_use]
+    pub fn load(device_id: &str) -> Self {
+        let Ok(data) = fs::read_to_string(Self::path(device_id))
+            .or_else(|_| fs::read_to_string(Self::legacy_path(device_id)))
+        else {
+            return Self::default();
+        };
+        serde_json::from_str(&data).unwrap_or_else(|e| {
+            error!("[JOURNAL] Invalid journal of {device_id}, starting a new one: {e}");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, device_id: &str) -> Result<(), String> {
+        let path = Self::path(device_id);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        write_atomic(path, json).map_err(|e| e.to_string())
+    }
+
+    /// Add a finished change, failed changes don't affect undo/redo
+    pub fn record(&mut self, entry: JournalEntry) {
+        let index = self.entries.len();
+        let succeeded = entry.error.is_none();
+        let action = entry.action;
+        self.entries.push(entry);
+        if !succeeded {
+            return;
+        }
+        match action {
+            JournalAction::Change | JournalAction::Restore => {
+                self.undo.push(index);
+                self.redo.clear();
+            }
+            JournalAction::Undo => {
+                if let Some(undone) = self.undo.pop() {
+                    self.redo.push(undone);
+                }
+            }
+            JournalAction::Redo => {
+                self.redo.pop();
+                self.undo.push(index);
+            }
+        }
+    }
+
+    /// The change that would be undone next
+    #[must_use]
+    pub fn undo_target(&self) -> Option<&JournalEntry> {
+        self.entries.get(*self.undo.last()?)
+    }
+
+    /// The change that would be redone next
+    #[must_use]
+    pub fn redo_target(&self) -> Option<&JournalEntry> {
+        self.entries.get(*self.redo.last()?)
+    }
+
+    /// The inverse of [`Self::undo_target`]
+    #[must_use]
+    pub fn plan_undo(&self, phone: &Phone) -> Option<JournalEntry> {
+        let target = self.undo_target()?;
+        JournalEntry::plan(
+            phone,
+            &target.package,
+            target.user_id,
+            target.to,
+            target.from,
+            JournalAction::Undo,
+        )
+    }
+
+    /// [`Self::redo_target`], made again
+    #[must_use]
+    pub fn plan_redo(&self, phone: &Phone) -> Option<JournalEntry> {
+        let target = self.redo_target()?;
+        JournalEntry::plan(
+            phone,
+            &target.package,
+            target.user_id,
+            target.from,
+            target.to,
+            JournalAction::Redo,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::sync::User;
+
+    fn phone() -> Phone {
+        Phone {
+            model: "Model".into(),
+            android_sdk: 34,
+            user_list: vec![User {
+                id: 0,
+                ..User::default()
+            }],
+            adb_id: "abc123".into(),
+        }
+    }
+
+    fn change(package: &str, from: PackageState, to: PackageState) -> JournalEntry {
+        JournalEntry::plan(&phone(), package, 0, from, to, JournalAction::Change).unwrap()
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let phone = phone();
+        let mut journal = Journal::default();
+        journal.record(change(
+            "com.a",
+            PackageState::Enabled,
+            PackageState::Uninstalled,
+        ));
+        journal.record(JournalEntry {
+            error: Some("Failure".into()),
+            ..change("com.b", PackageState::Enabled, PackageState::Disabled)
+        });
+        assert_eq!(journal.undo_target().unwrap().package, "com.a");
+
+        let undo = journal.plan_undo(&phone).unwrap();
+        assert_eq!(undo.from, PackageState::Uninstalled);
+        assert_eq!(undo.to, PackageState::Enabled);
+        assert_eq!(
+            undo.commands,
+            ["cmd package install-existing --user 0 com.a"]
+        );
+        journal.record(undo);
+        assert_eq!(journal.undo_target(), None);
+
+        let redo = journal.plan_redo(&phone).unwrap();
+        assert_eq!(redo.commands, ["pm uninstall --user 0 com.a"]);
+        journal.record(redo);
+        assert_eq!(journal.redo_target(), None);
+        assert_eq!(journal.undo_target().unwrap().action, JournalAction::Redo);
+
+        // a new change can't be followed by a redo
+        journal.record(journal.plan_undo(&phone).unwrap());
+        journal.record(change(
+            "com.c",
+            PackageState::Enabled,
+            PackageState::Disabled,
+        ));
+        assert_eq!(journal.redo_target(), None);
+        assert_eq!(journal.entries.len(), 6);
+
+        // restores are undone like any other change
+        journal.record(JournalEntry {
+            action: JournalAction::Restore,
+            ..change("com.d", PackageState::Disabled, PackageState::Enabled)
+        });
+        assert_eq!(
+            journal.plan_undo(&phone).unwrap().to,
+            PackageState::Disabled
+        );
+    }
+
+    #[test]
+    fn unknown_sdk_plans_no_reinstall() {
+        // e.g. an offline backup without metadata
+        let phone = Phone {
+            android_sdk: 0,
+            ..phone()
+        };
+        let entry = JournalEntry::plan(
+            &phone,
+            "com.a",
+            0,
+            PackageState::Uninstalled,
+            PackageState::Enabled,
+            JournalAction::Change,
+        )
+        .unwrap();
+        assert!(entry.commands.is_empty());
+    }
+}
@@ -0,0 +1,436 @@
+//! Validation of `uad_lists.json`-format files,
+//! so that custom (overlay) lists can be checked before deploying them.
+
+use crate::core::adb::PackageId;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// Values of `list` that can appear in a list file
+pub const KNOWN_LISTS: [&str; 6] = ["Aosp", "Carrier", "Google", "Misc", "Oem", "Pending"];
+/// Values of `removal` that can appear in a list file
+pub const KNOWN_REMOVALS: [&str; 4] = ["Recommended", "Advanced", "Expert", "Unsafe"];
+const KNOWN_FIELDS: [&str; 6] = [
+    "list",
+    "description",
+    "dependencies",
+    "neededBy",
+    "labels",
+    "removal",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable identifier of the check, e.g. `dangling-dependency`
+    pub code: &'static str,
+    /// `None` for file-level diagnostics
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{severity}[{}]", self.code)?;
+        if let Some(p) = &self.package {
+            write!(f, " {p}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Package IDs which are valid on devices, despite not following the
+/// [naming rules](https://developer.android.com/build/configure-app-module#set-application-id)
+const SPECIAL_PACKAGES: [&str; 1] = ["android"];
+
+struct Linter {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter {
+    fn push(&mut self, severity: Severity, code: &'static str, package: &str, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            code,
+            package: Some(package.to_string()),
+            message,
+        });
+    }
+}
+
+/// Validate the content of a list file.
+///
+/// `references` are other lists (e.g. upstream, for an overlay)
+/// that `dependencies`/`neededBy` may point to.
+/// Diagnostics are sorted by package, then severity.
+#[must_use]
+pub fn lint_list(text: &str, references: &[&str]) -> Vec<Diagnostic> {
+    let root: Map<String, Value> = match serde_json::from_str(text) {
+        Ok(Value::Object(o)) => o,
+        Ok(_) => {
+            return vec![file_error(
+                "not-an-object",
+                "the list must be a JSON object",
+            )];
+        }
+        Err(e) => return vec![file_error("invalid-json", &e.to_string())],
+    };
+    let mut known: HashSet<&str> = root.keys().map(String::as_str).collect();
+    let references: Vec<Map<String, Value>> = references
+        .iter()
+        .filter_map(|r| match serde_json::from_str(r) {
+            Ok(Value::Object(o)) => Some(o),
+            _ => None,
+        })
+        .collect();
+    known.extend(references.iter().flat_map(|r| r.keys().map(String::as_str)));
+
+    let mut linter = Linter {
+        diagnostics: vec![],
+    };
+    // edges of this file only, references are assumed to be valid
+    let mut dependencies: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    let mut needed_by: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+
+    for (name, entry) in &root {
+        if PackageId::new(name.as_str().into()).is_none() && !SPECIAL_PACKAGES.contains(&&**name) {
+            linter.push(
+                Severity::Error,
+                "invalid-package-id",
+                name,
+                "not a valid package name".to_string(),
+            );
+        }
+        let Some(entry) = entry.as_object() else {
+            linter.push(
+                Severity::Error,
+                "invalid-entry",
+                name,
+                "entry must be an object".to_string(),
+            );
+            continue;
+        };
+        check_fields(&mut linter, name, entry);
+        let _ = string_array(&mut linter, name, entry, "labels");
+        for (field, edges) in [
+            ("dependencies", &mut dependencies),
+            ("neededBy", &mut needed_by),
+        ] {
+            let Some(targets) = string_array(&mut linter, name, entry, field) else {
+                continue;
+            };
+            for target in &targets {
+                if !known.contains(target) {
+                    linter.push(
+                        Severity::Error,
+                        if field == "dependencies" {
+                            "dangling-dependency"
+                        } else {
+                            "dangling-needed-by"
+                        },
+                        name,
+                        format!("`{field}` references unknown package `{target}`"),
+                    );
+                }
+            }
+            edges.insert(name, targets);
+        }
+    }
+
+    check_symmetry(&mut linter, &root, &references, &dependencies, &needed_by);
+    check_cycles(&mut linter, &dependencies);
+
+    let mut diagnostics = linter.diagnostics;
+    diagnostics
+        .sort_by(|a, b| (&a.package, a.severity, a.code).cmp(&(&b.package, b.severity, b.code)));
+    diagnostics
+}
+
+/// Known fields, `list`/`removal` values and `description`
+fn check_fields(linter: &mut Linter, name: &str, entry: &Map<String, Value>) {
+    for field in entry.keys() {
+        if !KNOWN_FIELDS.contains(&field.as_str()) {
+            linter.push(
+                Severity::Warning,
+                "unknown-field",
+                name,
+                format!("unknown field `{field}`"),
+            );
+        }
+    }
+    check_enum(linter, name, entry, "list", &KNOWN_LISTS, "unknown-list");
+    check_enum(
+        linter,
+        name,
+        entry,
+        "removal",
+        &KNOWN_REMOVALS,
+        "unknown-removal",
+    );
+    match entry.get("description") {
+        Some(Value::String(d)) if d.trim().is_empty() => linter.push(
+            Severity::Warning,
+            "empty-description",
+            name,
+            "description is empty".to_string(),
+        ),
+        Some(Value::String(_)) => (),
+        Some(_) => linter.push(
+            Severity::Error,
+            "invalid-field-type",
+            name,
+            "`description` must be a string".to_string(),
+        ),
+        None => missing_field(linter, name, "description"),
+    }
+}
+
+fn file_error(code: &'static str, message: &str) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        code,
+        package: None,
+        message: message.to_string(),
+    }
+}
+
+fn missing_field(linter: &mut Linter, name: &str, field: &str) {
+    linter.push(
+        Severity::Error,
+        "missing-field",
+        name,
+        format!("missing field `{field}`"),
+    );
+}
+
+fn check_enum(
+    linter: &mut Linter,
+    name: &str,
+    entry: &Map<String, Value>,
+    field: &str,
+    known: &[&str],
+    code: &'static str,
+) {
+    match entry.get(field) {
+        Some(Value::String(v)) if known.contains(&v.as_str()) => (),
+        Some(v) => linter.push(
+            Severity::Error,
+            code,
+            name,
+            format!("`{field}` is {v}, expected one of {}", known.join(", ")),
+        ),
+        None => missing_field(linter, name, field),
+    }
+}
+
+/// `Some` if `field` is an array of strings, diagnostics are pushed otherwise
+fn string_array<'a>(
+    linter: &mut Linter,
+    name: &str,
+    entry: &'a Map<String, Value>,
+    field: &str,
+) -> Option<Vec<&'a str>> {
+    let Some(value) = entry.get(field) else {
+        missing_field(linter, name, field);
+        return None;
+    };
+    let strings: Option<Vec<&str>> = value
+        .as_array()
+        .and_then(|a| a.iter().map(Value::as_str).collect());
+    if strings.is_none() {
+        linter.push(
+            Severity::Error,
+            "invalid-field-type",
+            name,
+            format!("`{field}` must be an array of strings"),
+        );
+    }
+    strings
+}
+
+/// `A.dependencies` contains `B` <=> `B.neededBy` contains `A`
+fn check_symmetry(
+    linter: &mut Linter,
+    root: &Map<String, Value>,
+    references: &[Map<String, Value>],
+    dependencies: &BTreeMap<&str, Vec<&str>>,
+    needed_by: &BTreeMap<&str, Vec<&str>>,
+) {
+    let edges_of = |package: &str, field: &str| -> Option<Vec<String>> {
+        std::iter::once(root)
+            .chain(references)
+            .find_map(|list| list.get(package))
+            .and_then(|entry| entry.get(field))
+            .and_then(Value::as_array)
+            .map(|a| {
+                a.iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            })
+    };
+    for (forward, backward, edges) in [
+        ("dependencies", "neededBy", dependencies),
+        ("neededBy", "dependencies", needed_by),
+    ] {
+        for (name, targets) in edges {
+            for target in targets {
+                if edges_of(target, backward).is_some_and(|back| !back.iter().any(|b| b == name)) {
+                    linter.push(
+                        Severity::Warning,
+                        "asymmetric-edge",
+                        name,
+                        format!("`{forward}` has `{target}`, but its `{backward}` lacks `{name}`"),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Report each dependency cycle once, starting from its smallest package.
+/// Lists are small and have few edges, so a naive search is enough.
+fn check_cycles(linter: &mut Linter, dependencies: &BTreeMap<&str, Vec<&str>>) {
+    let mut reported: BTreeSet<Vec<&str>> = BTreeSet::new();
+    for &start in dependencies.keys() {
+        let mut path = vec![start];
+        find_cycles(start, dependencies, &mut path, &mut reported);
+    }
+    for cycle in reported {
+        let description = cycle
+            .iter()
+            .chain(std::iter::once(&cycle[0]))
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        linter.push(
+            Severity::Error,
+            "dependency-cycle",
+            cycle[0],
+            format!("dependency cycle: {description}"),
+        );
+    }
+}
+
+fn find_cycles<'a>(
+    start: &'a str,
+    dependencies: &BTreeMap<&'a str, Vec<&'a str>>,
+    path: &mut Vec<&'a str>,
+    reported: &mut BTreeSet<Vec<&'a str>>,
+) {
+    let current = path[path.len() - 1];
+    for &next in dependencies.get(current).into_iter().flatten() {
+        if next == start {
+            reported.insert(path.clone());
+        } else if next > start && !path.contains(&next) {
+            // only visiting bigger packages finds each cycle from its smallest one
+            path.push(next);
+            find_cycles(start, dependencies, path, reported);
+            path.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::uad_lists::DATA;
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<(&str, Option<&str>)> {
+        diagnostics
+            .iter()
+            .map(|d| (d.code, d.package.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn embedded_list_has_no_structural_errors() {
+        let diagnostics = lint_list(DATA, &[]);
+        assert!(diagnostics.iter().all(|d| d.code != "invalid-json"
+            && d.code != "missing-field"
+            && d.code != "unknown-list"
+            && d.code != "unknown-removal"));
+    }
+
+    #[test]
+    fn invalid_json() {
+        assert_eq!(codes(&lint_list("{", &[])), [("invalid-json", None)]);
+        assert_eq!(codes(&lint_list("[]", &[])), [("not-an-object", None)]);
+    }
+
+    #[test]
+    fn entry_checks() {
+        let list = r#"{
+            "com.example.ok": {"list": "Oem", "description": "fine", "dependencies": [], "neededBy": [], "labels": [], "removal": "Advanced"},
+            "bad..id": {"list": "Nope", "description": " ", "dependencies": [], "neededBy": [], "labels": [], "removal": "Maybe", "extra": 1},
+            "com.example.missing": {"list": "Oem", "dependencies": "x", "neededBy": [], "labels": []},
+            "android": {"list": "Aosp", "description": "core", "dependencies": [], "neededBy": [], "labels": [], "removal": "Unsafe"}
+        }"#;
+        assert_eq!(
+            codes(&lint_list(list, &[])),
+            [
+                ("invalid-package-id", Some("bad..id")),
+                ("unknown-list", Some("bad..id")),
+                ("unknown-removal", Some("bad..id")),
+                ("empty-description", Some("bad..id")),
+                ("unknown-field", Some("bad..id")),
+                ("invalid-field-type", Some("com.example.missing")),
+                ("missing-field", Some("com.example.missing")),
+                ("missing-field", Some("com.example.missing")),
+            ]
+        );
+    }
+
+    #[test]
+    fn dependency_checks() {
+        let entry = |deps: &str, needed_by: &str| {
+            format!(
+                r#"{{"list": "Oem", "description": "d", "dependencies": [{deps}], "neededBy": [{needed_by}], "labels": [], "removal": "Advanced"}}"#
+            )
+        };
+        let list = format!(
+            r#"{{"a.a": {}, "b.b": {}, "c.c": {}, "d.d": {}}}"#,
+            entry(r#""b.b""#, r#""c.c""#),
+            entry(r#""c.c""#, r#""a.a""#),
+            entry(r#""a.a", "x.x""#, r#""b.b""#),
+            entry(r#""up.stream""#, ""),
+        );
+        let diagnostics = lint_list(&list, &[]);
+        assert_eq!(
+            codes(&diagnostics),
+            [
+                ("dependency-cycle", Some("a.a")),
+                ("dangling-dependency", Some("c.c")),
+                ("dangling-dependency", Some("d.d")),
+            ]
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "dependency cycle: a.a -> b.b -> c.c -> a.a"
+        );
+
+        // references resolve dangling edges, and their edges are checked for symmetry
+        let upstream = r#"{"up.stream": {"list": "Oem", "description": "d", "dependencies": [], "neededBy": [], "labels": [], "removal": "Advanced"}}"#;
+        let with_reference = lint_list(&list, &[upstream]);
+        assert_eq!(
+            codes(&with_reference),
+            [
+                ("dependency-cycle", Some("a.a")),
+                ("dangling-dependency", Some("c.c")),
+                ("asymmetric-edge", Some("d.d")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! Systemless removal of system packages, as a [Magisk](https://topjohnwu.github.io/Magisk/guides.html) module.
+//!
+//! The module replaces the APK directories of the packages by empty ones,
+//! so they stay removed after a factory-reset of `/data`, and come back when the module is removed.
+//! Only the APK paths come from the device: the zip itself is built offline.
+//!
+//! Zip layout:
+//! - `module.prop`
+//! - `customize.sh`: the `REPLACE` list
+//! - `META-INF/com/google/android/{update-binary,updater-script}`: the standard Magisk installer
+
+use crate::core::adb::{ACommand as AdbCommand, PackageId};
+use crate::core::sync::Phone;
+use crate::core::utils::NAME;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{Seek, Write};
+use std::path::PathBuf;
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+/// Partitions which Magisk overlays through `/system`
+const PARTITIONS: [&str; 4] = ["/system/", "/product/", "/system_ext/", "/vendor/"];
+
+/// The installer of every Magisk module
+const UPDATE_BINARY: &str = r#"#!/sbin/sh
+
+umask 022
+
+ui_print() { echo "$1"; }
+
+require_new_magisk() {
+  ui_print "*******************************"
+  ui_print " Please install Magisk v20.4+! "
+  ui_print "*******************************"
+  exit 1
+}
+
+OUTFD=$2
+ZIPFILE=$3
+
+mount /data 2>/dev/null
+
+[ -f /data/adb/magisk/util_functions.sh ] || require_new_magisk
+. /data/adb/magisk/util_functions.sh
+[ $MAGISK_VER_CODE -lt 20400 ] && require_new_magisk
+
+install_module
+exit 0
+"#;
+
+/// A package and the paths of its APKs on the device, as given by `pm path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModulePackage {
+    pub name: String,
+    pub apks: Result<Vec<String>, String>,
+}
+
+/// Query the paths of the APKs of `packages`
+#[must_use]
+pub fn gather_apk_paths(serial: &str, packages: Vec<String>) -> Vec<ModulePackage> {
+    packages
+        .into_iter()
+        .map(|name| {
+            let apks = PackageId::new(name.as_str().into())
+                .ok_or_else(|| "invalid package name".to_string())
+                .and_then(|id| AdbCommand::new().shell(serial).pm().path(&id));
+            ModulePackage { name, apks }
+        })
+        .collect()
+}
+
+/// Directories replaced by a module
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModulePlan {
+    /// `REPLACE` entries, with the package they remove
+    pub replaced: Vec<(String, String)>,
+    /// Package and why it can't be removed systemlessly
+    pub skipped: Vec<(String, String)>,
+}
+
+/// The directory to replace to remove the APK at `path`, as seen by Magisk.
+///
+/// Only dedicated app directories are replaced: replacing e.g. `/system/framework`
+/// would remove much more than the package.
+fn replace_target(path: &str) -> Result<String, String> {
+    let partition = PARTITIONS
+        .iter()
+        .find(|p| path.starts_with(*p))
+        .ok_or("not a system package (or an update of one)")?;
+    // `<partition>/(priv-)app/<dir>/<file>.apk`
+    let dir = path[partition.len()..]
+        .rsplit_once('/')
+        .map(|(dir, _)| dir)
+        .filter(|dir| {
+            let mut parts = dir.split('/');
+            matches!(parts.next(), Some("app" | "priv-app"))
+                && parts.next().is_some_and(|d| !d.is_empty() && d != "..")
+                && parts.next().is_none()
+        })
+        .ok_or_else(|| format!("APK not in its own app directory: {path}"))?;
+    Ok(if *partition == "/system/" {
+        format!("/system/{dir}")
+    } else {
+        format!("/system{partition}{dir}")
+    })
+}
+
+/// Offline part of the module: what to replace
+#[must_use]
+pub fn plan_module(packages: &[ModulePackage]) -> ModulePlan {
+    let mut plan = ModulePlan::default();
+    for package in packages {
+        let targets: Result<Vec<String>, String> = match &package.apks {
+            Ok(apks) if apks.is_empty() => Err("no APK found on the device".to_string()),
+            Ok(apks) => apks.iter().map(|apk| replace_target(apk)).collect(),
+            Err(e) => Err(e.clone()),
+        };
+        match targets {
+            Ok(mut targets) => {
+                // split APKs share the directory of the base one
+                targets.sort_unstable();
+                targets.dedup();
+                for target in targets {
+                    if !plan.replaced.iter().any(|(t, _)| *t == target) {
+                        plan.replaced.push((target, package.name.clone()));
+                    }
+                }
+            }
+            Err(e) => plan.skipped.push((package.name.clone(), e)),
+        }
+    }
+    plan
+}
+
+/// Write the module of `plan` as a zip
+pub fn write_module<W: Write + Seek>(
+    writer: W,
+    phone: &Phone,
+    plan: &ModulePlan,
+) -> zip::result::ZipResult<W> {
+    let id = format!(
+        "uad_ng_{}",
+        phone
+            .model
+            .to_lowercase()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    );
+    let version_code = chrono::Local::now().format("%Y%m%d").to_string();
+    let module_prop = format!(
+        "id={id}\n\
+        name={NAME} debloat ({})\n\
+        version=v{version_code}\n\
+        versionCode={version_code}\n\
+        author={NAME}\n\
+        description=Systemlessly removes {} system package(s), selected in {NAME} v{}\n",
+        phone.model.replace('\n', " "),
+        plan.replaced.len(),
+        env!("CARGO_PKG_VERSION"),
+    );
+
+    let mut customize = format!(
+        "# Generated by {NAME} for {} (Android SDK {})\n#\n",
+        phone.model, phone.android_sdk
+    );
+    // `#` isn't a comment inside the quoted list: packages are named above it
+    for (target, package) in &plan.replaced {
+        let _ = writeln!(customize, "# {package}: {target}");
+    }
+    customize.push_str("\nREPLACE=\"\n");
+    for (target, _) in &plan.replaced {
+        let _ = writeln!(customize, "{target}");
+    }
+    customize.push_str("\"\n");
+
+    let mut zip = ZipWriter::new(writer);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for (name, content) in [
+        ("module.prop", module_prop.as_str()),
+        ("customize.sh", &customize),
+        ("META-INF/com/google/android/update-binary", UPDATE_BINARY),
+        ("META-INF/com/google/android/updater-script", "#MAGISK\n"),
+    ] {
+        zip.start_file(name, options.unix_permissions(0o644))?;
+        zip.write_all(content.as_bytes())?;
+    }
+    zip.finish()
+}
+
+/// Where a module was written, and what it removes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleExport {
+    pub path: PathBuf,
+    pub plan: ModulePlan,
+}
+
+/// Build the module removing `packages` in the same directory where UAD-ng is located
+pub async fn export_module(phone: Phone, packages: Vec<String>) -> Result<ModuleExport, String> {
+    let plan = plan_module(&gather_apk_paths(&phone.adb_id, packages));
+    if plan.replaced.is_empty() {
+        return Err("none of the selected packages can be removed systemlessly".to_string());
+    }
+    let path = PathBuf::from(format!(
+        "magisk_debloat_{}_{}.zip",
+        phone
+            .model
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_"),
+        chrono::Local::now().format("%Y%m%d_%H%M%S"),
+    ));
+    let file = File::create(&path).map_err(|e| e.to_string())?;
+    write_module(file, &phone, &plan).map_err(|e| e.to_string())?;
+    Ok(ModuleExport { path, plan })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read};
+
+    #[test]
+    fn module_zip() {
+        let package = |name: &str, apks: &[&str]| ModulePackage {
+            name: name.to_string(),
+            apks: Ok(apks.iter().map(ToString::to_string).collect()),
+        };
+        let packages = [
+            package(
+                "com.a",
+                &["/system/app/A/A.apk", "/system/app/A/split_config.apk"],
+            ),
+            package("com.b", &["/product/priv-app/B/B.apk"]),
+            package("com.updated", &["/data/app/~~x==/com.updated-y==/base.apk"]),
+            package("android", &["/system/framework/framework-res.apk"]),
+            package("com.gone", &[]),
+        ];
+        let plan = plan_module(&packages);
+        assert_eq!(
+            plan.replaced,
+            [
+                ("/system/app/A".to_string(), "com.a".to_string()),
+                (
+                    "/system/product/priv-app/B".to_string(),
+                    "com.b".to_string()
+                ),
+            ]
+        );
+        let skipped: Vec<_> = plan.skipped.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(skipped, ["com.updated", "android", "com.gone"]);
+
+        let phone = Phone {
+            model: "Pixel 8".into(),
+            android_sdk: 34,
+            ..Phone::default()
+        };
+        let zip = write_module(Cursor::new(vec![]), &phone, &plan).unwrap();
+        let mut zip = zip::ZipArchive::new(zip).unwrap();
+        let mut read = |name| {
+            let mut content = String::new();
+            zip.by_name(name)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            content
+        };
+        assert!(read("module.prop").starts_with("id=uad_ng_pixel_8\n"));
+        let customize = read("customize.sh");
+        assert!(
+            customize.contains("# com.a: /system/app/A\n# com.b: /system/product/priv-app/B\n")
+        );
+        assert!(customize.ends_with("REPLACE=\"\n/system/app/A\n/system/product/priv-app/B\n\"\n"));
+        assert_eq!(
+            read("META-INF/com/google/android/updater-script"),
+            "#MAGISK\n"
+        );
+        assert!(read("META-INF/com/google/android/update-binary").contains("install_module"));
+    }
+}
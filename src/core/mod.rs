@@ -1,7 +1,21 @@
 pub mod adb;
+pub mod archive;
+pub mod changelog;
+pub mod compare;
 pub mod config;
+pub mod contribution;
+pub mod fleet;
 pub mod helpers;
+pub mod journal;
+pub mod lint;
+pub mod magisk;
+pub mod offline;
+pub mod ota;
+pub mod profile;
+pub mod report;
 pub mod save;
+pub mod script;
+pub mod selection;
 pub mod sync;
 pub mod theme;
 pub mod uad_lists;
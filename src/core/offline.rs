@@ -0,0 +1,299 @@
+//! Virtual devices: browsing a device while it isn't connected.
+//!
+//! The inventory of a device is cached every time its packages are loaded.
+//! A virtual device is made of this cache, or of a backup: its packages can be browsed
+//! and selected, but nothing can be changed until the device is connected again.
+
+use crate::CACHE_DIR;
+use crate::core::save::{BackupFile, UserBackup, device_users, list_all_backups, read_backup};
+use crate::core::sync::{Phone, User};
+use crate::core::uad_lists::{Opposite, PackageHashMap, PackageState};
+use crate::core::utils::{package_row, write_atomic};
+use crate::gui::widgets::package_row::PackageRow;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn cache_dir() -> PathBuf {
+    CACHE_DIR.join("devices")
+}
+
+/// Last known inventory of a device
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct CachedDevice {
+    pub serial: String,
+    pub model: String,
+    pub android_sdk: u8,
+    /// RFC 3339, local time
+    pub timestamp: String,
+    pub users: Vec<UserBackup>,
+}
+
+impl CachedDevice {
+    #[must_use]
+    pub fn new(phone: &Phone, phone_packages: &[Vec<PackageRow>]) -> Self {
+        Self {
+            serial: phone.adb_id.clone(),
+            model: phone.model.clone(),
+            android_sdk: phone.android_sdk,
+            timestamp: chrono::Local::now().to_rfc3339(),
+            users: device_users(phone, phone_packages),
+        }
+    }
+}
+
+/// Cache the inventory of `phone`, replacing the previous one
+pub fn cache_device(phone: &Phone, phone_packages: &[Vec<PackageRow>]) -> Result<(), String> {
+    let dir = cache_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string(&CachedDevice::new(phone, phone_packages))
+        .map_err(|e| e.to_string())?;
+    write_atomic(dir.join(format!("{}.json", phone.adb_id)), json).map_err(|e| e.to_string())
+}
+
+/// Every cached device, most recently seen first
+#[must_use]
+pub fn list_cached_devices() -> Vec<CachedDevice> {
+    let Ok(files) = fs::read_dir(cache_dir()) else {
+        return vec![];
+    };
+    let mut devices: Vec<CachedDevice> = files
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|p| {
+            let data = fs::read_to_string(&p).ok()?;
+            serde_json::from_str(&data)
+                .inspect_err(|e| warn!("[OFFLINE] Invalid cache {}: {e}", p.display()))
+                .ok()
+        })
+        .collect();
+    devices.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    devices
+}
+
+/// What a virtual device is made of
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OfflineSource {
+    Cache(Box<CachedDevice>),
+    Backup(Box<BackupFile>),
+}
+
+impl fmt::Display for OfflineSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cache(device) => {
+                let date = chrono::DateTime::parse_from_rfc3339(&device.timestamp)
+                    .map(|d| d.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default();
+                write!(f, "{} ({}), seen {date}", device.model, device.serial)
+            }
+            Self::Backup(backup) => write!(f, "Backup: {backup}"),
+        }
+    }
+}
+
+/// Cached devices, then the readable backups of any device
+#[must_use]
+pub fn list_offline_sources(backup_folder: &Path) -> Vec<OfflineSource> {
+    list_cached_devices()
+        .into_iter()
+        .map(|d| OfflineSource::Cache(Box::new(d)))
+        .chain(
+            list_all_backups(backup_folder)
+                .into_iter()
+                .filter(|b| b.error.is_none())
+                .map(|b| OfflineSource::Backup(Box::new(b))),
+        )
+        .collect()
+}
+
+/// A device browsed while it isn't connected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OfflineDevice {
+    pub phone: Phone,
+    /// Where its packages come from
+    pub origin: String,
+    pub users: Vec<UserBackup>,
+}
+
+impl OfflineSource {
+    pub fn load(&self) -> Result<OfflineDevice, String> {
+        let (serial, model, android_sdk, users) = match self {
+            Self::Cache(device) => (
+                device.serial.clone(),
+                device.model.clone(),
+                device.android_sdk,
+                device.users.clone(),
+            ),
+            Self::Backup(backup) => {
+                let users = read_backup(&backup.path.path)?.users;
+                let (model, android_sdk) = backup.metadata.as_ref().map_or_else(
+                    || ("Unknown device".to_string(), 0),
+                    |m| (format!("{} {}", m.brand, m.model), m.android_sdk),
+                );
+                (backup.device_id.clone(), model, android_sdk, users)
+            }
+        };
+        Ok(OfflineDevice {
+            phone: Phone {
+                model,
+                android_sdk,
+                user_list: users
+                    .iter()
+                    .enumerate()
+                    .map(|(index, u)| User {
+                        id: u.id,
+                        index,
+                        protected: false,
+                        kind: u.kind,
+                    })
+                    .collect(),
+                adb_id: serial,
+            },
+            origin: self.to_string(),
+            users,
+        })
+    }
+}
+
+impl OfflineDevice {
+    /// Packages of every user, in the states they were in, described by `uad_lists`
+    #[must_use]
+    pub fn packages(&self, uad_lists: &PackageHashMap) -> Vec<Vec<PackageRow>> {
+        self.users
+            .iter()
+            .map(|u| {
+                let mut rows: Vec<PackageRow> = u
+                    .packages
+                    .iter()
+                    .map(|p| package_row(uad_lists, &p.name, p.state))
+                    .collect();
+                rows.sort_by_key(|p| p.name.to_lowercase());
+                rows
+            })
+            .collect()
+    }
+}
+
+/// A selection made offline, once the device is connected again
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResumedSelection {
+    /// `(i_user, index)` of the packages to select again
+    pub selected: Vec<(usize, usize)>,
+    /// Packages already in the state wanted for them
+    pub already: Vec<String>,
+    /// Packages whose state changed since: selecting them wouldn't give the wanted state
+    pub changed: Vec<String>,
+    /// Packages the device doesn't have anymore
+    pub missing: Vec<String>,
+}
+
+/// Match `selection`, made offline as `(user_id, package, wanted state)`,
+/// against the current `phone_packages` of `phone`
+#[must_use]
+pub fn resume_selection(
+    selection: &[(u16, String, PackageState)],
+    phone: &Phone,
+    phone_packages: &[Vec<PackageRow>],
+    disable_mode: bool,
+) -> ResumedSelection {
+    let mut resumed = ResumedSelection::default();
+    for (user_id, name, wanted) in selection {
+        let label = format!("{name} (user {user_id})");
+        let found = phone
+            .user_list
+            .iter()
+            .find(|u| u.id == *user_id)
+            .and_then(|u| {
+                let packages = phone_packages.get(u.index)?;
+                let index = packages.iter().position(|p| &p.name == name)?;
+                Some((u.index, index, packages[index].state))
+            });
+        match found {
+            None => resumed.missing.push(label),
+            Some((_, _, state)) if state == *wanted => resumed.already.push(label),
+            // Selecting toggles the state
+            Some((_, _, state)) if state.opposite(disable_mode) != *wanted => {
+                resumed.changed.push(label);
+            }
+            Some((i_user, index, _)) => resumed.selected.push((i_user, index)),
+        }
+    }
+    resumed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::uad_lists::{PackageState, Removal, UadList};
+
+    #[test]
+    fn cached_device_browsed_offline() {
+        let row = |name, state| PackageRow::test(name, state, UadList::Unlisted, Removal::Unlisted);
+        let phone = Phone {
+            model: "Google Pixel 8".into(),
+            android_sdk: 34,
+            user_list: vec![
+                User::default(),
+                User {
+                    id: 10,
+                    index: 1,
+                    ..User::default()
+                },
+            ],
+            adb_id: "abc123".into(),
+        };
+        let packages = vec![
+            vec![
+                row("com.b", PackageState::Uninstalled),
+                row("com.a", PackageState::Enabled),
+            ],
+            vec![row("com.a", PackageState::Disabled)],
+        ];
+        let source = OfflineSource::Cache(Box::new(CachedDevice::new(&phone, &packages)));
+        let device = source.load().unwrap();
+        assert_eq!(device.phone.adb_id, "abc123");
+        assert_eq!(device.phone.user_list, phone.user_list);
+        assert!(device.origin.starts_with("Google Pixel 8 (abc123), seen "));
+
+        let uad_lists: PackageHashMap = serde_json::from_str(
+            r#"{"com.a": {"list": "Oem", "description": "Ads", "dependencies": [],
+            "neededBy": [], "labels": [], "removal": "Recommended"}}"#,
+        )
+        .unwrap();
+        let rows = device.packages(&uad_lists);
+        let names: Vec<_> = rows[0].iter().map(|p| (p.name.as_str(), p.state)).collect();
+        assert_eq!(
+            names,
+            [
+                ("com.a", PackageState::Enabled),
+                ("com.b", PackageState::Uninstalled)
+            ]
+        );
+        assert_eq!(rows[0][0].removal, Removal::Recommended);
+        assert_eq!(rows[1][0].state, PackageState::Disabled);
+
+        // Meanwhile, `com.a` was disabled on user 0 and uninstalled on user 10
+        let now = vec![
+            vec![
+                row("com.a", PackageState::Disabled),
+                row("com.c", PackageState::Enabled),
+            ],
+            vec![row("com.a", PackageState::Uninstalled)],
+        ];
+        let selection = [
+            (0, "com.a".to_string(), PackageState::Uninstalled),
+            (0, "com.b".to_string(), PackageState::Enabled),
+            (0, "com.c".to_string(), PackageState::Uninstalled),
+            (10, "com.a".to_string(), PackageState::Uninstalled),
+        ];
+        let resumed = resume_selection(&selection, &phone, &now, false);
+        assert_eq!(resumed.selected, [(0, 1)]);
+        assert_eq!(resumed.already, ["com.a (user 10)"]);
+        // Selecting it would enable it
+        assert_eq!(resumed.changed, ["com.a (user 0)"]);
+        assert_eq!(resumed.missing, ["com.b (user 0)"]);
+    }
+}
@@ -0,0 +1,263 @@
+//! Detection of what a system update (OTA) brought back on a device.
+//!
+//! The build fingerprint (`ro.build.fingerprint`) and the package states of a device
+//! are remembered in its settings every time its packages are loaded.
+//! Changes made by UAD-ng since then are in its [`Journal`], so when the fingerprint
+//! differs on the next load, anything else that changed was done by the update.
+
+use crate::core::journal::Journal;
+use crate::core::profile::{Profile, UserOverride};
+use crate::core::save::UserBackup;
+use crate::core::uad_lists::{PackageHashMap, PackageState};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Package states of a device, as last seen
+#[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct KnownStates {
+    /// `ro.build.fingerprint` of the device when they were recorded
+    pub fingerprint: String,
+    /// RFC 3339, local time
+    pub timestamp: String,
+    pub users: Vec<UserBackup>,
+}
+
+/// A package brought back by an update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Restored {
+    pub user_id: u16,
+    pub package: String,
+    /// State before the update
+    pub was: PackageState,
+    pub now: PackageState,
+}
+
+impl fmt::Display for Restored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (user {}): {} -> {}",
+            self.package, self.user_id, self.was, self.now
+        )
+    }
+}
+
+/// What a system update changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtaChanges {
+    pub previous_fingerprint: String,
+    pub fingerprint: String,
+    /// Packages re-enabled or reinstalled by the update
+    pub restored: Vec<Restored>,
+    /// `(user_id, package)` added by the update, unknown to the lists
+    pub new_unlisted: Vec<(u16, String)>,
+}
+
+impl OtaChanges {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.restored.is_empty() && self.new_unlisted.is_empty()
+    }
+
+    /// The debloat undone by the update, to re-apply it
+    #[must_use]
+    pub fn previous_debloat(&self) -> Profile {
+        let mut users: BTreeMap<u16, BTreeMap<String, PackageState>> = BTreeMap::new();
+        for r in &self.restored {
+            users
+                .entry(r.user_id)
+                .or_default()
+                .insert(r.package.clone(), r.was);
+        }
+        Profile {
+            name: "Debloat before the system update".to_string(),
+            description: String::new(),
+            rules: vec![],
+            packages: BTreeMap::new(),
+            users: users
+                .into_iter()
+                .map(|(id, packages)| UserOverride { id, packages })
+                .collect(),
+        }
+    }
+}
+
+/// How present a package is: an update only ever makes packages more present
+const fn presence(state: PackageState) -> u8 {
+    match state {
+        PackageState::Uninstalled => 0,
+        PackageState::Disabled => 1,
+        PackageState::Enabled | PackageState::All => 2,
+    }
+}
+
+impl KnownStates {
+    #[must_use]
+    pub fn new(fingerprint: String, users: Vec<UserBackup>) -> Self {
+        Self {
+            fingerprint,
+            timestamp: chrono::Local::now().to_rfc3339(),
+            users,
+        }
+    }
+
+    /// The recorded states, with the successful changes journaled since
+    fn expected(&self, journal: &Journal) -> Vec<UserBackup> {
+        let mut users = self.users.clone();
+        let Ok(recorded) = chrono::DateTime::parse_from_rfc3339(&self.timestamp) else {
+            return users;
+        };
+        let newer = journal.entries.iter().filter(|e| {
+            e.error.is_none()
+                && chrono::DateTime::parse_from_rfc3339(&e.timestamp).is_ok_and(|t| t > recorded)
+        });
+        for entry in newer {
+            if let Some(package) = users
+                .iter_mut()
+                .filter(|u| u.id == entry.user_id)
+                .flat_map(|u| u.packages.iter_mut())
+                .find(|p| p.name == entry.package)
+            {
+                package.state = entry.to;
+            }
+        }
+        users
+    }
+
+    /// What an update changed, `None` if the device wasn't updated since the states were recorded.
+    ///
+    /// `users` are the current states of the device, with the `fingerprint` of its build.
+    #[must_use]
+    pub fn detect(
+        &self,
+        journal: &Journal,
+        fingerprint: &str,
+        users: &[UserBackup],
+        uad_lists: &PackageHashMap,
+    ) -> Option<OtaChanges> {
+        if self.fingerprint.is_empty() || fingerprint.is_empty() || self.fingerprint == fingerprint
+        {
+            return None;
+        }
+        let mut changes = OtaChanges {
+            previous_fingerprint: self.fingerprint.clone(),
+            fingerprint: fingerprint.to_string(),
+            restored: vec![],
+            new_unlisted: vec![],
+        };
+        for expected in self.expected(journal) {
+            let Some(user) = users.iter().find(|u| u.id == expected.id) else {
+                continue;
+            };
+            let known: BTreeMap<&str, PackageState> = expected
+                .packages
+                .iter()
+                .map(|p| (p.name.as_str(), p.state))
+                .collect();
+            let mut new = BTreeSet::new();
+            for package in &user.packages {
+                match known.get(package.name.as_str()) {
+                    Some(&was) if presence(package.state) > presence(was) => {
+                        changes.restored.push(Restored {
+                            user_id: user.id,
+                            package: package.name.clone(),
+                            was,
+                            now: package.state,
+                        });
+                    }
+                    None if !uad_lists.contains_key(&package.name) => {
+                        new.insert(package.name.clone());
+                    }
+                    _ => {}
+                }
+            }
+            changes
+                .new_unlisted
+                .extend(new.into_iter().map(|name| (user.id, name)));
+        }
+        Some(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::adb::UserKind;
+    use crate::core::journal::{JournalAction, JournalEntry};
+    use crate::core::sync::CorePackage;
+
+    fn user(packages: &[(&str, PackageState)]) -> UserBackup {
+        UserBackup {
+            id: 0,
+            kind: UserKind::default(),
+            packages: packages
+                .iter()
+                .map(|&(name, state)| CorePackage {
+                    name: name.into(),
+                    state,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn update_brings_packages_back() {
+        let known = KnownStates {
+            fingerprint: "brand/device:14/AP1A/1:user/release-keys".into(),
+            timestamp: "2024-01-01T00:00:00+00:00".into(),
+            users: vec![user(&[
+                ("com.ads", PackageState::Uninstalled),
+                ("com.carrier", PackageState::Disabled),
+                ("com.changed", PackageState::Enabled),
+                ("com.kept", PackageState::Enabled),
+            ])],
+        };
+        // `com.changed` was uninstalled by UAD-ng after the states were recorded
+        let mut journal = Journal::default();
+        journal.record(JournalEntry {
+            timestamp: "2024-02-01T00:00:00+00:00".into(),
+            package: "com.changed".into(),
+            user_id: 0,
+            from: PackageState::Enabled,
+            to: PackageState::Uninstalled,
+            commands: vec![],
+            action: JournalAction::Change,
+            error: None,
+        });
+        let current = [user(&[
+            ("com.ads", PackageState::Enabled),
+            ("com.carrier", PackageState::Disabled),
+            ("com.changed", PackageState::Enabled),
+            ("com.kept", PackageState::Enabled),
+            ("com.new", PackageState::Enabled),
+        ])];
+        let lists = PackageHashMap::new();
+        assert_eq!(
+            known.detect(&journal, &known.fingerprint, &current, &lists),
+            None
+        );
+
+        let changes = known
+            .detect(
+                &journal,
+                "brand/device:15/AP3A/2:user/release-keys",
+                &current,
+                &lists,
+            )
+            .unwrap();
+        let restored: Vec<_> = changes.restored.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            restored,
+            [
+                "com.ads (user 0): Uninstalled -> Enabled",
+                "com.changed (user 0): Uninstalled -> Enabled"
+            ]
+        );
+        assert_eq!(changes.new_unlisted, [(0, "com.new".to_string())]);
+        assert_eq!(
+            changes.previous_debloat().users[0].packages["com.ads"],
+            PackageState::Uninstalled
+        );
+    }
+}
@@ -0,0 +1,404 @@
+//! Declarative debloat profiles.
+//!
+//! A profile describes the wanted state of packages, rather than a list of actions.
+//! Applying it computes what differs on the device and only changes that.
+//!
+//! Profiles are TOML or JSON files in `<config dir>/profiles`:
+//! ```toml
+//! name = "Minimal"
+//! description = "Remove what's recommended, keep the camera"
+//!
+//! # Rules are applied in order, later ones win
+//! [[rules]]
+//! removal = "Recommended"
+//! state = "Uninstalled"
+//!
+//! [[rules]]
+//! list = "Carrier"
+//! removal = "Advanced"
+//! state = "Disabled"
+//!
+//! # Patterns (`*` and `?`) and exact names win over rules
+//! [packages]
+//! "com.facebook.*" = "Uninstalled"
+//! "com.android.camera2" = "Enabled"
+//!
+//! # Per-user overrides win over everything else
+//! [[users]]
+//! id = 10
+//! packages = { "com.whatsapp" = "Enabled" }
+//! ```
+//! A `All` state means "leave the package as it is".
+
+use crate::CONFIG_DIR;
+use crate::core::journal::{JournalAction, JournalEntry};
+use crate::core::sync::Phone;
+use crate::core::uad_lists::{PackageState, Removal, UadList};
+use crate::gui::widgets::package_row::PackageRow;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Wanted state of every package matching the (optional) list and removal tier
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    #[serde(default)]
+    pub list: Option<UadList>,
+    #[serde(default)]
+    pub removal: Option<Removal>,
+    pub state: PackageState,
+}
+
+impl Rule {
+    fn matches(&self, list: UadList, removal: Removal) -> bool {
+        self.list.is_none_or(|l| l == UadList::All || l == list)
+            && self
+                .removal
+                .is_none_or(|r| r == Removal::All || r == removal)
+    }
+}
+
+/// Wanted states that only apply to one user
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UserOverride {
+    pub id: u16,
+    #[serde(default)]
+    pub packages: BTreeMap<String, PackageState>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// Defaults to the file name
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Package names or patterns
+    #[serde(default)]
+    pub packages: BTreeMap<String, PackageState>,
+    #[serde(default)]
+    pub users: Vec<UserOverride>,
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// `true` if `name` matches `pattern`, where `*` is any sequence and `?` any character
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // position of the last `*` in `pattern`, and of what it matched up to in `name`
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    n = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Wanted state of `name` in `packages`: exact name first, then the longest matching pattern
+fn lookup(packages: &BTreeMap<String, PackageState>, name: &str) -> Option<PackageState> {
+    packages.get(name).copied().or_else(|| {
+        packages
+            .iter()
+            .filter(|(pattern, _)| pattern.contains(['*', '?']) && glob_match(pattern, name))
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(_, &state)| state)
+    })
+}
+
+impl Profile {
+    /// Parse a profile, TOML or JSON according to the extension of `path`
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut profile: Self = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&data).map_err(|e| e.to_string())?
+        } else {
+            toml::from_str(&data).map_err(|e| e.to_string())?
+        };
+        if profile.name.is_empty() {
+            profile.name = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+        }
+        Ok(profile)
+    }
+
+    /// Package names (not patterns) the profile mentions for `user_id`
+    pub fn exact_names(&self, user_id: u16) -> impl Iterator<Item = &str> {
+        self.users
+            .iter()
+            .filter(move |u| u.id == user_id)
+            .flat_map(|u| u.packages.keys())
+            .chain(self.packages.keys())
+            .filter(|name| !name.contains(['*', '?']))
+            .map(String::as_str)
+    }
+
+    /// Wanted state of a package for `user_id`, `None` if the profile doesn't care
+    #[must_use]
+    pub fn desired_state(
+        &self,
+        user_id: u16,
+        name: &str,
+        list: UadList,
+        removal: Removal,
+    ) -> Option<PackageState> {
+        self.users
+            .iter()
+            .filter(|u| u.id == user_id)
+            .find_map(|u| lookup(&u.packages, name))
+            .or_else(|| lookup(&self.packages, name))
+            .or_else(|| {
+                self.rules
+                    .iter()
+                    .rev()
+                    .find(|r| r.matches(list, removal))
+                    .map(|r| r.state)
+            })
+            .filter(|&state| state != PackageState::All)
+    }
+}
+
+/// Directory the profiles are loaded from
+#[must_use]
+pub fn profiles_dir() -> PathBuf {
+    CONFIG_DIR.join("profiles")
+}
+
+/// Valid profiles of [`profiles_dir`], sorted by name.
+/// Invalid ones are logged and skipped.
+#[must_use]
+pub fn list_profiles() -> Vec<Profile> {
+    let Ok(entries) = fs::read_dir(profiles_dir()) else {
+        return vec![];
+    };
+    let mut profiles: Vec<Profile> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|ext| ext == "toml" || ext == "json")
+        })
+        .filter_map(|path| {
+            Profile::load(&path)
+                .map_err(|e| warn!("[PROFILE] Ignoring {}: {e}", path.display()))
+                .ok()
+        })
+        .collect();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    profiles
+}
+
+/// A change needed to converge a package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileChange {
+    /// `phone_packages` indexes
+    pub i_user: usize,
+    pub index: usize,
+    pub entry: JournalEntry,
+}
+
+/// What applying a profile would do on a device
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfilePlan {
+    pub profile: String,
+    pub changes: Vec<ProfileChange>,
+    /// `Unsafe` packages the profile would change, left alone outside of expert mode
+    pub unsafe_skipped: Vec<String>,
+}
+
+/// Compare the wanted states of `profile` with `phone_packages`, for every unprotected user
+#[must_use]
+pub fn reconcile(
+    profile: &Profile,
+    phone: &Phone,
+    phone_packages: &[Vec<PackageRow>],
+    expert_mode: bool,
+) -> ProfilePlan {
+    let mut plan = ProfilePlan {
+        profile: profile.name.clone(),
+        ..ProfilePlan::default()
+    };
+    for user in phone.user_list.iter().filter(|u| !u.protected) {
+        let Some(packages) = phone_packages.get(user.index) else {
+            continue;
+        };
+        for (index, package) in packages.iter().enumerate() {
+            let Some(to) =
+                profile.desired_state(user.id, &package.name, package.uad_list, package.removal)
+            else {
+                continue;
+            };
+            if to == package.state {
+                continue;
+            }
+            if package.removal == Removal::Unsafe && !expert_mode {
+                if !plan.unsafe_skipped.contains(&package.name) {
+                    plan.unsafe_skipped.push(package.name.clone());
+                }
+                continue;
+            }
+            let Some(entry) = JournalEntry::plan(
+                phone,
+                &package.name,
+                user.id,
+                package.state,
+                to,
+                JournalAction::Change,
+            ) else {
+                continue;
+            };
+            if !entry.commands.is_empty() {
+                plan.changes.push(ProfileChange {
+                    i_user: user.index,
+                    index,
+                    entry,
+                });
+            }
+        }
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::sync::User;
+
+    const PROFILE: &str = r#"
+        [[rules]]
+        removal = "Recommended"
+        state = "Uninstalled"
+
+        [[rules]]
+        list = "Carrier"
+        state = "Disabled"
+
+        [packages]
+        "com.facebook.*" = "Uninstalled"
+        "com.facebook.katana" = "All"
+        "com.keep" = "Enabled"
+
+        [[users]]
+        id = 10
+        packages = { "com.keep" = "Disabled" }
+    "#;
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("com.facebook.*", "com.facebook.system"));
+        assert!(glob_match("com.*.app?", "com.example.app2"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("com.facebook.*", "com.facebook"));
+        assert!(!glob_match("com.?", "com.ab"));
+    }
+
+    #[test]
+    fn precedence_and_reconcile() {
+        let profile: Profile = toml::from_str(PROFILE).unwrap();
+        let state = |user, name, list, removal| profile.desired_state(user, name, list, removal);
+        let (aosp, carrier) = (UadList::Aosp, UadList::Carrier);
+        // the later rule wins
+        assert_eq!(
+            state(0, "com.a", carrier, Removal::Recommended),
+            Some(PackageState::Disabled)
+        );
+        assert_eq!(state(0, "com.a", aosp, Removal::Expert), None);
+        // exact names win over patterns, which win over rules
+        assert_eq!(
+            state(0, "com.facebook.katana", aosp, Removal::Recommended),
+            None
+        );
+        assert_eq!(
+            state(0, "com.facebook.appmanager", aosp, Removal::Expert),
+            Some(PackageState::Uninstalled)
+        );
+        assert_eq!(
+            state(10, "com.keep", aosp, Removal::Recommended),
+            Some(PackageState::Disabled)
+        );
+
+        let phone = Phone {
+            android_sdk: 34,
+            user_list: vec![
+                User::default(),
+                User {
+                    id: 10,
+                    index: 1,
+                    protected: true,
+                    ..User::default()
+                },
+            ],
+            ..Phone::default()
+        };
+        let user_packages = vec![
+            PackageRow::test(
+                "com.keep",
+                PackageState::Disabled,
+                aosp,
+                Removal::Recommended,
+            ),
+            PackageRow::test(
+                "com.gone",
+                PackageState::Uninstalled,
+                aosp,
+                Removal::Recommended,
+            ),
+            PackageRow::test(
+                "com.facebook.services",
+                PackageState::Enabled,
+                aosp,
+                Removal::Unsafe,
+            ),
+            PackageRow::test(
+                "com.carrier",
+                PackageState::Enabled,
+                carrier,
+                Removal::Advanced,
+            ),
+        ];
+        let packages = vec![user_packages.clone(), user_packages];
+
+        let plan = reconcile(&profile, &phone, &packages, false);
+        let changes: Vec<_> = plan.changes.iter().map(|c| c.entry.to_string()).collect();
+        assert_eq!(
+            changes,
+            [
+                "com.keep (user 0): Disabled -> Enabled",
+                "com.carrier (user 0): Enabled -> Disabled",
+            ]
+        );
+        assert_eq!((plan.changes[1].i_user, plan.changes[1].index), (0, 3));
+        assert_eq!(plan.unsafe_skipped, ["com.facebook.services"]);
+
+        let expert_plan = reconcile(&profile, &phone, &packages, true);
+        assert_eq!(expert_plan.changes.len(), 3);
+        assert!(expert_plan.unsafe_skipped.is_empty());
+    }
+}
@@ -0,0 +1,419 @@
+//! Reports of the packages of a device, to be attached to support tickets.
+//!
+//! A report covers every user of the device, with summary statistics,
+//! and can be written as CSV, JSON, Markdown or self-contained HTML.
+
+use crate::core::adb::UserKind;
+use crate::core::sync::Phone;
+use crate::core::uad_lists::{PackageState, list_version};
+use crate::core::utils::NAME;
+use crate::gui::widgets::package_row::PackageRow;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Csv,
+    Json,
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub const ALL: [Self; 4] = [Self::Csv, Self::Json, Self::Markdown, Self::Html];
+
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+            Self::Markdown => "md",
+            Self::Html => "html",
+        }
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Csv => "CSV",
+            Self::Json => "JSON",
+            Self::Markdown => "Markdown",
+            Self::Html => "HTML",
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReportDevice {
+    pub serial: String,
+    pub model: String,
+    pub android_sdk: u8,
+    pub fingerprint: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReportPackage {
+    pub name: String,
+    pub list: String,
+    pub removal: String,
+    pub state: PackageState,
+    pub description: String,
+}
+
+/// Number of packages, by category
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReportSummary {
+    pub packages: usize,
+    pub by_state: BTreeMap<String, usize>,
+    pub by_removal: BTreeMap<String, usize>,
+    pub by_list: BTreeMap<String, usize>,
+}
+
+impl ReportSummary {
+    fn add(&mut self, package: &ReportPackage) {
+        self.packages += 1;
+        *self.by_state.entry(package.state.to_string()).or_default() += 1;
+        *self.by_removal.entry(package.removal.clone()).or_default() += 1;
+        *self.by_list.entry(package.list.clone()).or_default() += 1;
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReportUser {
+    pub id: u16,
+    pub kind: UserKind,
+    /// ADB isn't allowed to list its packages
+    pub protected: bool,
+    pub summary: ReportSummary,
+    pub packages: Vec<ReportPackage>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeviceReport {
+    /// RFC 3339, local time
+    pub generated: String,
+    pub app_version: String,
+    /// See [`list_version`]
+    pub list_version: String,
+    pub device: ReportDevice,
+    /// All users together
+    pub summary: ReportSummary,
+    pub users: Vec<ReportUser>,
+}
+
+impl DeviceReport {
+    /// `fingerprint` is the one of `phone`
+    #[must_use]
+    pub fn new(phone: &Phone, phone_packages: &[Vec<PackageRow>], fingerprint: &str) -> Self {
+        let mut summary = ReportSummary::default();
+        let users = phone
+            .user_list
+            .iter()
+            .map(|u| {
+                let packages: Vec<ReportPackage> = phone_packages
+                    .get(u.index)
+                    .map(|rows| {
+                        rows.iter()
+                            .map(|p| ReportPackage {
+                                name: p.name.clone(),
+                                list: p.uad_list.to_string(),
+                                removal: p.removal.to_string(),
+                                state: p.state,
+                                description: p.description.clone(),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let mut user_summary = ReportSummary::default();
+                for p in &packages {
+                    user_summary.add(p);
+                    summary.add(p);
+                }
+                ReportUser {
+                    id: u.id,
+                    kind: u.kind,
+                    protected: u.protected,
+                    summary: user_summary,
+                    packages,
+                }
+            })
+            .collect();
+        Self {
+            generated: chrono::Local::now().to_rfc3339(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            list_version: list_version(),
+            device: ReportDevice {
+                serial: phone.adb_id.clone(),
+                model: phone.model.clone(),
+                android_sdk: phone.android_sdk,
+                fingerprint: fingerprint.to_string(),
+            },
+            summary,
+            users,
+        }
+    }
+
+    /// The report as `format`.
+    ///
+    /// CSV has a row per package and per user: the device info is repeated,
+    /// and summary statistics are left to the spreadsheet.
+    pub fn render(&self, format: ReportFormat) -> Result<String, String> {
+        match format {
+            ReportFormat::Csv => self.csv(),
+            ReportFormat::Json => serde_json::to_string_pretty(self).map_err(|e| e.to_string()),
+            ReportFormat::Markdown => Ok(self.markdown()),
+            ReportFormat::Html => Ok(self.html()),
+        }
+    }
+
+    fn csv(&self) -> Result<String, String> {
+        let mut wtr = csv::Writer::from_writer(vec![]);
+        let to_string = |e: csv::Error| e.to_string();
+        wtr.write_record([
+            "Serial",
+            "Model",
+            "User",
+            "Package Name",
+            "List",
+            "Removal",
+            "State",
+            "Description",
+        ])
+        .map_err(to_string)?;
+        for user in &self.users {
+            for p in &user.packages {
+                wtr.write_record([
+                    self.device.serial.as_str(),
+                    &self.device.model,
+                    &user.id.to_string(),
+                    &p.name,
+                    &p.list,
+                    &p.removal,
+                    &p.state.to_string(),
+                    &p.description.replace('\n', " "),
+                ])
+                .map_err(to_string)?;
+            }
+        }
+        String::from_utf8(wtr.into_inner().map_err(|e| e.to_string())?).map_err(|e| e.to_string())
+    }
+
+    /// Label and value of the device info header
+    fn header(&self) -> [(&'static str, String); 6] {
+        [
+            ("Device", self.device.model.clone()),
+            ("Serial", self.device.serial.clone()),
+            ("Android SDK", self.device.android_sdk.to_string()),
+            ("Build", self.device.fingerprint.clone()),
+            ("Made by", format!("{NAME} v{}", self.app_version)),
+            ("List version", self.list_version.clone()),
+        ]
+    }
+
+    fn markdown(&self) -> String {
+        let cell = |s: &str| s.replace('|', "\\|").replace('\n', " ");
+        let mut md = format!("# {NAME} report\n\nGenerated on {}\n\n", self.generated);
+        md.push_str("| | |\n|---|---|\n");
+        for (label, value) in self.header() {
+            let _ = writeln!(md, "| {label} | {} |", cell(&value));
+        }
+        md.push_str("\n## Summary\n\n");
+        summary_markdown(&mut md, &self.summary);
+        for user in &self.users {
+            let _ = write!(md, "\n## User {} ({})\n\n", user.id, user.kind);
+            if user.protected {
+                md.push_str("ADB isn't allowed to list the packages of this user.\n");
+                continue;
+            }
+            summary_markdown(&mut md, &user.summary);
+            md.push_str("\n| Package | List | Removal | State | Description |\n");
+            md.push_str("|---|---|---|---|---|\n");
+            for p in &user.packages {
+                let _ = writeln!(
+                    md,
+                    "| {} | {} | {} | {} | {} |",
+                    cell(&p.name),
+                    p.list,
+                    p.removal,
+                    p.state,
+                    cell(&p.description)
+                );
+            }
+        }
+        md
+    }
+
+    fn html(&self) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+            <title>{NAME} report - {}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n\
+            <h1>{NAME} report</h1>\n<p>Generated on {}</p>\n<table>\n",
+            escape(&self.device.model),
+            escape(&self.generated)
+        );
+        for (label, value) in self.header() {
+            let _ = writeln!(html, "<tr><th>{label}</th><td>{}</td></tr>", escape(&value));
+        }
+        html.push_str("</table>\n<h2>Summary</h2>\n");
+        summary_html(&mut html, &self.summary);
+        for user in &self.users {
+            let _ = writeln!(html, "<h2>User {} ({})</h2>", user.id, user.kind);
+            if user.protected {
+                html.push_str("<p>ADB isn't allowed to list the packages of this user.</p>\n");
+                continue;
+            }
+            summary_html(&mut html, &user.summary);
+            html.push_str(
+                "<table>\n<tr><th>Package</th><th>List</th><th>Removal</th>\
+                <th>State</th><th>Description</th></tr>\n",
+            );
+            for p in &user.packages {
+                let _ = writeln!(
+                    html,
+                    "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    p.state.to_string().to_lowercase(),
+                    escape(&p.name),
+                    escape(&p.list),
+                    escape(&p.removal),
+                    p.state,
+                    escape(&p.description).replace('\n', "<br>")
+                );
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+const HTML_STYLE: &str = "body{font-family:sans-serif;margin:2em}\
+    table{border-collapse:collapse;margin-bottom:1em}\
+    th,td{border:1px solid #ccc;padding:4px 8px;text-align:left;vertical-align:top}\
+    th{background:#eee}\
+    tr.uninstalled td{color:#a00}\
+    tr.disabled td{color:#a60}";
+
+/// Escape text for HTML element content and attribute values
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn summary_rows(summary: &ReportSummary) -> Vec<(&'static str, String)> {
+    let counts = |map: &BTreeMap<String, usize>| {
+        map.iter()
+            .map(|(k, v)| format!("{k}: {v}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    vec![
+        ("Packages", summary.packages.to_string()),
+        ("By state", counts(&summary.by_state)),
+        ("By removal", counts(&summary.by_removal)),
+        ("By list", counts(&summary.by_list)),
+    ]
+}
+
+fn summary_markdown(md: &mut String, summary: &ReportSummary) {
+    for (label, value) in summary_rows(summary) {
+        let _ = writeln!(md, "- {label}: {value}");
+    }
+}
+
+fn summary_html(html: &mut String, summary: &ReportSummary) {
+    html.push_str("<ul>\n");
+    for (label, value) in summary_rows(summary) {
+        let _ = writeln!(html, "<li>{label}: {}</li>", escape(&value));
+    }
+    html.push_str("</ul>\n");
+}
+
+/// Ask where to save the report, then write it.
+/// `None` if no destination was chosen.
+pub async fn export_report(
+    report: DeviceReport,
+    format: ReportFormat,
+) -> Result<Option<PathBuf>, String> {
+    let file_name = format!(
+        "uad_report_{}_{}.{}",
+        report
+            .device
+            .serial
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_"),
+        chrono::Local::now().format("%Y%m%d_%H%M%S"),
+        format.extension()
+    );
+    let Some(file) = rfd::AsyncFileDialog::new()
+        .set_file_name(file_name)
+        .add_filter(format.to_string(), &[format.extension()])
+        .save_file()
+        .await
+    else {
+        return Ok(None);
+    };
+    let path = file.path().to_owned();
+    fs::write(&path, report.render(format)?).map_err(|e| e.to_string())?;
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::sync::User;
+    use crate::core::uad_lists::{Removal, UadList};
+
+    #[test]
+    fn every_format() {
+        let phone = Phone {
+            model: "Brand <Model>".into(),
+            android_sdk: 34,
+            user_list: vec![
+                User::default(),
+                User {
+                    id: 10,
+                    index: 1,
+                    protected: true,
+                    ..User::default()
+                },
+            ],
+            adb_id: "abc123".into(),
+        };
+        let row = |name, state, description: &str| PackageRow {
+            description: description.to_string(),
+            ..PackageRow::test(name, state, UadList::Oem, Removal::Recommended)
+        };
+        let packages = vec![
+            vec![
+                row("com.a", PackageState::Enabled, "A | B\nnext line"),
+                row("com.b", PackageState::Uninstalled, ""),
+            ],
+            vec![],
+        ];
+        let report = DeviceReport::new(&phone, &packages, "brand/model:14");
+        assert_eq!(report.summary.packages, 2);
+        assert_eq!(report.users[0].summary.by_state["Uninstalled"], 1);
+
+        let csv = report.render(ReportFormat::Csv).unwrap();
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.contains("abc123,Brand <Model>,0,com.a,oem,Recommended,Enabled,"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&report.render(ReportFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["users"][0]["packages"][1]["state"], "Uninstalled");
+
+        let md = report.render(ReportFormat::Markdown).unwrap();
+        assert!(md.contains("| com.a | oem | Recommended | Enabled | A \\| B next line |"));
+        assert!(md.contains("ADB isn't allowed"));
+
+        let html = report.render(ReportFormat::Html).unwrap();
+        assert!(html.contains("<td>Brand &lt;Model&gt;</td>"));
+        assert!(html.contains("<tr class=\"uninstalled\"><td>com.b</td>"));
+    }
+}
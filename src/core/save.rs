@@ -1,171 +1,1272 @@
+use crate::core::adb::UserKind;
+use crate::core::archive::{extract_apks, is_archive, list_apks, read_archive_backup};
 use crate::core::config::{Config, DeviceSettings};
-use crate::core::sync::{CorePackage, Phone, User, apply_pkg_state_commands};
-use crate::core::utils::DisplayablePath;
+use crate::core::journal::{JournalAction, JournalEntry};
+use crate::core::sync::{
+    CorePackage, Phone, User, get_device_brand, get_device_fingerprint, get_device_model,
+    supports_multi_user, user_flag,
+};
+use crate::core::uad_lists::{PackageState, list_version};
+use crate::core::utils::{DisplayablePath, NAME, write_atomic};
 use crate::gui::widgets::package_row::PackageRow;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
+    fmt::{self, Write as _},
     fs,
     path::{Path, PathBuf},
 };
 
-#[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+/// Version of the backup format written by this version of UAD-ng:
+/// 1. `device_id` and `users` (files have no `version` field)
+/// 2. `metadata` (with an optional `trigger`) and user `kind`s
+pub const BACKUP_FORMAT_VERSION: u32 = 2;
+
+const fn legacy_format_version() -> u32 {
+    1
+}
+
+/// Older formats are migrated when deserializing:
+/// fields they lack get their default value.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct PhoneBackup {
+    #[serde(default = "legacy_format_version")]
+    pub version: u32,
     pub device_id: String,
+    /// `None` for backups made before format 2
+    #[serde(default)]
+    pub metadata: Option<BackupMetadata>,
     pub users: Vec<UserBackup>,
 }
 
+/// Context in which a backup was made
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BackupMetadata {
+    pub brand: String,
+    pub model: String,
+    pub android_sdk: u8,
+    /// `ro.build.fingerprint`, which changes with every system update
+    pub fingerprint: String,
+    /// Version of UAD-ng that made the backup
+    pub app_version: String,
+    /// See [`list_version`]
+    pub list_version: String,
+    /// RFC 3339, local time
+    pub timestamp: String,
+    /// Missing in backups made before automatic snapshots existed
+    #[serde(default)]
+    pub trigger: BackupTrigger,
+}
+
+/// What made a backup
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BackupTrigger {
+    /// The user pressed "Backup"
+    #[default]
+    Manual,
+    /// Automatic snapshot before applying a selection
+    BeforeApply,
+    /// Automatic snapshot before restoring a backup
+    BeforeRestore,
+}
+
+impl BackupTrigger {
+    /// Automatic snapshots are subject to [`SnapshotRetention`], manual backups never expire
+    #[must_use]
+    pub const fn is_snapshot(self) -> bool {
+        !matches!(self, Self::Manual)
+    }
+}
+
+impl fmt::Display for BackupTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Manual => "manual",
+            Self::BeforeApply => "before applying",
+            Self::BeforeRestore => "before restoring",
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionUnit {
+    #[default]
+    Snapshots,
+    Days,
+}
+
+impl RetentionUnit {
+    pub const ALL: [Self; 2] = [Self::Snapshots, Self::Days];
+}
+
+impl fmt::Display for RetentionUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Snapshots => "last snapshots",
+            Self::Days => "days",
+        })
+    }
+}
+
+/// How many automatic snapshots are kept, per device.
+/// The most recent one is always kept.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotRetention {
+    pub keep: u16,
+    pub unit: RetentionUnit,
+}
+
+impl Default for SnapshotRetention {
+    fn default() -> Self {
+        Self {
+            keep: 10,
+            unit: RetentionUnit::Snapshots,
+        }
+    }
+}
+
+/// Delete the automatic snapshots of `dir` that `retention` doesn't keep,
+/// returns how many were deleted.
+pub fn prune_snapshots(
+    dir: &Path,
+    retention: SnapshotRetention,
+    now: chrono::DateTime<chrono::Local>,
+) -> usize {
+    let snapshots = list_available_backups(dir)
+        .into_iter()
+        .filter(|b| b.metadata.as_ref().is_some_and(|m| m.trigger.is_snapshot()));
+    let expired: Vec<BackupFile> = match retention.unit {
+        RetentionUnit::Snapshots => snapshots.skip(retention.keep.max(1).into()).collect(),
+        RetentionUnit::Days => {
+            let limit = now - chrono::Duration::days(retention.keep.into());
+            snapshots
+                .skip(1)
+                .filter(|b| {
+                    b.metadata
+                        .as_ref()
+                        .and_then(|m| chrono::DateTime::parse_from_rfc3339(&m.timestamp).ok())
+                        .is_some_and(|date| date < limit)
+                })
+                .collect()
+        }
+    };
+    expired
+        .iter()
+        .filter(|b| match fs::remove_file(&b.path.path) {
+            Ok(()) => true,
+            Err(e) => {
+                error!(
+                    "[BACKUP] Can't delete expired snapshot {}: {e}",
+                    b.path.path.display()
+                );
+                false
+            }
+        })
+        .count()
+}
+
 #[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct UserBackup {
     pub id: u16,
+    #[serde(default)]
+    pub kind: UserKind,
     pub packages: Vec<CorePackage>,
 }
 
-/// Backup all `Uninstalled` and `Disabled` packages
+/// The current state of the packages of every user of `phone`
+#[must_use]
+pub fn device_users(phone: &Phone, phone_packages: &[Vec<PackageRow>]) -> Vec<UserBackup> {
+    phone
+        .user_list
+        .iter()
+        .map(|u| UserBackup {
+            id: u.id,
+            kind: u.kind,
+            packages: phone_packages
+                .get(u.index)
+                .map(|packages| {
+                    packages
+                        .iter()
+                        .map(|p| CorePackage {
+                            name: p.name.clone(),
+                            state: p.state,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Read and migrate a backup file, or the backup of an archive
+pub fn read_backup(path: &Path) -> Result<PhoneBackup, String> {
+    let data = if is_archive(path) {
+        read_archive_backup(path)?
+    } else {
+        fs::read_to_string(path).map_err(|e| e.to_string())?
+    };
+    let backup: PhoneBackup = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+    if backup.version > BACKUP_FORMAT_VERSION {
+        return Err(format!(
+            "backup format {} is too recent, please update UAD-ng",
+            backup.version
+        ));
+    }
+    Ok(backup)
+}
+
+/// The current state of `phone`, with its metadata
+#[must_use]
+pub fn new_backup(
+    phone: &Phone,
+    phone_packages: &[Vec<PackageRow>],
+    trigger: BackupTrigger,
+) -> PhoneBackup {
+    let device_id = &phone.adb_id;
+    PhoneBackup {
+        version: BACKUP_FORMAT_VERSION,
+        device_id: device_id.clone(),
+        metadata: Some(BackupMetadata {
+            brand: get_device_brand(device_id),
+            model: get_device_model(device_id),
+            android_sdk: phone.android_sdk,
+            fingerprint: get_device_fingerprint(device_id),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            list_version: list_version(),
+            timestamp: chrono::Local::now().to_rfc3339(),
+            trigger,
+        }),
+        users: device_users(phone, phone_packages),
+    }
+}
+
+/// Backup all `Uninstalled` and `Disabled` packages.
+///
+/// Automatic snapshots (see [`BackupTrigger`]) older than
+/// the configured [`SnapshotRetention`] are then deleted.
 pub async fn backup_phone(
-    users: Vec<User>,
-    device_id: String,
+    phone: Phone,
     phone_packages: Vec<Vec<PackageRow>>,
+    trigger: BackupTrigger,
 ) -> Result<bool, String> {
-    let mut backup = PhoneBackup {
-        device_id: device_id.clone(),
-        ..PhoneBackup::default()
-    };
-
-    for u in users {
-        let mut user_backup = UserBackup {
-            id: u.id,
-            ..UserBackup::default()
-        };
+    save_backup(&phone, &phone_packages, trigger).map(|_| true)
+}
 
-        for p in phone_packages[u.index].clone() {
-            user_backup.packages.push(CorePackage {
-                name: p.name.clone(),
-                state: p.state,
-            });
-        }
-        backup.users.push(user_backup);
-    }
+/// [`backup_phone`], returning the path of the backup
+pub fn save_backup(
+    phone: &Phone,
+    phone_packages: &[Vec<PackageRow>],
+    trigger: BackupTrigger,
+) -> Result<PathBuf, String> {
+    let device_id = phone.adb_id.clone();
+    let backup = new_backup(phone, phone_packages, trigger);
 
     match serde_json::to_string_pretty(&backup) {
         Ok(json) => {
-            let backup_dir: PathBuf = Config::load_configuration_file().general.backup_folder;
-            let backup_path = &*backup_dir.join(device_id);
+            let general = Config::load_configuration_file().general;
+            let backup_path = &*general.backup_folder.join(device_id);
 
             if let Err(e) = fs::create_dir_all(backup_path) {
                 error!("BACKUP: could not create backup dir: {e}");
                 return Err(e.to_string());
             }
 
-            let backup_filename =
-                format!("{}.json", chrono::Local::now().format("%Y-%m-%d_%H-%M-%S"));
-
-            match fs::write(backup_path.join(backup_filename), json) {
-                Ok(()) => Ok(true),
-                Err(err) => Err(err.to_string()),
+            let now = chrono::Local::now();
+            let file = match new_backup_path(backup_path, now, trigger, ".json") {
+                Ok(file) => file,
+                Err(e) => return Err(e.to_string()),
+            };
+            match write_atomic(&file, json) {
+                Ok(()) => {
+                    if trigger.is_snapshot() {
+                        let pruned = prune_snapshots(backup_path, general.snapshot_retention, now);
+                        if pruned > 0 {
+                            info!("[BACKUP] Deleted {pruned} expired snapshot(s)");
+                        }
+                    }
+                    Ok(file)
+                }
+                Err(err) => {
+                    // An empty placeholder would be listed as an unreadable backup
+                    let _ = fs::remove_file(&file);
+                    Err(err.to_string())
+                }
             }
         }
         Err(err) => Err(err.to_string()),
     }
 }
 
-pub fn list_available_backups(dir: &Path) -> Vec<DisplayablePath> {
+/// Reserve the path of a new backup in `dir`, named after `now` and `trigger`,
+/// ending with `extension` (e.g. `.json`).
+///
+/// Backups made within the same second get a counter instead of replacing each other,
+/// which keeps them sorted by name.
+pub fn new_backup_path(
+    dir: &Path,
+    now: chrono::DateTime<chrono::Local>,
+    trigger: BackupTrigger,
+    extension: &str,
+) -> std::io::Result<PathBuf> {
+    let stem = match trigger {
+        BackupTrigger::Manual => now.format("%Y-%m-%d_%H-%M-%S").to_string(),
+        BackupTrigger::BeforeApply => format!("{}_before-apply", now.format("%Y-%m-%d_%H-%M-%S")),
+        BackupTrigger::BeforeRestore => {
+            format!("{}_before-restore", now.format("%Y-%m-%d_%H-%M-%S"))
+        }
+    };
+    for n in 1.. {
+        let name = if n == 1 {
+            format!("{stem}{extension}")
+        } else {
+            format!("{stem}_{n}{extension}")
+        };
+        let path = dir.join(name);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("a backup name is always free")
+}
+
+/// A backup file, with what the backup picker needs to know about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupFile {
+    pub path: DisplayablePath,
+    /// Serial of the device that was backed up
+    pub device_id: String,
+    /// Why the file can't be restored, if so
+    pub error: Option<String>,
+    pub metadata: Option<BackupMetadata>,
+    pub users: Vec<User>,
+}
+
+impl BackupFile {
+    #[must_use]
+    pub fn new(path: PathBuf) -> Self {
+        let path = DisplayablePath { path };
+        match read_backup(&path.path) {
+            Ok(backup) => Self {
+                device_id: backup.device_id,
+                error: None,
+                metadata: backup.metadata,
+                users: backup
+                    .users
+                    .into_iter()
+                    .map(|u| User {
+                        id: u.id,
+                        index: 0,
+                        protected: false,
+                        kind: u.kind,
+                    })
+                    .collect(),
+                path,
+            },
+            Err(e) => {
+                warn!("[BACKUP] {}: {e}", path.path.display());
+                Self {
+                    // backups are stored in a directory named after the device
+                    device_id: path
+                        .path
+                        .parent()
+                        .and_then(Path::file_name)
+                        .map(|d| d.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    path,
+                    error: Some(e),
+                    metadata: None,
+                    users: vec![],
+                }
+            }
+        }
+    }
+
+    /// `true` if APKs can be reinstalled from it
+    #[must_use]
+    pub fn is_archive(&self) -> bool {
+        is_archive(&self.path.path)
+    }
+
+    /// Details shown below the backup picker
+    #[must_use]
+    pub fn details(&self) -> String {
+        if let Some(e) = &self.error {
+            return format!("Unreadable backup: {e}");
+        }
+        let users = self
+            .users
+            .iter()
+            .map(|u| format!("{} ({})", u.id, u.kind))
+            .collect::<Vec<_>>()
+            .join(", ");
+        match &self.metadata {
+            Some(m) => format!(
+                "Device {}: {}\nMade by {NAME} v{} with list {}\nUsers: {users}",
+                self.device_id, m.fingerprint, m.app_version, m.list_version
+            ),
+            None => format!(
+                "Device {}: made by an older version of {NAME}, without device info\nUsers: {users}",
+                self.device_id
+            ),
+        }
+    }
+}
+
+impl fmt::Display for BackupFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let date = self
+            .metadata
+            .as_ref()
+            .and_then(|m| chrono::DateTime::parse_from_rfc3339(&m.timestamp).ok());
+        match (&self.metadata, date) {
+            (Some(m), Some(date)) => {
+                write!(
+                    f,
+                    "{} - {} {} (SDK {})",
+                    date.format("%Y-%m-%d %H:%M"),
+                    m.brand,
+                    m.model,
+                    m.android_sdk
+                )?;
+                if m.trigger.is_snapshot() {
+                    write!(f, " [{}]", m.trigger)?;
+                }
+                if self.is_archive() {
+                    write!(f, " [with APKs]")?;
+                }
+                Ok(())
+            }
+            _ => write!(f, "{}", self.path),
+        }
+    }
+}
+
+/// Backups of a device, most recent first
+pub fn list_available_backups(dir: &Path) -> Vec<BackupFile> {
     match fs::read_dir(dir) {
-        Ok(files) => files
-            .filter_map(Result::ok)
-            .map(|e| DisplayablePath { path: e.path() })
-            .collect::<Vec<_>>(),
+        Ok(files) => {
+            let mut paths = files
+                .filter_map(Result::ok)
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "json") || is_archive(p))
+                .collect::<Vec<_>>();
+            // names are timestamps
+            paths.sort_unstable_by(|a, b| b.cmp(a));
+            paths.into_iter().map(BackupFile::new).collect()
+        }
         Err(_) => vec![],
     }
 }
 
-pub fn list_available_backup_user(backup: DisplayablePath) -> Vec<User> {
-    match fs::read_to_string(backup.path) {
-        Ok(data) => serde_json::from_str::<PhoneBackup>(&data)
-            .expect("Unable to parse backup file")
-            .users
-            .into_iter()
-            .map(|u| User {
-                id: u.id,
-                index: 0,
-                protected: false,
-            })
-            .collect(),
-        Err(e) => {
-            error!("[BACKUP]: Selected backup file not found: {e}");
-            vec![]
+/// Backups of every device in `backup_folder`, most recent first
+pub fn list_all_backups(backup_folder: &Path) -> Vec<BackupFile> {
+    let mut backups: Vec<BackupFile> = fs::read_dir(backup_folder)
+        .map(|dirs| {
+            dirs.filter_map(Result::ok)
+                .filter(|d| d.path().is_dir())
+                .flat_map(|d| list_available_backups(&d.path()))
+                .collect()
+        })
+        .unwrap_or_default();
+    backups.sort_by(|a, b| b.path.path.file_name().cmp(&a.path.path.file_name()));
+    backups
+}
+
+/// Which backups are offered for restoring on a device
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BackupScope {
+    /// Same serial
+    #[default]
+    ThisDevice,
+    /// Same brand and model, e.g. after a motherboard swap or on an identical phone
+    SameModel,
+    /// Same system build
+    SameFingerprint,
+    AllDevices,
+}
+
+impl BackupScope {
+    pub const ALL: [Self; 4] = [
+        Self::ThisDevice,
+        Self::SameModel,
+        Self::SameFingerprint,
+        Self::AllDevices,
+    ];
+
+    /// `fingerprint` is the one of `phone`.
+    /// Backups without metadata only match [`Self::ThisDevice`] and [`Self::AllDevices`].
+    #[must_use]
+    pub fn matches(self, backup: &BackupFile, phone: &Phone, fingerprint: &str) -> bool {
+        let same_device = backup.device_id == phone.adb_id;
+        match (self, &backup.metadata) {
+            (Self::AllDevices, _) => true,
+            (Self::SameModel, Some(m)) => {
+                same_device || format!("{} {}", m.brand, m.model) == phone.model
+            }
+            (Self::SameFingerprint, Some(m)) => {
+                same_device || (!fingerprint.is_empty() && m.fingerprint == fingerprint)
+            }
+            (Self::ThisDevice, _) | (_, None) => same_device,
+        }
+    }
+}
+
+impl fmt::Display for BackupScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::ThisDevice => "This device",
+            Self::SameModel => "Same model",
+            Self::SameFingerprint => "Same system build",
+            Self::AllDevices => "All devices",
+        })
+    }
+}
+
+/// Device user to restore each backup user to, `None` if there's no match.
+///
+/// On the backed-up device, users keep their id.
+/// On another device, ids are meaningless: users are matched by type,
+/// then the remaining ones by order.
+#[must_use]
+pub fn map_users(
+    backup_users: &[UserBackup],
+    device_users: &[User],
+    same_device: bool,
+) -> Vec<(u16, Option<User>)> {
+    if same_device {
+        return backup_users
+            .iter()
+            .map(|u| (u.id, device_users.iter().find(|d| d.id == u.id).copied()))
+            .collect();
+    }
+    let mut available: Vec<User> = device_users.to_vec();
+    let mut mapping: Vec<(u16, Option<User>)> = backup_users
+        .iter()
+        .map(|u| {
+            let same_kind = (u.kind != UserKind::Unknown)
+                .then(|| available.iter().position(|d| d.kind == u.kind))
+                .flatten();
+            (u.id, same_kind.map(|i| available.remove(i)))
+        })
+        .collect();
+    for (_, user) in mapping.iter_mut().filter(|(_, user)| user.is_none()) {
+        if !available.is_empty() {
+            *user = Some(available.remove(0));
+        }
+    }
+    mapping
+}
+
+/// Differences between the backed-up device and the one being restored,
+/// only for a backup of another device
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Compatibility {
+    /// Source device
+    pub device_id: String,
+    pub metadata: Option<BackupMetadata>,
+    /// Backup user id, and device user id it's restored to
+    pub user_mapping: Vec<(u16, Option<u16>)>,
+    pub warnings: Vec<String>,
+}
+
+/// A package whose current state differs from its state in a backup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreItem {
+    /// User of the device (not of the backup)
+    pub user: User,
+    /// Index in the packages of `user`
+    pub index: usize,
+    /// Current state
+    pub package: CorePackage,
+    /// State in the backup
+    pub wanted: PackageState,
+    /// Whether it should be restored
+    pub selected: bool,
+}
+
+/// A package of an archive which is no longer on the device,
+/// `install-existing` can't bring it back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReinstallItem {
+    /// User of the device (not of the backup)
+    pub user: User,
+    pub package: String,
+    /// State in the backup, `Enabled` or `Disabled`
+    pub wanted: PackageState,
+    /// Base and split APKs, extracted from the archive
+    pub apks: Vec<PathBuf>,
+    /// Whether it should be reinstalled
+    pub selected: bool,
+}
+
+impl ReinstallItem {
+    /// The reinstall as a change, to be journaled: its commands are full ADB command lines
+    #[must_use]
+    pub fn entry(&self, selected_device: &Phone) -> JournalEntry {
+        let serial = if selected_device.adb_id.is_empty() {
+            String::new()
+        } else {
+            format!(" -s {}", selected_device.adb_id)
+        };
+        let user = user_flag(supports_multi_user(selected_device).then_some(self.user));
+        let apks: Vec<String> = self.apks.iter().map(|a| a.display().to_string()).collect();
+        let mut commands = vec![format!(
+            "adb{serial} install-multiple{user} {}",
+            apks.join(" ")
+        )];
+        if self.wanted == PackageState::Disabled {
+            commands.push(format!(
+                "adb{serial} shell pm disable-user{user} {}",
+                self.package
+            ));
+        }
+        JournalEntry {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            package: self.package.clone(),
+            user_id: self.user.id,
+            // Fully removed: the closest state it can be brought back to
+            from: PackageState::Uninstalled,
+            to: self.wanted,
+            commands,
+            action: JournalAction::Restore,
+            error: None,
         }
     }
 }
 
 #[derive(Debug)]
 pub struct BackupPackage {
+    pub i_user: usize,
     pub index: usize,
-    pub commands: Vec<String>,
+    /// The change of the package, whose commands are run in order
+    pub entry: JournalEntry,
 }
 
+/// Something of a backup that can't be restored on the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedItem {
+    /// User of the backup
+    pub user_id: u16,
+    /// `None` if the whole user is skipped
+    pub package: Option<String>,
+    pub reason: String,
+}
+
+impl fmt::Display for SkippedItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.package {
+            Some(p) => write!(f, "{p} (user {}): {}", self.user_id, self.reason),
+            None => write!(f, "user {}: {}", self.user_id, self.reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestorePlan {
+    pub items: Vec<RestoreItem>,
+    /// Only for archives
+    pub reinstalls: Vec<ReinstallItem>,
+    pub skipped: Vec<SkippedItem>,
+    /// `None` when restoring a device from its own backup
+    pub compatibility: Option<Compatibility>,
+    /// Archive the `reinstalls` come from, see [`extract_reinstalls`]
+    pub archive: Option<PathBuf>,
+}
+
+impl RestorePlan {
+    /// Nothing to restore, reinstall or report: the device already matches the backup
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.reinstalls.is_empty() && self.skipped.is_empty()
+    }
+}
+
+/// Every package of the selected backup whose state differs from the current one,
+/// grouped by user. All of them are selected.
+///
+/// Users and packages which no longer exist on the device are skipped,
+/// only an unreadable backup is an error.
+/// Missing packages are reinstalled instead if they are in the archive.
 pub fn restore_backup(
     selected_device: &Phone,
     packages: &[Vec<PackageRow>],
     settings: &DeviceSettings,
-) -> Result<Vec<BackupPackage>, String> {
-    match fs::read_to_string(
-        settings
-            .backup
-            .selected
-            .as_ref()
-            .ok_or("field should be Some type")?
-            .path
-            .clone(),
-    ) {
-        Ok(data) => {
-            let phone_backup: PhoneBackup =
-                serde_json::from_str(&data).expect("Unable to parse backup file");
-
-            let mut commands = vec![];
-            for u in phone_backup.users {
-                let index = match selected_device.user_list.iter().find(|x| x.id == u.id) {
-                    Some(i) => i.index,
-                    None => return Err(format!("user {} doesn't exist", u.id)),
-                };
-
-                for (i, backup_package) in u.packages.iter().enumerate() {
-                    let package: CorePackage = match packages[index]
-                        .iter()
-                        .find(|x| x.name == backup_package.name)
-                    {
-                        Some(p) => p.into(),
-                        None => {
-                            return Err(format!(
-                                "{} not found for user {}",
-                                backup_package.name, u.id
-                            ));
-                        }
-                    };
-                    let p_commands = apply_pkg_state_commands(
-                        &package,
-                        backup_package.state,
-                        settings
-                            .backup
-                            .selected_user
-                            .ok_or("field should be Some type")?,
-                        selected_device,
-                    );
-                    if !p_commands.is_empty() {
-                        commands.push(BackupPackage {
-                            index: i,
-                            commands: p_commands,
+) -> Result<RestorePlan, String> {
+    let path = &settings
+        .backup
+        .selected
+        .as_ref()
+        .ok_or("field should be Some type")?
+        .path
+        .path;
+    let phone_backup = read_backup(path)?;
+    // APKs are only extracted for the selected reinstalls, when the restore runs
+    let apks = if is_archive(path) {
+        list_apks(path, &extraction_dir(selected_device))?
+    } else {
+        BTreeMap::new()
+    };
+
+    let same_device = phone_backup.device_id == selected_device.adb_id;
+    let mapping = map_users(&phone_backup.users, &selected_device.user_list, same_device);
+    let mut plan = RestorePlan {
+        compatibility: (!same_device)
+            .then(|| compatibility(&phone_backup, selected_device, &mapping)),
+        archive: is_archive(path).then(|| path.clone()),
+        ..RestorePlan::default()
+    };
+    for (u, (_, user)) in phone_backup.users.into_iter().zip(mapping) {
+        let skip_user = |reason: &str| SkippedItem {
+            user_id: u.id,
+            package: None,
+            reason: format!("{reason} ({} packages)", u.packages.len()),
+        };
+        let Some(user) = user else {
+            plan.skipped
+                .push(skip_user("has no matching user on this device"));
+            continue;
+        };
+        if user.protected {
+            plan.skipped
+                .push(skip_user("ADB isn't allowed to access this user"));
+            continue;
+        }
+
+        for backup_package in &u.packages {
+            let Some((index, package)) = packages[user.index]
+                .iter()
+                .enumerate()
+                .find(|(_, x)| x.name == backup_package.name)
+            else {
+                match apks.get(&backup_package.name) {
+                    Some(files) if backup_package.state != PackageState::Uninstalled => {
+                        plan.reinstalls.push(ReinstallItem {
+                            user,
+                            package: backup_package.name.clone(),
+                            wanted: backup_package.state,
+                            apks: files.clone(),
+                            selected: true,
                         });
                     }
+                    _ => plan.skipped.push(SkippedItem {
+                        user_id: u.id,
+                        package: Some(backup_package.name.clone()),
+                        reason: "not found on this device".to_string(),
+                    }),
                 }
-            }
-            if !commands.is_empty() {
-                commands.push(BackupPackage {
-                    index: 0,
-                    commands: vec![],
+                continue;
+            };
+            if package.state != backup_package.state {
+                plan.items.push(RestoreItem {
+                    user,
+                    index,
+                    package: package.into(),
+                    wanted: backup_package.state,
+                    selected: true,
                 });
             }
-            Ok(commands)
         }
-        Err(e) => Err(e.to_string()),
+    }
+    Ok(plan)
+}
+
+/// Where the APKs of an archive are extracted to be reinstalled on `device`
+fn extraction_dir(device: &Phone) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "uad_restore_{}",
+        device
+            .adb_id
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    ))
+}
+
+/// Extract the APKs of the selected `reinstalls` from `archive`, where their commands expect them
+pub fn extract_reinstalls(
+    archive: &Path,
+    selected_device: &Phone,
+    reinstalls: &[ReinstallItem],
+) -> Result<(), String> {
+    let dest = extraction_dir(selected_device);
+    let _ = fs::remove_dir_all(&dest);
+    let packages: Vec<&str> = reinstalls
+        .iter()
+        .filter(|r| r.selected)
+        .map(|r| r.package.as_str())
+        .collect();
+    extract_apks(archive, &dest, &packages)
+}
+
+fn compatibility(
+    backup: &PhoneBackup,
+    device: &Phone,
+    mapping: &[(u16, Option<User>)],
+) -> Compatibility {
+    let mut warnings = vec![];
+    match &backup.metadata {
+        Some(m) => {
+            if format!("{} {}", m.brand, m.model) != device.model {
+                warnings.push(format!(
+                    "The backup was made on another model ({} {}): some packages may not exist on this one",
+                    m.brand, m.model
+                ));
+            }
+            if m.android_sdk != device.android_sdk {
+                warnings.push(format!(
+                    "The backup was made on Android SDK {}, this device runs SDK {}",
+                    m.android_sdk, device.android_sdk
+                ));
+            }
+        }
+        None => warnings.push(format!(
+            "The backup was made by an older version of {NAME}: the model it comes from is unknown"
+        )),
+    }
+    Compatibility {
+        device_id: backup.device_id.clone(),
+        metadata: backup.metadata.clone(),
+        user_mapping: mapping
+            .iter()
+            .map(|(id, u)| (*id, u.map(|u| u.id)))
+            .collect(),
+        warnings,
+    }
+}
+
+/// Outcome of a restore, filled as commands complete
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreReport {
+    pub skipped: Vec<SkippedItem>,
+    pub restored: Vec<String>,
+    /// Package and error
+    pub failed: Vec<(String, String)>,
+    /// Packages still being restored
+    pub pending: usize,
+}
+
+impl RestoreReport {
+    #[must_use]
+    pub const fn is_done(&self) -> bool {
+        self.pending == 0
+    }
+
+    pub fn record(&mut self, package: String, result: Result<(), String>) {
+        self.pending = self.pending.saturating_sub(1);
+        match result {
+            Ok(()) => self.restored.push(package),
+            Err(e) => self.failed.push((package, e)),
+        }
+    }
+}
+
+/// Commands restoring the selected `items`
+#[must_use]
+pub fn restore_commands(selected_device: &Phone, items: &[RestoreItem]) -> Vec<BackupPackage> {
+    items
+        .iter()
+        .filter(|item| item.selected)
+        .filter_map(|item| {
+            Some(BackupPackage {
+                i_user: item.user.index,
+                index: item.index,
+                entry: JournalEntry::plan(
+                    selected_device,
+                    &item.package.name,
+                    item.user.id,
+                    item.package.state,
+                    item.wanted,
+                    JournalAction::Restore,
+                )?,
+            })
+        })
+        .filter(|p| !p.entry.commands.is_empty())
+        .collect()
+}
+
+/// A package of a restore, exactly as it will be run
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PlannedRestore {
+    pub user_id: u16,
+    pub package: String,
+    /// `None` if the package is reinstalled from an archive
+    pub from: Option<PackageState>,
+    pub to: PackageState,
+    /// Full ADB command lines, in order
+    pub commands: Vec<String>,
+}
+
+/// Dry run of the selected items of `plan`: nothing is sent to the device
+#[must_use]
+pub fn dry_run(selected_device: &Phone, plan: &RestorePlan) -> Vec<PlannedRestore> {
+    let serial = if selected_device.adb_id.is_empty() {
+        String::new()
+    } else {
+        format!(" -s {}", selected_device.adb_id)
+    };
+    // The very commands the restore runs
+    let items = restore_commands(selected_device, &plan.items)
+        .into_iter()
+        .map(|p| PlannedRestore {
+            user_id: p.entry.user_id,
+            from: Some(p.entry.from),
+            to: p.entry.to,
+            commands: p
+                .entry
+                .commands
+                .iter()
+                .map(|c| format!("adb{serial} shell {c}"))
+                .collect(),
+            package: p.entry.package,
+        });
+    let reinstalls = plan
+        .reinstalls
+        .iter()
+        .filter(|r| r.selected)
+        .map(|r| PlannedRestore {
+            user_id: r.user.id,
+            package: r.package.clone(),
+            from: None,
+            to: r.wanted,
+            commands: r.entry(selected_device).commands,
+        });
+    items.chain(reinstalls).collect()
+}
+
+/// [`dry_run`] as text, to be reviewed (or run by hand) outside of UAD-ng
+#[must_use]
+pub fn dry_run_text(selected_device: &Phone, backup: &str, steps: &[PlannedRestore]) -> String {
+    let mut text = format!(
+        "# Restore of {backup}\n# on {} ({}), {} package(s)\n",
+        selected_device.model,
+        selected_device.adb_id,
+        steps.len()
+    );
+    for step in steps {
+        let from = step
+            .from
+            .map_or_else(|| "not installed".to_string(), |s| s.to_string());
+        let _ = write!(
+            text,
+            "\n# user {}: {} ({from} -> {})\n",
+            step.user_id, step.package, step.to
+        );
+        for command in &step.commands {
+            let _ = writeln!(text, "{command}");
+        }
+    }
+    text
+}
+
+/// Write [`dry_run_text`] in the same directory where UAD-ng is located
+pub async fn export_dry_run(text: String) -> Result<PathBuf, String> {
+    let path = PathBuf::from(format!(
+        "restore_plan_{}.txt",
+        chrono::Local::now().format("%Y%m%d_%H%M%S")
+    ));
+    fs::write(&path, text).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_backup_is_migrated() {
+        let legacy = r#"{
+            "device_id": "abc123",
+            "users": [{"id": 0, "packages": [{"name": "com.example", "state": "Disabled"}]}]
+        }"#;
+        let backup: PhoneBackup = serde_json::from_str(legacy).expect("legacy backup");
+        assert_eq!(backup.version, 1);
+        assert_eq!(backup.metadata, None);
+        assert_eq!(backup.users[0].kind, UserKind::Unknown);
+        assert_eq!(backup.users[0].packages[0].state, PackageState::Disabled);
+
+        let json = serde_json::to_string(&backup).expect("serializable");
+        assert_eq!(
+            serde_json::from_str::<PhoneBackup>(&json).ok(),
+            Some(backup)
+        );
+    }
+
+    #[test]
+    fn missing_users_and_packages_are_skipped() {
+        let path = std::env::temp_dir().join("uad_skipping_backup.json");
+        fs::write(
+            &path,
+            r#"{
+                "device_id": "abc123",
+                "users": [
+                    {"id": 0, "packages": [
+                        {"name": "com.changed", "state": "Uninstalled"},
+                        {"name": "com.gone", "state": "Uninstalled"}
+                    ]},
+                    {"id": 10, "packages": [{"name": "com.changed", "state": "Enabled"}]}
+                ]
+            }"#,
+        )
+        .expect("writable temp dir");
+        let mut settings = DeviceSettings::default();
+        settings.backup.selected = Some(BackupFile::new(path.clone()));
+        let phone = Phone {
+            user_list: vec![User::default()],
+            ..Phone::default()
+        };
+        let packages = vec![vec![PackageRow::new(
+            "com.changed",
+            PackageState::Enabled,
+            "",
+            crate::core::uad_lists::UadList::Oem,
+            crate::core::uad_lists::Removal::Recommended,
+            false,
+            false,
+        )]];
+
+        let plan = restore_backup(&phone, &packages, &settings).expect("readable backup");
+        let _ = fs::remove_file(path);
+        assert_eq!(plan.items.len(), 1);
+        assert_eq!(plan.items[0].wanted, PackageState::Uninstalled);
+        assert_eq!(
+            plan.skipped
+                .iter()
+                .map(|s| (s.user_id, s.package.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![(0, Some("com.gone")), (10, None)]
+        );
+    }
+
+    #[test]
+    fn users_are_mapped_by_kind_then_order() {
+        let backup_user = |id, kind| UserBackup {
+            id,
+            kind,
+            packages: vec![],
+        };
+        let device_user = |id, index, kind| User {
+            id,
+            index,
+            protected: false,
+            kind,
+        };
+        let backup_users = [
+            backup_user(0, UserKind::Primary),
+            backup_user(10, UserKind::Managed),
+            backup_user(11, UserKind::Unknown),
+            backup_user(12, UserKind::Guest),
+        ];
+        let device_users = [
+            device_user(0, 0, UserKind::Primary),
+            device_user(10, 1, UserKind::Secondary),
+            device_user(11, 2, UserKind::Managed),
+        ];
+        let ids = |mapping: Vec<(u16, Option<User>)>| {
+            mapping
+                .into_iter()
+                .map(|(from, to)| (from, to.map(|u| u.id)))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ids(map_users(&backup_users, &device_users, false)),
+            vec![(0, Some(0)), (10, Some(11)), (11, Some(10)), (12, None)]
+        );
+        assert_eq!(
+            ids(map_users(&backup_users, &device_users, true)),
+            vec![(0, Some(0)), (10, Some(10)), (11, Some(11)), (12, None)]
+        );
+    }
+
+    #[test]
+    fn only_selected_items_are_restored() {
+        let phone = Phone {
+            android_sdk: 30,
+            user_list: vec![User::default()],
+            ..Phone::default()
+        };
+        let item = |name: &str, selected| RestoreItem {
+            user: User::default(),
+            index: 0,
+            package: CorePackage {
+                name: name.into(),
+                state: PackageState::Uninstalled,
+            },
+            wanted: PackageState::Enabled,
+            selected,
+        };
+        let packages = restore_commands(
+            &phone,
+            &[item("com.kept", true), item("com.skipped", false)],
+        );
+        assert_eq!(packages.len(), 1);
+        assert!(packages[0].entry.commands[0].contains("com.kept"));
+        assert_eq!(packages[0].entry.action, JournalAction::Restore);
+    }
+
+    #[test]
+    fn plan_of_reinstalls_only_is_not_empty() {
+        assert!(RestorePlan::default().is_empty());
+        // Apps fully removed from the device, only held in the archive
+        let plan = RestorePlan {
+            reinstalls: vec![ReinstallItem {
+                user: User::default(),
+                package: "com.gone".into(),
+                wanted: PackageState::Enabled,
+                apks: vec!["base.apk".into()],
+                selected: true,
+            }],
+            ..RestorePlan::default()
+        };
+        assert!(!plan.is_empty());
+    }
+
+    #[test]
+    fn dry_run_lists_every_command() {
+        let phone = Phone {
+            android_sdk: 30,
+            user_list: vec![User::default()],
+            adb_id: "abc123".into(),
+            ..Phone::default()
+        };
+        let plan = RestorePlan {
+            items: vec![RestoreItem {
+                user: User::default(),
+                index: 0,
+                package: CorePackage {
+                    name: "com.enabled".into(),
+                    state: PackageState::Disabled,
+                },
+                wanted: PackageState::Uninstalled,
+                selected: true,
+            }],
+            reinstalls: vec![ReinstallItem {
+                user: User::default(),
+                package: "com.gone".into(),
+                wanted: PackageState::Disabled,
+                apks: vec!["base.apk".into()],
+                selected: true,
+            }],
+            ..RestorePlan::default()
+        };
+        let steps = dry_run(&phone, &plan);
+        assert_eq!(
+            steps[0].commands,
+            ["adb -s abc123 shell pm uninstall --user 0 com.enabled"]
+        );
+        // What is previewed is what is run
+        let run: Vec<_> = restore_commands(&phone, &plan.items)
+            .into_iter()
+            .map(|p| p.entry.commands)
+            .collect();
+        let previewed: Vec<_> = steps[..run.len()]
+            .iter()
+            .map(|step| {
+                step.commands
+                    .iter()
+                    .map(|c| c.trim_start_matches("adb -s abc123 shell ").to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(previewed, run);
+        assert_eq!(
+            steps[1].commands,
+            [
+                "adb -s abc123 install-multiple --user 0 base.apk",
+                "adb -s abc123 shell pm disable-user --user 0 com.gone"
+            ]
+        );
+        let text = dry_run_text(&phone, "backup", &steps);
+        assert!(text.contains("# user 0: com.gone (not installed -> Disabled)"));
+    }
+
+    #[test]
+    fn only_expired_snapshots_are_pruned() {
+        let dir = std::env::temp_dir().join("uad_pruned_snapshots");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("writable temp dir");
+        let now = chrono::Local::now();
+        let write = |file: &str, days_ago: i64, trigger: BackupTrigger| {
+            let backup = PhoneBackup {
+                version: BACKUP_FORMAT_VERSION,
+                device_id: "abc123".into(),
+                metadata: Some(BackupMetadata {
+                    brand: "Brand".into(),
+                    model: "Model".into(),
+                    android_sdk: 34,
+                    fingerprint: String::new(),
+                    app_version: String::new(),
+                    list_version: String::new(),
+                    timestamp: (now - chrono::Duration::days(days_ago)).to_rfc3339(),
+                    trigger,
+                }),
+                users: vec![],
+            };
+            fs::write(dir.join(file), serde_json::to_string(&backup).unwrap()).unwrap();
+        };
+        write("2024-01-01_00-00-00.json", 30, BackupTrigger::Manual);
+        write(
+            "2024-01-02_00-00-00_before-apply.json",
+            20,
+            BackupTrigger::BeforeApply,
+        );
+        write(
+            "2024-01-03_00-00-00_before-restore.json",
+            10,
+            BackupTrigger::BeforeRestore,
+        );
+        write(
+            "2024-01-04_00-00-00_before-apply.json",
+            5,
+            BackupTrigger::BeforeApply,
+        );
+
+        let by_days = SnapshotRetention {
+            keep: 15,
+            unit: RetentionUnit::Days,
+        };
+        assert_eq!(prune_snapshots(&dir, by_days, now), 1);
+        assert!(!dir.join("2024-01-02_00-00-00_before-apply.json").exists());
+
+        let by_count = SnapshotRetention {
+            keep: 0,
+            unit: RetentionUnit::Snapshots,
+        };
+        // the most recent snapshot and manual backups are always kept
+        assert_eq!(prune_snapshots(&dir, by_count, now), 1);
+        let mut left: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        assert_eq!(
+            left,
+            [
+                "2024-01-01_00-00-00.json",
+                "2024-01-04_00-00-00_before-apply.json"
+            ]
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn backups_of_the_same_second_are_kept() {
+        let dir = std::env::temp_dir().join("uad_same_second_backups");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("writable temp dir");
+        let now = chrono::Local::now();
+        let first =
+            new_backup_path(&dir, now, BackupTrigger::BeforeApply, ".json").expect("free name");
+        let second =
+            new_backup_path(&dir, now, BackupTrigger::BeforeApply, ".json").expect("free name");
+        assert_ne!(first, second);
+        // The later backup must sort first, as the picker lists the newest at the top.
+        assert!(second > first);
+        let _ = fs::remove_dir_all(dir);
     }
 }
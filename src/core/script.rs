@@ -0,0 +1,247 @@
+//! Standalone scripts applying (and reverting) a selection,
+//! for devices debloated without UAD-ng.
+//!
+//! The POSIX `sh` scripts are run on the device, the batch ones on a Windows host.
+//! Both contain the exact commands UAD-ng would run, which depend on the Android version
+//! and on the users of the device they were generated for.
+
+use crate::core::journal::{JournalAction, JournalEntry};
+use crate::core::sync::Phone;
+use crate::core::utils::NAME;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+
+/// A change of the selection, and the description of its package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptChange {
+    pub entry: JournalEntry,
+    pub description: String,
+}
+
+/// Where the scripts were written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptExport {
+    pub apply_sh: PathBuf,
+    pub revert_sh: PathBuf,
+    pub apply_bat: PathBuf,
+    pub revert_bat: PathBuf,
+    pub changes: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shell {
+    /// Run on the device
+    Posix,
+    /// Run on a Windows host, through `adb shell`
+    Batch,
+}
+
+impl Shell {
+    fn comment(self, script: &mut String, line: &str) {
+        match self {
+            Self::Posix if line.is_empty() => script.push_str("#\n"),
+            Self::Posix => {
+                let _ = writeln!(script, "# {line}");
+            }
+            Self::Batch => {
+                let _ = writeln!(script, "REM {line}");
+            }
+        }
+    }
+
+    fn command(self, script: &mut String, command: &str, on_failure: Option<&str>) {
+        let _ = match (self, on_failure) {
+            (Self::Posix, Some(msg)) => writeln!(script, "{command} || echo 'FAILED: {msg}'"),
+            (Self::Posix, None) => writeln!(script, "{command}"),
+            // `>` would be a redirection
+            (Self::Batch, Some(msg)) => writeln!(
+                script,
+                "adb shell {command} || echo FAILED: {}",
+                msg.replace('>', "^>")
+            ),
+            (Self::Batch, None) => writeln!(script, "adb shell {command}"),
+        };
+    }
+}
+
+/// The changes reverting `changes`, in reverse order
+fn revert(phone: &Phone, changes: &[ScriptChange]) -> Vec<ScriptChange> {
+    changes
+        .iter()
+        .rev()
+        .filter_map(|c| {
+            let entry = JournalEntry::plan(
+                phone,
+                &c.entry.package,
+                c.entry.user_id,
+                c.entry.to,
+                c.entry.from,
+                JournalAction::Undo,
+            )?;
+            Some(ScriptChange {
+                entry,
+                description: c.description.clone(),
+            })
+        })
+        .collect()
+}
+
+fn script(phone: &Phone, changes: &[ScriptChange], shell: Shell, title: &str) -> String {
+    let mut script = match shell {
+        Shell::Posix => "#!/system/bin/sh\n".to_string(),
+        Shell::Batch => "@echo off\n".to_string(),
+    };
+    let users: Vec<String> = phone.user_list.iter().map(|u| u.id.to_string()).collect();
+    let header = [
+        format!("{title}: {} change(s)", changes.len()),
+        format!(
+            "Generated by {NAME} v{} on {}",
+            env!("CARGO_PKG_VERSION"),
+            chrono::Local::now().format("%Y-%m-%d %H:%M")
+        ),
+        format!(
+            "for {} (Android SDK {}, users {})",
+            phone.model,
+            phone.android_sdk,
+            users.join(" ")
+        ),
+        "The commands depend on the Android version and on the users:".to_string(),
+        "only run this script on a matching device.".to_string(),
+        match shell {
+            Shell::Posix => "Run it with `adb push <script> /data/local/tmp/`, \
+                then `adb shell sh /data/local/tmp/<script>`"
+                .to_string(),
+            Shell::Batch => {
+                "If several devices are connected, set ANDROID_SERIAL to the one to change"
+                    .to_string()
+            }
+        },
+    ];
+    for line in &header {
+        shell.comment(&mut script, line);
+    }
+
+    for change in changes {
+        script.push('\n');
+        shell.comment(&mut script, &change.entry.to_string());
+        for line in change.description.lines().map(str::trim_end) {
+            shell.comment(&mut script, line);
+        }
+        if change.entry.commands.is_empty() {
+            shell.comment(&mut script, "Not possible on this Android version");
+        }
+        // Only the 1st command changes the state, the following ones only clean up
+        for (i, command) in change.entry.commands.iter().enumerate() {
+            let failure = (i == 0).then(|| change.entry.to_string());
+            shell.command(&mut script, command, failure.as_deref());
+        }
+    }
+
+    match shell {
+        Shell::Posix => script,
+        Shell::Batch => script.replace('\n', "\r\n"),
+    }
+}
+
+/// The `sh` script of `changes`, its revert, then the batch ones
+#[must_use]
+pub fn build_scripts(phone: &Phone, changes: &[ScriptChange]) -> [String; 4] {
+    let reverted = revert(phone, changes);
+    [
+        script(phone, changes, Shell::Posix, "Debloat"),
+        script(phone, &reverted, Shell::Posix, "Revert of the debloat"),
+        script(phone, changes, Shell::Batch, "Debloat"),
+        script(phone, &reverted, Shell::Batch, "Revert of the debloat"),
+    ]
+}
+
+/// Write the scripts of `changes` in the same directory where UAD-ng is located
+pub async fn export_scripts(
+    phone: Phone,
+    changes: Vec<ScriptChange>,
+) -> Result<ScriptExport, String> {
+    let stem = format!(
+        "debloat_{}_{}",
+        phone
+            .model
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_"),
+        chrono::Local::now().format("%Y%m%d_%H%M%S"),
+    );
+    let [apply_sh, revert_sh, apply_bat, revert_bat] = build_scripts(&phone, &changes);
+    let export = ScriptExport {
+        apply_sh: PathBuf::from(format!("{stem}.sh")),
+        revert_sh: PathBuf::from(format!("{stem}_revert.sh")),
+        apply_bat: PathBuf::from(format!("{stem}.bat")),
+        revert_bat: PathBuf::from(format!("{stem}_revert.bat")),
+        changes: changes.len(),
+    };
+    for (path, script) in [
+        (&export.apply_sh, apply_sh),
+        (&export.revert_sh, revert_sh),
+        (&export.apply_bat, apply_bat),
+        (&export.revert_bat, revert_bat),
+    ] {
+        fs::write(path, script).map_err(|e| format!("{}: {e}", path.display()))?;
+    }
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::sync::User;
+    use crate::core::uad_lists::PackageState;
+
+    #[test]
+    fn apply_and_revert() {
+        let phone = Phone {
+            model: "Model".into(),
+            android_sdk: 34,
+            user_list: vec![
+                User::default(),
+                User {
+                    id: 10,
+                    index: 1,
+                    ..User::default()
+                },
+            ],
+            adb_id: "abc123".into(),
+        };
+        let change = |package, user_id, to, description: &str| ScriptChange {
+            entry: JournalEntry::plan(
+                &phone,
+                package,
+                user_id,
+                PackageState::Enabled,
+                to,
+                JournalAction::Change,
+            )
+            .unwrap(),
+            description: description.to_string(),
+        };
+        let changes = [
+            change("com.a", 0, PackageState::Uninstalled, "Ads\nand tracking"),
+            change("com.b", 10, PackageState::Disabled, ""),
+        ];
+        let [apply_sh, revert_sh, apply_bat, revert_bat] = build_scripts(&phone, &changes);
+
+        assert!(apply_sh.contains(
+            "# com.a (user 0): Enabled -> Uninstalled\n# Ads\n# and tracking\n\
+            pm uninstall --user 0 com.a || echo 'FAILED: com.a (user 0): Enabled -> Uninstalled'\n"
+        ));
+        assert!(apply_sh.contains("\nam force-stop --user 10 com.b\n"));
+        let b = revert_sh.find("pm enable --user 10 com.b").unwrap();
+        let a = revert_sh
+            .find("cmd package install-existing --user 0 com.a")
+            .unwrap();
+        assert!(b < a, "reverted in reverse order");
+
+        assert!(
+            apply_bat.contains("\r\nREM and tracking\r\nadb shell pm uninstall --user 0 com.a || ")
+        );
+        assert!(apply_bat.contains("|| echo FAILED: com.a (user 0): Enabled -^> Uninstalled\r\n"));
+        assert!(!apply_bat.replace("\r\n", "").contains('\n'));
+        assert!(revert_bat.contains("adb shell pm enable --user 10 com.b"));
+    }
+}
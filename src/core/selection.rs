@@ -0,0 +1,277 @@
+//! Import of a package selection, the reverse of
+//! [`export_selection`](crate::core::utils::export_selection).
+
+use crate::core::journal::{JournalAction, JournalEntry};
+use crate::core::profile::{Profile, ProfileChange, ProfilePlan};
+use crate::core::sync::{Phone, User};
+use crate::core::uad_lists::{Opposite, PackageState, Removal};
+use crate::core::utils::Error;
+use crate::gui::widgets::package_row::PackageRow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Content of a selection file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportedSelection {
+    /// From a plain text file (one package per line) or a CSV file (packages in the 1st column)
+    Names(Vec<String>),
+    Profile(Box<Profile>),
+}
+
+/// Pick a selection file: plain text, CSV, or a profile
+pub async fn pick_selection_file() -> Result<PathBuf, Error> {
+    let picked_file = rfd::AsyncFileDialog::new()
+        .add_filter("Package list", &["txt", "csv", "toml", "json"])
+        .add_filter("All files", &["*"])
+        .pick_file()
+        .await
+        .ok_or(Error::DialogClosed)?;
+
+    Ok(picked_file.path().to_owned())
+}
+
+/// Parse a selection file according to its extension
+pub fn read_selection(path: &Path) -> Result<ImportedSelection, String> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "toml" | "json" => Profile::load(path).map(|p| ImportedSelection::Profile(Box::new(p))),
+        "csv" => {
+            let mut reader = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_path(path)
+                .map_err(|e| e.to_string())?;
+            let mut names = vec![];
+            for record in reader.records() {
+                let record = record.map_err(|e| e.to_string())?;
+                if let Some(name) = record.get(0).map(str::trim)
+                    // header of `export_packages`
+                    && !name.is_empty()
+                    && name != "Package Name"
+                {
+                    names.push(name.to_string());
+                }
+            }
+            Ok(ImportedSelection::Names(names))
+        }
+        _ => {
+            let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+            Ok(ImportedSelection::Names(
+                text.lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .map(ToString::to_string)
+                    .collect(),
+            ))
+        }
+    }
+}
+
+/// How the entries of a selection file match the packages of a user
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelectionMatch {
+    pub path: PathBuf,
+    /// Indexes of the packages to change, with the state wanted for them
+    pub matched: Vec<(usize, PackageState)>,
+    /// Names which aren't packages of the user
+    pub unknown: Vec<String>,
+    /// Packages of a plain list which are already removed
+    pub already_removed: Vec<String>,
+    /// Packages of a plain list which selecting wouldn't remove in the current mode
+    /// (disabled packages, which would be enabled in uninstall mode)
+    pub not_selectable: Vec<String>,
+    /// `Unsafe` packages, which can't be selected outside of expert mode
+    pub unsafe_skipped: Vec<String>,
+}
+
+/// Match `selection` against the `packages` of `user`.
+///
+/// Packages of a plain list are to be removed (disabled in `disable_mode`),
+/// those of a profile only if their state differs from the wanted one.
+#[must_use]
+pub fn match_selection(
+    path: PathBuf,
+    selection: &ImportedSelection,
+    user: User,
+    packages: &[PackageRow],
+    expert_mode: bool,
+    disable_mode: bool,
+) -> SelectionMatch {
+    let mut result = SelectionMatch {
+        path,
+        ..SelectionMatch::default()
+    };
+    let index_of = |name: &str| packages.iter().position(|p| p.name == name);
+    let mut candidates = vec![];
+    match selection {
+        ImportedSelection::Names(names) => {
+            let removed = PackageState::Enabled.opposite(disable_mode);
+            for name in names {
+                match index_of(name) {
+                    None => result.unknown.push(name.clone()),
+                    Some(i)
+                        if matches!(packages[i].state, PackageState::Uninstalled)
+                            || packages[i].state == removed =>
+                    {
+                        result.already_removed.push(name.clone());
+                    }
+                    // Selecting toggles the state
+                    Some(i) if packages[i].state.opposite(disable_mode) != removed => {
+                        result.not_selectable.push(name.clone());
+                    }
+                    Some(i) => candidates.push((i, removed)),
+                }
+            }
+        }
+        ImportedSelection::Profile(profile) => {
+            result.unknown = profile
+                .exact_names(user.id)
+                .filter(|name| index_of(name).is_none())
+                .map(ToString::to_string)
+                .collect();
+            candidates = packages
+                .iter()
+                .enumerate()
+                .filter_map(|(i, p)| {
+                    profile
+                        .desired_state(user.id, &p.name, p.uad_list, p.removal)
+                        .filter(|&state| state != p.state)
+                        .map(|state| (i, state))
+                })
+                .collect();
+        }
+    }
+    candidates.sort_unstable_by_key(|&(i, _)| i);
+    candidates.dedup_by_key(|&mut (i, _)| i);
+    for (i, state) in candidates {
+        if packages[i].removal == Removal::Unsafe && !expert_mode {
+            result.unsafe_skipped.push(packages[i].name.clone());
+        } else {
+            result.matched.push((i, state));
+        }
+    }
+    result.unknown.sort_unstable();
+    result.unknown.dedup();
+    result
+}
+
+impl SelectionMatch {
+    /// Changes bringing the matched `packages` of `user` to the state wanted for them,
+    /// to review and apply an imported profile as is
+    #[must_use]
+    pub fn plan(
+        &self,
+        name: &str,
+        phone: &Phone,
+        user: User,
+        packages: &[PackageRow],
+    ) -> ProfilePlan {
+        ProfilePlan {
+            profile: name.to_string(),
+            changes: self
+                .matched
+                .iter()
+                .filter_map(|&(index, to)| {
+                    let package = packages.get(index)?;
+                    let entry = JournalEntry::plan(
+                        phone,
+                        &package.name,
+                        user.id,
+                        package.state,
+                        to,
+                        JournalAction::Change,
+                    )?;
+                    Some(ProfileChange {
+                        i_user: user.index,
+                        index,
+                        entry,
+                    })
+                })
+                .collect(),
+            unsafe_skipped: self.unsafe_skipped.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::uad_lists::UadList;
+
+    #[test]
+    fn import_list_and_csv() {
+        let dir = std::env::temp_dir().join("uad_selection_tests");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let txt = dir.join("selection_export.txt");
+        fs::write(
+            &txt,
+            "com.a\n# comment\n\ncom.gone\ncom.unsafe\ncom.missing\ncom.off\n",
+        )
+        .unwrap();
+        let csv = dir.join("uninstalled.csv");
+        fs::write(&csv, "Package Name,Description\ncom.a,\"A, the app\"\n").unwrap();
+
+        let row = |name, state, removal| PackageRow::test(name, state, UadList::Oem, removal);
+        let packages = [
+            row("com.a", PackageState::Enabled, Removal::Recommended),
+            row("com.gone", PackageState::Uninstalled, Removal::Advanced),
+            row("com.unsafe", PackageState::Enabled, Removal::Unsafe),
+            row("com.off", PackageState::Disabled, Removal::Recommended),
+        ];
+
+        let selection = read_selection(&txt).unwrap();
+        let result = match_selection(txt, &selection, User::default(), &packages, false, false);
+        assert_eq!(result.matched, [(0, PackageState::Uninstalled)]);
+        assert_eq!(result.already_removed, ["com.gone"]);
+        assert_eq!(result.unsafe_skipped, ["com.unsafe"]);
+        assert_eq!(result.unknown, ["com.missing"]);
+        // Selecting it would enable it
+        assert_eq!(result.not_selectable, ["com.off"]);
+
+        // The states of a profile are planned as they are, not toggled
+        let profile = ImportedSelection::Profile(Box::new(Profile {
+            name: "Imported".into(),
+            description: String::new(),
+            rules: vec![],
+            packages: [
+                ("com.a".to_string(), PackageState::Disabled),
+                ("com.off".to_string(), PackageState::Uninstalled),
+            ]
+            .into(),
+            users: vec![],
+        }));
+        let imported = match_selection(
+            "profile.toml".into(),
+            &profile,
+            User::default(),
+            &packages,
+            false,
+            false,
+        );
+        let phone = Phone {
+            model: "Model".into(),
+            android_sdk: 34,
+            user_list: vec![User::default()],
+            adb_id: "abc123".into(),
+        };
+        let plan = imported.plan("Imported", &phone, User::default(), &packages);
+        let changes: Vec<_> = plan.changes.iter().map(|c| c.entry.to_string()).collect();
+        assert_eq!(
+            changes,
+            [
+                "com.a (user 0): Enabled -> Disabled",
+                "com.off (user 0): Disabled -> Uninstalled"
+            ]
+        );
+
+        assert_eq!(
+            read_selection(&csv).unwrap(),
+            ImportedSelection::Names(vec!["com.a".into()])
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+}
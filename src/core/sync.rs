@@ -1,5 +1,5 @@
 use crate::core::{
-    adb::{ACommand as AdbCommand, PM_CLEAR_PACK, to_trimmed_utf8},
+    adb::{ACommand as AdbCommand, PM_CLEAR_PACK, UserKind, to_trimmed_utf8},
     uad_lists::PackageState,
 };
 use crate::gui::{views::list::PackageInfo, widgets::package_row::PackageRow};
@@ -47,6 +47,7 @@ pub struct User {
     pub id: u16,
     pub index: usize,
     pub protected: bool,
+    pub kind: UserKind,
 }
 
 impl std::fmt::Display for User {
@@ -182,7 +183,8 @@ pub fn apply_pkg_state_commands(
                 i if i >= 23 => vec!["cmd package install-existing"],
                 21 | 22 => vec!["pm unhide"],
                 19 | 20 => vec!["pm unblock", PM_CLEAR_PACK],
-                _ => unreachable!("already prevented by the GUI"),
+                // Older devices can't get it back, and offline devices may not know their SDK
+                _ => vec![],
             },
             _ => vec![],
         },
@@ -208,6 +210,21 @@ pub fn apply_pkg_state_commands(
     request_builder(&commands, &package.name, user)
 }
 
+/// Run the commands of a package state change one after the other.
+///
+/// Only the 1st one changes the state, so it's the only one whose failure matters:
+/// the following ones only clean up.
+pub fn run_pkg_state_commands(serial: &str, commands: &[String]) -> Result<(), String> {
+    for (i, command) in commands.iter().enumerate() {
+        match AdbCommand::new().shell(serial).pkg_state_change(command) {
+            Ok(out) => info!("{command} -> {out}"),
+            Err(err) if i == 0 => return Err(format!("{command} -> {err}")),
+            Err(err) => warn!("{command} -> {err}"),
+        }
+    }
+    Ok(())
+}
+
 /// Build a command request to be sent via ADB to a device.
 /// `commands` accepts one or more ADB shell commands
 /// which act on a common `package` and `user`.
@@ -249,6 +266,18 @@ pub fn get_device_brand(serial: &str) -> String {
         .unwrap_or_default()
 }
 
+/// Get the build fingerprint by querying the `ro.build.fingerprint` property.
+/// It changes with every system update.
+///
+/// If `serial` is empty, it lets ADB choose the default device.
+pub fn get_device_fingerprint(serial: &str) -> String {
+    AdbCommand::new()
+        .shell(serial)
+        .getprop("ro.build.fingerprint")
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
 /// Get Android SDK version by querying the
 // `ro.build.version.sdk` property or defaulting to 0.
 ///
@@ -305,6 +334,7 @@ pub fn list_users_idx_prot(device_serial: &str) -> Vec<User> {
                         id,
                         index: i,
                         protected: is_protected_user(id, device_serial),
+                        kind: user.kind(),
                     }
                 })
                 .collect()
@@ -312,6 +342,16 @@ pub fn list_users_idx_prot(device_serial: &str) -> Vec<User> {
         .unwrap_or_default()
 }
 
+/// Query everything UAD-ng needs to know about the device `serial`
+pub fn device_info(serial: &str) -> Phone {
+    Phone {
+        model: format!("{} {}", get_device_brand(serial), get_device_model(serial)),
+        android_sdk: get_android_sdk(serial),
+        user_list: list_users_idx_prot(serial),
+        adb_id: serial.to_string(),
+    }
+}
+
 /// This matches serials (`getprop ro.serialno`)
 /// that are authorized by the user.
 pub async fn get_devices_list() -> Vec<Phone> {
@@ -324,13 +364,7 @@ pub async fn get_devices_list() -> Vec<Phone> {
                     return OperationResult::Retry(vec![]);
                 }
                 for device in devices {
-                    let serial = &device.0;
-                    device_list.push(Phone {
-                        model: format!("{} {}", get_device_brand(serial), get_device_model(serial)),
-                        android_sdk: get_android_sdk(serial),
-                        user_list: list_users_idx_prot(serial),
-                        adb_id: serial.to_string(),
-                    });
+                    device_list.push(device_info(&device.0));
                 }
                 OperationResult::Ok(device_list)
             }
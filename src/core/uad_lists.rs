@@ -1,12 +1,15 @@
 use crate::CACHE_DIR;
-use crate::core::utils::{format_diff_time_from_now, last_modified_date};
-use retry::{OperationResult, delay::Fixed, retry};
+use crate::core::utils::{format_diff_time_from_now, is_w, last_modified_date, write_atomic};
+use retry::{OperationResult, delay::Exponential, retry};
 use serde::{Deserialize, Serialize};
 use serde_json;
+use sha2::{Digest, Sha256};
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt::Write;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use ureq::{Body, http::Response};
 
 pub const LIST_FNAME: &str = "uad_lists.json";
 
@@ -41,12 +44,13 @@ pub enum UadList {
     Unlisted,
 }
 
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub enum UadListState {
     #[default]
     Downloading,
     Done,
-    Failed,
+    /// The reason is shown to the user
+    Failed(String),
 }
 
 impl std::fmt::Display for UadListState {
@@ -55,7 +59,7 @@ impl std::fmt::Display for UadListState {
         let s = match self {
             Self::Downloading => "Checking updates...".to_string(),
             Self::Done => format!("Done (last was {})", format_diff_time_from_now(date)),
-            Self::Failed => "Failed to check update!".to_string(),
+            Self::Failed(e) => format!("Failed to check update! ({e})"),
         };
         write!(f, "{s}")
     }
@@ -201,66 +205,638 @@ impl From<Removal> for Cow<'_, str> {
 }
 
 pub type PackageHashMap = HashMap<String, Package>;
-pub fn load_debloat_lists(remote: bool) -> Result<PackageHashMap, PackageHashMap> {
-    let cached_uad_lists: PathBuf = CACHE_DIR.join(LIST_FNAME);
-    let mut error = false;
-    let list: PackageHashMap = if remote {
-        retry(Fixed::from_millis(1000).take(60), || {
-            match ureq::get(format!(
-                "https://raw.githubusercontent.com\
-                    /Universal-Debloater-Alliance\
-                    /universal-android-debloater\
-                    /main\
-                    /resources\
-                    /assets\
-                    /{LIST_FNAME}"
-            ))
-            .call()
-            {
-                Ok(mut data) => {
-                    // https://github.com/Universal-Debloater-Alliance/universal-android-debloater-next-generation/discussions/608
-                    let text = data
-                        .body_mut()
-                        .with_config()
-                        .limit(1 << (3 + 10 + 10))
-                        .read_to_string()
-                        .expect("remote list is bigger than 8MiB");
-                    fs::write(cached_uad_lists.clone(), &text).expect("Unable to write file");
-                    let list: PackageHashMap =
-                        serde_json::from_str(&text).expect("Unable to parse");
-                    OperationResult::Ok(list)
-                }
-                Err(e) => {
-                    warn!("Could not load remote debloat list: {e}");
-                    error = true;
-                    OperationResult::Retry(PackageHashMap::new())
+
+/// Upstream location of the community-maintained list
+pub const UPSTREAM_LIST_URL: &str = "https://raw.githubusercontent.com\
+    /Universal-Debloater-Alliance\
+    /universal-android-debloater\
+    /main\
+    /resources\
+    /assets\
+    /uad_lists.json";
+
+/// Kind of location a [`ListSource`] points to
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ListSourceKind {
+    /// HTTP(S) URL of a `uad_lists.json`-format file
+    #[default]
+    Url,
+    /// Local `uad_lists.json`-format file
+    File,
+    /// Local checkout of the upstream git repository
+    GitCheckout,
+}
+
+impl ListSourceKind {
+    /// Guess the kind of a user-provided location
+    pub fn detect(location: &str) -> Self {
+        if location.starts_with("http://") || location.starts_with("https://") {
+            Self::Url
+        } else if Path::new(location).is_dir() {
+            Self::GitCheckout
+        } else {
+            Self::File
+        }
+    }
+}
+
+impl std::fmt::Display for ListSourceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Url => "URL",
+            Self::File => "File",
+            Self::GitCheckout => "Git checkout",
+        })
+    }
+}
+
+/// A place debloat lists are loaded from.
+///
+/// All enabled sources are merged in ascending `priority` order:
+/// a package defined by several sources takes the **whole** entry
+/// of the source with the highest priority.
+/// Sources with equal priority keep their configuration order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListSource {
+    pub kind: ListSourceKind,
+    /// URL or path, depending on `kind`
+    pub location: String,
+    pub priority: i32,
+    pub enabled: bool,
+    /// URL of the SHA-256 (`sha256sum` format) a downloaded list must match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum_url: Option<String>,
+}
+
+/// The upstream list.
+///
+/// It's exempt from the checksum check: upstream publishes no SHA-256 of it,
+/// so a download is only checked to be a valid list, served over HTTPS.
+impl Default for ListSource {
+    fn default() -> Self {
+        Self {
+            kind: ListSourceKind::Url,
+            location: UPSTREAM_LIST_URL.to_string(),
+            priority: 0,
+            enabled: true,
+            checksum_url: None,
+        }
+    }
+}
+
+impl ListSource {
+    #[must_use]
+    pub fn new(location: &str, priority: i32) -> Self {
+        Self {
+            kind: ListSourceKind::detect(location),
+            location: location.to_string(),
+            priority,
+            enabled: true,
+            checksum_url: None,
+        }
+    }
+
+    #[must_use]
+    pub fn is_upstream(&self) -> bool {
+        self.kind == ListSourceKind::Url && self.location == UPSTREAM_LIST_URL
+    }
+
+    /// Where the last successful download of this source is kept.
+    /// The upstream list keeps the historical [`LIST_FNAME`].
+    #[must_use]
+    pub fn cache_path(&self) -> PathBuf {
+        self.cache_path_in(&CACHE_DIR)
+    }
+
+    fn cache_path_in(&self, cache_dir: &Path) -> PathBuf {
+        if self.is_upstream() {
+            return cache_dir.join(LIST_FNAME);
+        }
+        let sanitized: String = self
+            .location
+            .bytes()
+            .map(|c| if is_w(c) { c as char } else { '_' })
+            .collect();
+        cache_dir.join(format!("uad_lists_{sanitized}.json"))
+    }
+}
+
+impl std::fmt::Display for ListSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.kind, self.location)
+    }
+}
+
+#[must_use]
+pub fn default_list_sources() -> Vec<ListSource> {
+    vec![ListSource::default()]
+}
+
+/// Load and merge every enabled source (see [`ListSource`] for the merge rules).
+///
+/// If `remote` is `false`, URL sources are only read from their cache.
+/// `Err` means at least one source couldn't be loaded:
+/// it still holds the best list we could build (from caches or the embedded list),
+/// along with the reasons of the failures.
+pub fn load_debloat_lists(
+    sources: &[ListSource],
+    remote: bool,
+) -> Result<PackageHashMap, (PackageHashMap, String)> {
+    load_debloat_lists_in(sources, remote, &CACHE_DIR)
+}
+
+/// [`load_debloat_lists`], with the caches of URL sources kept in `cache_dir`.
+fn load_debloat_lists_in(
+    sources: &[ListSource],
+    remote: bool,
+    cache_dir: &Path,
+) -> Result<PackageHashMap, (PackageHashMap, String)> {
+    let mut errors = vec![];
+    let mut sources: Vec<&ListSource> = sources.iter().filter(|s| s.enabled).collect();
+    sources.sort_by_key(|s| s.priority);
+
+    let mut lists = Vec::with_capacity(sources.len());
+    for source in &sources {
+        match load_source(source, remote, cache_dir) {
+            Ok(list) => lists.push(list),
+            Err(e) => {
+                warn!("Could not load debloat list {source}: {e}");
+                errors.push(e);
+                // Falling back keeps the previous behavior of always having a list
+                if let Some(list) = get_cached_list(source, cache_dir) {
+                    lists.push(list);
+                } else if source.is_upstream() {
+                    lists.push(get_local_lists(cache_dir));
                 }
             }
-        })
-        .unwrap_or_else(|_| get_local_lists())
+        }
+    }
+    if lists.is_empty() {
+        if sources.is_empty() {
+            warn!("No debloat list source is enabled, falling back to the embedded list");
+        } else {
+            warn!(
+                "No enabled debloat list source could be loaded, falling back to the embedded list"
+            );
+        }
+        lists.push(get_local_lists(cache_dir));
+    }
+
+    let list = merge_lists(lists);
+    if errors.is_empty() {
+        Ok(list)
     } else {
-        warn!("Could not load remote debloat list");
-        get_local_lists()
+        Err((list, errors.join("; ")))
+    }
+}
+
+/// Merge lists in order: entries of later lists replace entries of earlier ones.
+#[must_use]
+pub fn merge_lists<I: IntoIterator<Item = PackageHashMap>>(lists: I) -> PackageHashMap {
+    lists
+        .into_iter()
+        .fold(PackageHashMap::new(), |mut acc, list| {
+            acc.extend(list);
+            acc
+        })
+}
+
+fn load_source(
+    source: &ListSource,
+    remote: bool,
+    cache_dir: &Path,
+) -> Result<PackageHashMap, String> {
+    match source.kind {
+        ListSourceKind::Url if remote => fetch_remote_list(
+            &source.location,
+            source.checksum_url.as_deref(),
+            &source.cache_path_in(cache_dir),
+        ),
+        ListSourceKind::Url => {
+            get_cached_list(source, cache_dir).ok_or_else(|| "no cached copy".to_string())
+        }
+        ListSourceKind::File => read_list_file(Path::new(&source.location)),
+        ListSourceKind::GitCheckout => read_list_file(
+            &Path::new(&source.location)
+                .join("resources")
+                .join("assets")
+                .join(LIST_FNAME),
+        ),
+    }
+}
+
+/// HTTP validators and checksum of a cached list,
+/// stored next to it (see [`meta_path`]).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheMeta {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Hex-encoded SHA-256 of the cached file
+    sha256: String,
+}
+
+fn meta_path(cache: &Path) -> PathBuf {
+    cache.with_extension("meta.json")
+}
+
+#[must_use]
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, b| {
+            let _ = write!(hex, "{b:02x}");
+            hex
+        })
+}
+
+/// Short identifier of the upstream list in use:
+/// the checksum of the cached list, or of the embedded one if never downloaded.
+#[must_use]
+pub fn list_version() -> String {
+    let hash = read_cache(&ListSource::default().cache_path()).map_or_else(
+        || sha256_hex(DATA.as_bytes()),
+        |(text, meta)| meta.map_or_else(|| sha256_hex(text.as_bytes()), |m| m.sha256),
+    );
+    hash[..12].to_string()
+}
+
+/// Read a cached list, only if it matches the checksum recorded when it was written.
+/// Caches written by older versions have no metadata, they are trusted as-is.
+fn read_cache(cache: &Path) -> Option<(String, Option<CacheMeta>)> {
+    let text = fs::read_to_string(cache).ok()?;
+    let meta = fs::read_to_string(meta_path(cache))
+        .ok()
+        .and_then(|m| serde_json::from_str::<CacheMeta>(&m).ok());
+    if let Some(m) = &meta
+        && m.sha256 != sha256_hex(text.as_bytes())
+    {
+        warn!("Cached list {} is corrupted, ignoring it", cache.display());
+        return None;
+    }
+    Some((text, meta))
+}
+
+/// Download a list and atomically cache it to `cache` on success.
+///
+/// The request is conditional (`ETag`/`Last-Modified`), so an unchanged list
+/// isn't downloaded again.
+/// A new list replaces the cache only if it parses and,
+/// when `checksum_url` is set, matches the SHA-256 published there.
+pub fn fetch_remote_list(
+    url: &str,
+    checksum_url: Option<&str>,
+    cache: &Path,
+) -> Result<PackageHashMap, String> {
+    let cached = read_cache(cache);
+    let validators = cached.as_ref().and_then(|(_, meta)| meta.as_ref());
+
+    let mut response = http_get(url, validators)?;
+    if response.status() == 304 {
+        debug!("{url} not modified, using cached copy");
+        let (text, _) = cached.ok_or("server answered 304 without a cached copy")?;
+        return parse_list(&text);
+    }
+
+    // https://github.com/Universal-Debloater-Alliance/universal-android-debloater-next-generation/discussions/608
+    let text = response
+        .body_mut()
+        .with_config()
+        .limit(1 << (3 + 10 + 10))
+        .read_to_string()
+        .map_err(|e| format!("{url}: {e}"))?;
+    let list = parse_list(&text)?;
+
+    let sha256 = sha256_hex(text.as_bytes());
+    if let Some(checksum_url) = checksum_url {
+        let expected = http_get(checksum_url, None)?
+            .body_mut()
+            .read_to_string()
+            .map_err(|e| format!("{checksum_url}: {e}"))?;
+        // `sha256sum` format: "<hex>  <file name>"
+        let expected = expected.split_whitespace().next().unwrap_or_default();
+        if !expected.eq_ignore_ascii_case(&sha256) {
+            return Err(format!(
+                "{url}: checksum mismatch (expected {expected}, got {sha256})"
+            ));
+        }
+    }
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
     };
+    let meta = CacheMeta {
+        etag: header("etag"),
+        last_modified: header("last-modified"),
+        sha256,
+    };
+    write_atomic(cache, &text).map_err(|e| format!("{}: {e}", cache.display()))?;
+    let meta = serde_json::to_string(&meta).map_err(|e| e.to_string())?;
+    write_atomic(meta_path(cache), meta).map_err(|e| format!("{}: {e}", cache.display()))?;
+    Ok(list)
+}
 
-    (if error { Err } else { Ok })(list)
+/// `GET` request, retried a few times on network errors only.
+fn http_get(url: &str, validators: Option<&CacheMeta>) -> Result<Response<Body>, String> {
+    retry(Exponential::from_millis(500).take(3), || {
+        let mut request = ureq::get(url);
+        if let Some(meta) = validators {
+            if let Some(etag) = &meta.etag {
+                request = request.header("If-None-Match", etag);
+            }
+            if let Some(date) = &meta.last_modified {
+                request = request.header("If-Modified-Since", date);
+            }
+        }
+        match request.call() {
+            Ok(response) => OperationResult::Ok(response),
+            Err(ureq::Error::StatusCode(code)) => {
+                OperationResult::Err(format!("{url}: HTTP {code}"))
+            }
+            Err(e) => {
+                warn!("Could not load remote debloat list: {e}");
+                OperationResult::Retry(format!("{url}: {e}"))
+            }
+        }
+    })
+    .map_err(|e| e.error)
+}
+
+fn parse_list(text: &str) -> Result<PackageHashMap, String> {
+    serde_json::from_str(text).map_err(|e| format!("invalid list: {e}"))
 }
 
-fn get_local_lists() -> PackageHashMap {
-    let cached_uad_lists = CACHE_DIR.join(LIST_FNAME);
-    serde_json::from_str(
-        fs::read_to_string(cached_uad_lists)
-            .as_deref()
-            .unwrap_or(DATA),
-    )
-    .expect("Unable to parse")
+fn read_list_file(path: &Path) -> Result<PackageHashMap, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    parse_list(&text).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn get_cached_list(source: &ListSource, cache_dir: &Path) -> Option<PackageHashMap> {
+    if source.kind != ListSourceKind::Url {
+        return None;
+    }
+    let (text, _) = read_cache(&source.cache_path_in(cache_dir))?;
+    parse_list(&text).ok()
+}
+
+/// Cached upstream list, or the embedded one if there's no usable cache.
+fn get_local_lists(cache_dir: &Path) -> PackageHashMap {
+    read_cache(&cache_dir.join(LIST_FNAME))
+        .and_then(|(text, _)| {
+            parse_list(&text)
+                .inspect_err(|e| error!("Cached list: {e}"))
+                .ok()
+        })
+        .unwrap_or_else(|| serde_json::from_str(DATA).expect("Unable to parse"))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    const LIST_A: &str = r#"{
+        "com.example.a": {"list": "Oem", "description": "from A", "dependencies": [], "neededBy": [], "labels": [], "removal": "Recommended"},
+        "com.example.shared": {"list": "Oem", "description": "from A", "dependencies": [], "neededBy": [], "labels": [], "removal": "Advanced"}
+    }"#;
+    const LIST_B: &str = r#"{
+        "com.example.shared": {"list": "Misc", "description": "from B", "dependencies": [], "neededBy": [], "labels": [], "removal": "Unsafe"}
+    }"#;
+
+    type Handler = Box<dyn Fn(&str) -> (&'static str, String, String) + Send>;
+
+    /// Minimal local HTTP stand-in: answers one request per handler, in order.
+    /// Handlers get the raw request and return `(status line, headers, body)`.
+    /// Received requests are sent back through the returned channel.
+    fn serve(handlers: Vec<Handler>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for handler in handlers {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0; 4096];
+                let n = stream.read(&mut buf).unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let (status, headers, body) = handler(&request);
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+                tx.send(request).unwrap();
+            }
+        });
+        (format!("http://{addr}/uad_lists.json"), rx)
+    }
+
+    fn serve_once(body: &'static str) -> String {
+        serve(vec![Box::new(move |_| {
+            ("200 OK", String::new(), body.to_string())
+        })])
+        .0
+    }
+
+    fn temp_cache(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join("uad_lists_tests");
+        fs::create_dir_all(&dir).unwrap();
+        let cache = dir.join(name);
+        let _ = fs::remove_file(&cache);
+        let _ = fs::remove_file(meta_path(&cache));
+        cache
+    }
+
+    /// Empty cache directory, so tests never touch the real [`CACHE_DIR`].
+    fn temp_cache_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join("uad_lists_tests").join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn temp_file(name: &str, content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join("uad_lists_tests");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
     #[test]
     fn test_parse_json() {
         let _: PackageHashMap = serde_json::from_str(DATA).expect("Unable to parse");
     }
+
+    #[test]
+    fn detect_source_kind() {
+        assert_eq!(
+            ListSourceKind::detect("https://example.org/l.json"),
+            ListSourceKind::Url
+        );
+        assert_eq!(
+            ListSourceKind::detect("http://localhost/l.json"),
+            ListSourceKind::Url
+        );
+        assert_eq!(
+            ListSourceKind::detect(&std::env::temp_dir().to_string_lossy()),
+            ListSourceKind::GitCheckout
+        );
+        assert_eq!(
+            ListSourceKind::detect("/nonexistent/l.json"),
+            ListSourceKind::File
+        );
+        assert!(ListSource::default().is_upstream());
+    }
+
+    #[test]
+    fn fetch_from_local_http() {
+        let url = serve_once(LIST_A);
+        let cache = temp_cache("http_cache.json");
+        let list = fetch_remote_list(&url, None, &cache).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(fs::read_to_string(cache).unwrap(), LIST_A);
+    }
+
+    #[test]
+    fn higher_priority_wins() {
+        let url = serve_once(LIST_A);
+        let file_b = temp_file("b.json", LIST_B);
+        let sources = [
+            ListSource::new(&file_b.to_string_lossy(), 10),
+            ListSource::new(&url, 0),
+            ListSource {
+                enabled: false,
+                ..ListSource::new("/nonexistent/disabled.json", 20)
+            },
+        ];
+        let cache_dir = temp_cache_dir("priority");
+        let list = load_debloat_lists_in(&sources, true, &cache_dir).unwrap();
+        assert_eq!(list.len(), 2);
+        let shared = &list["com.example.shared"];
+        assert_eq!(shared.description, "from B");
+        assert_eq!(shared.removal, Removal::Unsafe);
+        assert_eq!(shared.list, UadList::Misc);
+        assert_eq!(list["com.example.a"].description, "from A");
+    }
+
+    #[test]
+    fn git_checkout_source() {
+        let checkout = std::env::temp_dir().join("uad_lists_tests_checkout");
+        let assets = checkout.join("resources").join("assets");
+        fs::create_dir_all(&assets).unwrap();
+        fs::write(assets.join(LIST_FNAME), LIST_B).unwrap();
+        let source = ListSource::new(&checkout.to_string_lossy(), 0);
+        assert_eq!(source.kind, ListSourceKind::GitCheckout);
+        let cache_dir = temp_cache_dir("checkout");
+        let list = load_debloat_lists_in(&[source], false, &cache_dir).unwrap();
+        assert_eq!(list["com.example.shared"].description, "from B");
+    }
+
+    #[test]
+    fn failing_source_is_reported() {
+        let sources = [ListSource::new("/nonexistent/missing.json", 0)];
+        let cache_dir = temp_cache_dir("failing");
+        let (list, _) = load_debloat_lists_in(&sources, false, &cache_dir).unwrap_err();
+        // nothing could be loaded, so the embedded list is used
+        assert!(!list.is_empty());
+    }
+
+    #[test]
+    fn conditional_request_uses_cache() {
+        let cache = temp_cache("conditional.json");
+        let (url, requests) = serve(vec![
+            Box::new(|_| ("200 OK", "ETag: \"v1\"\r\n".into(), LIST_A.into())),
+            Box::new(|req| {
+                if req.to_ascii_lowercase().contains("if-none-match: \"v1\"") {
+                    ("304 Not Modified", String::new(), String::new())
+                } else {
+                    ("200 OK", String::new(), LIST_B.into())
+                }
+            }),
+        ]);
+        assert_eq!(fetch_remote_list(&url, None, &cache).unwrap().len(), 2);
+        assert_eq!(fetch_remote_list(&url, None, &cache).unwrap().len(), 2);
+        assert!(
+            !requests
+                .recv()
+                .unwrap()
+                .to_ascii_lowercase()
+                .contains("if-none-match")
+        );
+        assert!(
+            requests
+                .recv()
+                .unwrap()
+                .to_ascii_lowercase()
+                .contains("if-none-match")
+        );
+        assert_eq!(fs::read_to_string(cache).unwrap(), LIST_A);
+    }
+
+    #[test]
+    fn corrupted_cache_is_not_trusted() {
+        let cache = temp_cache("corrupted.json");
+        let (url, requests) = serve(vec![
+            Box::new(|_| ("200 OK", "ETag: \"v1\"\r\n".into(), LIST_A.into())),
+            Box::new(|_| ("200 OK", "ETag: \"v1\"\r\n".into(), LIST_A.into())),
+        ]);
+        fetch_remote_list(&url, None, &cache).unwrap();
+        fs::write(&cache, LIST_B).unwrap();
+        assert!(read_cache(&cache).is_none());
+        fetch_remote_list(&url, None, &cache).unwrap();
+        let _ = requests.recv().unwrap();
+        // the corrupted copy can't be used to answer a 304
+        assert!(
+            !requests
+                .recv()
+                .unwrap()
+                .to_ascii_lowercase()
+                .contains("if-none-match")
+        );
+        assert_eq!(fs::read_to_string(cache).unwrap(), LIST_A);
+    }
+
+    #[test]
+    fn invalid_download_keeps_cache() {
+        let cache = temp_cache("invalid.json");
+        let url = serve_once(LIST_A);
+        fetch_remote_list(&url, None, &cache).unwrap();
+        let invalid_url = serve_once("{ not json");
+        assert!(fetch_remote_list(&invalid_url, None, &cache).is_err());
+        assert_eq!(fs::read_to_string(&cache).unwrap(), LIST_A);
+        assert!(read_cache(&cache).is_some());
+    }
+
+    #[test]
+    fn checksum_is_verified() {
+        let cache = temp_cache("checksum.json");
+        let good = sha256_hex(LIST_A.as_bytes());
+        let (url, _requests) = serve(vec![
+            Box::new(|_| ("200 OK", String::new(), LIST_A.into())),
+            Box::new(move |_| ("200 OK", String::new(), format!("{good}  uad_lists.json\n"))),
+            Box::new(|_| ("200 OK", String::new(), LIST_B.into())),
+            Box::new(|_| ("200 OK", String::new(), "0000  uad_lists.json\n".into())),
+        ]);
+        // The stand-in doesn't route, the checksum is simply the next answer
+        assert!(fetch_remote_list(&url, Some(&url), &cache).is_ok());
+        let err = fetch_remote_list(&url, Some(&url), &cache).unwrap_err();
+        assert!(err.contains("checksum mismatch"));
+        assert_eq!(fs::read_to_string(cache).unwrap(), LIST_A);
+    }
+
+    #[test]
+    fn http_errors_are_not_retried() {
+        let cache = temp_cache("not_found.json");
+        let (url, _requests) = serve(vec![Box::new(|_| {
+            ("404 Not Found", String::new(), String::new())
+        })]);
+        let err = fetch_remote_list(&url, None, &cache).unwrap_err();
+        assert!(err.ends_with("HTTP 404"));
+    }
 }
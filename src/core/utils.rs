@@ -90,26 +90,12 @@ pub fn fetch_packages(
         .into_iter()
         .collect();
 
-    let mut description;
-    let mut uad_list;
     let mut state;
-    let mut removal;
     let mut user_package: Vec<PackageRow> = Vec::new();
 
     for pack_name in all_sys_packs {
         let p_name = &pack_name;
         state = PackageState::Uninstalled;
-        description = "[No description]: CONTRIBUTION WELCOMED";
-        uad_list = UadList::Unlisted;
-        removal = Removal::Unlisted;
-
-        if let Some(package) = uad_lists.get(p_name) {
-            if !package.description.is_empty() {
-                description = &package.description;
-            }
-            uad_list = package.list;
-            removal = package.removal;
-        }
 
         if enabled_sys_packs.contains(p_name) {
             state = PackageState::Enabled;
@@ -117,14 +103,45 @@ pub fn fetch_packages(
             state = PackageState::Disabled;
         }
 
-        let package_row =
-            PackageRow::new(p_name, state, description, uad_list, removal, false, false);
-        user_package.push(package_row);
+        user_package.push(package_row(uad_lists, p_name, state));
     }
     user_package.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
     user_package
 }
 
+/// Row of the package `name`, described by `uad_lists`
+pub fn package_row(uad_lists: &PackageHashMap, name: &str, state: PackageState) -> PackageRow {
+    let mut description = "[No description]: CONTRIBUTION WELCOMED";
+    let mut uad_list = UadList::Unlisted;
+    let mut removal = Removal::Unlisted;
+
+    if let Some(package) = uad_lists.get(name) {
+        if !package.description.is_empty() {
+            description = &package.description;
+        }
+        uad_list = package.list;
+        removal = package.removal;
+    }
+
+    PackageRow::new(name, state, description, uad_list, removal, false, false)
+}
+
+/// Packages of every user of a device: `User::index` is the index of their packages
+pub fn fetch_phone_packages(
+    uad_lists: &PackageHashMap,
+    device_serial: &str,
+    user_list: &[User],
+) -> Vec<Vec<PackageRow>> {
+    if user_list.len() <= 1 {
+        vec![fetch_packages(uad_lists, device_serial, None)]
+    } else {
+        user_list
+            .iter()
+            .map(|user| fetch_packages(uad_lists, device_serial, Some(user.id)))
+            .collect()
+    }
+}
+
 pub fn string_to_theme(theme: &str) -> Theme {
     let map: HashMap<String, Theme> = Theme::ALL.iter().map(|value| (value.to_string(), value.clone())).collect();
     map.get(theme).map(|theme| theme.clone()).unwrap_or_default()
@@ -196,6 +213,19 @@ pub async fn export_selection(packages: Vec<PackageRow>) -> Result<bool, String>
     }
 }
 
+/// Write `contents` to a temporary sibling file, then rename it over `path`,
+/// so that readers never observe a partially written file.
+pub fn write_atomic<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DisplayablePath {
     pub path: PathBuf,
@@ -269,4 +299,13 @@ mod tests {
             "uninstalled_packages_19700101.csv".to_string()
         );
     }
+
+    #[test]
+    fn atomic_write_replaces_file() {
+        let path = std::env::temp_dir().join("uad_atomic_write.txt");
+        write_atomic(&path, "first").expect("first write");
+        write_atomic(&path, "second").expect("second write");
+        assert_eq!(fs::read_to_string(&path).expect("read back"), "second");
+        assert!(!path.with_extension("txt.tmp").exists());
+    }
 }
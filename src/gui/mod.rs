@@ -3,9 +3,11 @@ pub mod views;
 pub mod widgets;
 
 use crate::core::adb;
+use crate::core::fleet::selection_profile;
+use crate::core::offline::{list_offline_sources, OfflineSource};
 use crate::core::sync::{get_devices_list, initial_load, Phone};
 use crate::core::theme::OS_COLOR_SCHEME;
-use crate::core::uad_lists::UadListState;
+use crate::core::uad_lists::{PackageState, UadListState};
 use crate::core::update::{get_latest_release, Release, SelfUpdateState, SelfUpdateStatus};
 use crate::core::utils::{string_to_theme, NAME};
 
@@ -13,9 +15,11 @@ use iced::advanced::graphics::image::image_rs::ImageFormat;
 use iced::window::icon;
 use iced::{font, Task};
 use views::about::{About as AboutView, Message as AboutMessage};
+use views::compare::{Compare as CompareView, Message as CompareMessage};
+use views::fleet::{Fleet as FleetView, Message as FleetMessage};
 use views::list::{List as AppsView, LoadingState as ListLoadingState, Message as AppsMessage};
 use views::settings::{Message as SettingsMessage, Settings as SettingsView};
-use widgets::navigation_menu::nav_menu;
+use widgets::navigation_menu::{nav_menu, tab_bar};
 
 use iced::widget::column;
 use iced::{
@@ -34,6 +38,8 @@ enum View {
     List,
     About,
     Settings,
+    Fleet,
+    Compare,
 }
 
 #[derive(Default, Clone)]
@@ -42,6 +48,17 @@ pub struct UpdateState {
     uad_list: UadListState,
 }
 
+/// A device opened in a tab
+struct DeviceTab {
+    phone: Phone,
+    /// `None` for the selected tab: its views are the ones of [`UadGui`]
+    views: Option<Box<(AppsView, SettingsView)>>,
+    /// Browsed from a cache or a backup, see [`AppsView::set_offline`]
+    offline: bool,
+    /// Commands of a restore still running on the device
+    nb_running_async_adb_commands: u32,
+}
+
 //gui status
 #[derive(Default)]
 pub struct UadGui {
@@ -49,11 +66,16 @@ pub struct UadGui {
     apps_view: AppsView,
     about_view: AboutView,
     settings_view: SettingsView,
+    fleet_view: FleetView,
+    compare_view: CompareView,
     devices_list: Vec<Phone>,
     /// index of `devices_list`
     selected_device: Option<Phone>,
+    /// Opened devices, the selected one included
+    tabs: Vec<DeviceTab>,
+    /// Devices which can be browsed while they aren't connected
+    offline_sources: Vec<OfflineSource>,
     update_state: UpdateState,
-    nb_running_async_adb_commands: u32,
     adb_satisfied: bool,
 }
 
@@ -63,11 +85,22 @@ pub enum Message {
     AboutPressed,
     SettingsPressed,
     AppsPress,
+    FleetPressed,
+    ComparePressed,
     DeviceSelected(Phone),
+    OpenOffline(OfflineSource),
+    TabSelected(String),
+    TabClosed(String),
+    /// Result of a task started for the device of a tab, which may not be the selected one anymore
+    ForDevice(String, Box<Message>),
     AboutAction(AboutMessage),
     AppsAction(AppsMessage),
     SettingsAction(SettingsMessage),
+    FleetAction(FleetMessage),
+    CompareAction(CompareMessage),
     RefreshButtonPressed,
+    /// Reload the packages of the selected device, after they were changed
+    ReloadDevice,
     RebootButtonPressed,
     LoadDevices(Vec<Phone>),
     #[cfg(feature = "self-update")]
@@ -138,16 +171,43 @@ impl UadGui {
     fn update(state: &mut UadGui, msg: Message) -> Task<Message> {
         match msg {
             Message::LoadDevices(devices_list) => {
-                state.selected_device = match &state.selected_device {
-                    Some(s_device) => {
-                        // Try to reload last selected phone
-                        devices_list
-                            .iter()
-                            .find(|phone| phone.adb_id == s_device.adb_id)
-                            .cloned()
+                // Tabs of disconnected devices are closed, unless they are browsed offline
+                state.tabs.retain(|t| t.offline || devices_list.iter().any(|d| d.adb_id == t.phone.adb_id));
+                for tab in state.tabs.iter_mut().filter(|t| !t.offline) {
+                    if let Some(phone) = devices_list.iter().find(|d| d.adb_id == tab.phone.adb_id) {
+                        tab.phone = phone.clone();
                     }
-                    None => devices_list.first().cloned(),
-                };
+                }
+                state.offline_sources = list_offline_sources(&state.settings_view.general.backup_folder);
+                if state.apps_view.is_offline() {
+                    state.devices_list = devices_list;
+                    return state.reconnect_offline();
+                }
+                // Try to reload last selected phone
+                state.selected_device = state.selected_device.as_ref().and_then(|s_device| {
+                    devices_list
+                        .iter()
+                        .find(|phone| phone.adb_id == s_device.adb_id)
+                        .cloned()
+                });
+                if state.selected_device.is_none() {
+                    match state.tabs.first().map(|t| t.phone.adb_id.clone()) {
+                        Some(serial) => {
+                            state.activate_tab(&serial);
+                        }
+                        None => state.selected_device = devices_list.first().cloned(),
+                    }
+                }
+                if let Some(phone) = &state.selected_device
+                    && state.tab_index(&phone.adb_id).is_none()
+                {
+                    state.tabs.push(DeviceTab {
+                        phone: phone.clone(),
+                        views: None,
+                        offline: false,
+                        nb_running_async_adb_commands: 0,
+                    });
+                }
                 state.devices_list = devices_list;
 
                 #[expect(unused_must_use, reason = "side-effect")]
@@ -173,7 +233,70 @@ impl UadGui {
                 state.view = View::Settings;
                 Task::none()
             }
+            Message::FleetPressed => {
+                state.view = View::Fleet;
+                state.fleet_view.reload_profiles();
+                Task::none()
+            }
+            Message::FleetAction(msg) => {
+                let selected_device = state.selected_device.clone().unwrap_or_default();
+                let multi_user_mode = state.settings_view.device.multi_user_mode;
+                let selection = selection_profile(
+                    &state.apps_view.selection(&selected_device, state.settings_view.device.disable_mode),
+                    multi_user_mode,
+                );
+                let was_busy = state.fleet_view.is_busy();
+                let task = state
+                    .fleet_view
+                    .update(
+                        &state.devices_list,
+                        &selection,
+                        &state.apps_view.uad_lists,
+                        state.settings_view.general.expert_mode,
+                        msg,
+                    )
+                    .map(Message::FleetAction);
+                // The journal and packages of the selected device are stale
+                if was_busy
+                    && !state.fleet_view.is_busy()
+                    && state.fleet_view.changed(&selected_device.adb_id)
+                {
+                    return Task::batch([task, UadGui::update(state, Message::RefreshButtonPressed)]);
+                }
+                task
+            }
+            Message::ComparePressed => {
+                state.view = View::Compare;
+                state
+                    .compare_view
+                    .reload_backups(&state.settings_view.general.backup_folder);
+                Task::none()
+            }
+            Message::CompareAction(msg) => {
+                let was_busy = state.compare_view.is_busy();
+                let task = state
+                    .compare_view
+                    .update(
+                        &state.apps_view.uad_lists,
+                        state.settings_view.general.expert_mode,
+                        msg,
+                    )
+                    .map(Message::CompareAction);
+                // The journal and packages of the selected device are stale
+                let selected_device = state.selected_device.clone().unwrap_or_default();
+                if was_busy
+                    && !state.compare_view.is_busy()
+                    && state.compare_view.changed(&selected_device.adb_id)
+                {
+                    return Task::batch([task, UadGui::update(state, Message::RefreshButtonPressed)]);
+                }
+                task
+            }
             Message::RefreshButtonPressed => {
+                // A device browsed offline is only reloaded once connected
+                if state.apps_view.is_offline() {
+                    return Task::perform(get_devices_list(), Message::LoadDevices);
+                }
                 state.apps_view = AppsView::default();
                 #[expect(unused_must_use, reason = "side-effect")]
                 {
@@ -183,12 +306,29 @@ impl UadGui {
                 }
                 Task::perform(get_devices_list(), Message::LoadDevices)
             }
+            Message::ReloadDevice => {
+                let uad_lists = std::mem::take(&mut state.apps_view.uad_lists);
+                state.apps_view = AppsView::default();
+                state.apps_view.uad_lists = uad_lists;
+                #[expect(unused_must_use, reason = "side-effects")]
+                {
+                    UadGui::update(state, Message::AppsAction(AppsMessage::ADBSatisfied(
+                        state.adb_satisfied,
+                    )));
+                    UadGui::update(state, Message::SettingsAction(SettingsMessage::LoadDeviceSettings));
+                }
+                UadGui::update(state, Message::AppsAction(AppsMessage::LoadPhonePackages((
+                    state.apps_view.uad_lists.clone(),
+                    UadListState::Done,
+                ))))
+            }
             Message::RebootButtonPressed => {
                 state.apps_view = AppsView::default();
                 let serial = match &state.selected_device {
                     Some(d) => d.adb_id.clone(),
                     _ => String::default(),
                 };
+                state.tabs.retain(|t| t.phone.adb_id != serial);
                 state.selected_device = None;
                 state.devices_list = vec![];
                 Task::perform(
@@ -196,20 +336,34 @@ impl UadGui {
                     |_| Message::Nothing,
                 )
             }
-            Message::AppsAction(msg) => state
-                .apps_view
-                .update(
-                    &mut state.settings_view,
-                    &mut state.selected_device.clone().unwrap_or_default(),
-                    &mut state.update_state.uad_list,
-                    msg,
-                )
-                .map(Message::AppsAction),
+            Message::AppsAction(msg) => {
+                let task = state
+                    .apps_view
+                    .update(
+                        &mut state.settings_view,
+                        &mut state.selected_device.clone().unwrap_or_default(),
+                        &mut state.update_state.uad_list,
+                        msg,
+                    )
+                    .map(Message::AppsAction);
+                state.device_task(task)
+            }
             Message::SettingsAction(msg) => {
+                // The report of the restore must be complete before refreshing
+                let mut restore_done = false;
+                // Restores run on the device of their tab, which `ForDevice` makes the selected one
+                let mut no_tab = 0;
+                let nb_running_async_adb_commands = match state
+                    .selected_device
+                    .as_ref()
+                    .and_then(|d| state.tabs.iter().position(|t| t.phone.adb_id == d.adb_id))
+                {
+                    Some(i) => &mut state.tabs[i].nb_running_async_adb_commands,
+                    None => &mut no_tab,
+                };
                 match msg {
-                    SettingsMessage::RestoringDevice(ref output) => {
-                        state.nb_running_async_adb_commands -= 1;
-                        state.view = View::List;
+                    SettingsMessage::RestoringDevice(_, ref output) => {
+                        *nb_running_async_adb_commands -= 1;
 
                         #[expect(unused_must_use, reason = "side-effect")]
                         {
@@ -220,9 +374,11 @@ impl UadGui {
                                 AppsMessage::RestoringDevice(output.clone()),
                             );
                         }
-                        if state.nb_running_async_adb_commands == 0 {
-                            return UadGui::update(state, Message::RefreshButtonPressed);
-                        }
+                        restore_done = *nb_running_async_adb_commands == 0;
+                    }
+                    SettingsMessage::PackageReinstalled(..) => {
+                        *nb_running_async_adb_commands -= 1;
+                        restore_done = *nb_running_async_adb_commands == 0;
                     }
                     SettingsMessage::MultiUserMode(toggled) if toggled => {
                         for user in state.apps_view.phone_packages.clone() {
@@ -242,15 +398,21 @@ impl UadGui {
                     }
                     _ => (),
                 }
-                state.settings_view
+                let task = state.settings_view
                     .update(
                         &state.selected_device.clone().unwrap_or_default(),
                         &state.apps_view.phone_packages,
-                        &mut state.nb_running_async_adb_commands,
+                        nb_running_async_adb_commands,
                         msg,
                         state.apps_view.selected_user,
+                        &state.apps_view.uad_lists,
                     )
-                    .map(Message::SettingsAction)
+                    .map(Message::SettingsAction);
+                // Reloads the device of the restore, even if another one is selected by then
+                if restore_done {
+                    return state.device_task(Task::batch([task, Task::done(Message::ReloadDevice)]));
+                }
+                state.device_task(task)
             }
             Message::AboutAction(msg) => {
                 state.about_view.update(msg.clone());
@@ -281,8 +443,17 @@ impl UadGui {
                 }
             }
             Message::DeviceSelected(s_device) => {
-                state.selected_device = Some(s_device.clone());
                 state.view = View::List;
+                // A device browsed offline is loaded, keeping the selection made offline
+                let offline_selection = state.close_offline_tab(&s_device.adb_id);
+                // Already opened: nothing to reload
+                if offline_selection.is_none() && state.activate_tab(&s_device.adb_id) {
+                    return Task::none();
+                }
+                state.open_tab(s_device.clone(), false);
+                if let Some(selection) = offline_selection {
+                    state.apps_view.set_pending_selection(selection);
+                }
                 info!("{:-^65}", "-");
                 info!(
                     "ANDROID_SDK: {} | DEVICE: {}",
@@ -370,10 +541,176 @@ impl UadGui {
                     state.adb_satisfied,
                 )))
             }
+            Message::OpenOffline(source) => {
+                let device = match source.load() {
+                    Ok(device) => device,
+                    Err(e) => {
+                        error!("[OFFLINE] Can't open {source}: {e}");
+                        return Task::none();
+                    }
+                };
+                state.view = View::List;
+                // Already opened, connected or not
+                if state.activate_tab(&device.phone.adb_id) {
+                    return Task::none();
+                }
+                info!("[OFFLINE] Browsing {}", device.origin);
+                state.open_tab(device.phone.clone(), true);
+                state.apps_view.set_offline(device);
+                #[expect(unused_must_use, reason = "side-effect")]
+                {
+                    UadGui::update(state, Message::SettingsAction(SettingsMessage::LoadDeviceSettings));
+                }
+                UadGui::update(state, Message::AppsAction(AppsMessage::LoadUadList(false)))
+            }
+            Message::TabSelected(serial) => {
+                state.activate_tab(&serial);
+                if state.apps_view.is_offline() {
+                    return state.reconnect_offline();
+                }
+                Task::none()
+            }
+            Message::TabClosed(serial) => {
+                // The last tab stays opened
+                let Some(i) = state.tab_index(&serial).filter(|_| state.tabs.len() > 1) else {
+                    return Task::none();
+                };
+                // The selected tab is replaced by its neighbour
+                if state.tabs[i].views.is_none() {
+                    let next = state.tabs[if i == 0 { 1 } else { i - 1 }].phone.adb_id.clone();
+                    state.activate_tab(&next);
+                }
+                state.tabs.remove(i);
+                Task::none()
+            }
+            Message::ForDevice(serial, msg) => {
+                let selected = state
+                    .selected_device
+                    .as_ref()
+                    .map(|d| d.adb_id.clone())
+                    .unwrap_or_default();
+                // Tasks started without a device follow the selected one
+                if serial == selected || serial.is_empty() {
+                    return UadGui::update(state, *msg);
+                }
+                // The tab was closed
+                if !state.activate_tab(&serial) {
+                    return Task::none();
+                }
+                let task = UadGui::update(state, *msg);
+                state.activate_tab(&selected);
+                task
+            }
             Message::Nothing => Task::none(),
         }
     }
 
+    /// Index in `tabs` of the device `serial`
+    fn tab_index(&self, serial: &str) -> Option<usize> {
+        self.tabs.iter().position(|t| t.phone.adb_id == serial)
+    }
+
+    /// Make the tab of `serial` the selected one, without querying the device.
+    ///
+    /// Returns `false` if there is no such tab.
+    fn activate_tab(&mut self, serial: &str) -> bool {
+        let Some(i) = self.tab_index(serial) else {
+            return false;
+        };
+        let Some(views) = self.tabs[i].views.take() else {
+            // already selected
+            return true;
+        };
+        let (mut apps_view, mut settings_view) = *views;
+        // General settings are shared by all devices
+        settings_view.general.clone_from(&self.settings_view.general);
+        std::mem::swap(&mut self.apps_view, &mut apps_view);
+        std::mem::swap(&mut self.settings_view, &mut settings_view);
+        if let Some(previous) = self
+            .selected_device
+            .as_ref()
+            .and_then(|d| self.tab_index(&d.adb_id))
+        {
+            self.tabs[previous].views = Some(Box::new((apps_view, settings_view)));
+        }
+        self.selected_device = Some(self.tabs[i].phone.clone());
+        true
+    }
+
+    /// Load the device browsed offline in the selected tab, if it is connected again
+    fn reconnect_offline(&mut self) -> Task<Message> {
+        let connected = self.selected_device.as_ref().and_then(|s_device| {
+            self.devices_list
+                .iter()
+                .find(|phone| phone.adb_id == s_device.adb_id)
+                .cloned()
+        });
+        if let Some(phone) = connected {
+            UadGui::update(self, Message::DeviceSelected(phone))
+        } else {
+            Task::none()
+        }
+    }
+
+    /// Close the tab of `serial` if it is browsed offline, returning its selection
+    /// as `(user_id, package, wanted state)`
+    fn close_offline_tab(&mut self, serial: &str) -> Option<Vec<(u16, String, PackageState)>> {
+        let i = self.tab_index(serial).filter(|&i| self.tabs[i].offline)?;
+        let tab = self.tabs.remove(i);
+        if let Some(views) = tab.views {
+            let (apps_view, settings_view) = *views;
+            return Some(apps_view.offline_selection(settings_view.device.disable_mode));
+        }
+        // The selected tab: its views are dropped when another tab is opened
+        self.selected_device = None;
+        Some(self.apps_view.offline_selection(self.settings_view.device.disable_mode))
+    }
+
+    /// Open `phone` in a new selected tab, with empty views
+    fn open_tab(&mut self, phone: Phone, offline: bool) {
+        let uad_lists = self.apps_view.uad_lists.clone();
+        let mut settings_view = SettingsView::default();
+        settings_view.general.clone_from(&self.settings_view.general);
+        let views = (
+            std::mem::take(&mut self.apps_view),
+            std::mem::replace(&mut self.settings_view, settings_view),
+        );
+        if let Some(previous) = self
+            .selected_device
+            .as_ref()
+            .and_then(|d| self.tab_index(&d.adb_id))
+        {
+            self.tabs[previous].views = Some(Box::new(views));
+        }
+        self.apps_view.uad_lists = uad_lists;
+        #[expect(unused_must_use, reason = "side-effect")]
+        {
+            self.apps_view.update(
+                &mut self.settings_view,
+                &mut phone.clone(),
+                &mut self.update_state.uad_list,
+                AppsMessage::ADBSatisfied(self.adb_satisfied),
+            );
+        }
+        self.tabs.push(DeviceTab {
+            phone: phone.clone(),
+            views: None,
+            offline,
+            nb_running_async_adb_commands: 0,
+        });
+        self.selected_device = Some(phone);
+    }
+
+    /// Route the messages of `task` to the selected device, even if another one gets selected
+    fn device_task(&self, task: Task<Message>) -> Task<Message> {
+        let serial = self
+            .selected_device
+            .as_ref()
+            .map(|d| d.adb_id.clone())
+            .unwrap_or_default();
+        task.map(move |msg| Message::ForDevice(serial.clone(), Box::new(msg)))
+    }
+
     fn view(state: &UadGui) -> Element<Message> {
 
         
@@ -381,6 +718,7 @@ impl UadGui {
             &state.devices_list,
             state.selected_device.clone(),
             &state.apps_view,
+            &state.offline_sources,
             &state.update_state.self_update
         );
 
@@ -398,9 +736,27 @@ impl UadGui {
                 .settings_view
                 .view(&selected_device, &state.apps_view)
                 .map(Message::SettingsAction),
+            View::Fleet => state
+                .fleet_view
+                .view(&state.devices_list)
+                .map(Message::FleetAction),
+            View::Compare => state
+                .compare_view
+                .view(&state.devices_list)
+                .map(Message::CompareAction),
         };
 
-        column![navigation_container, main_container]
+        let selected_serial = state
+            .selected_device
+            .as_ref()
+            .map_or("", |d| d.adb_id.as_str());
+        // A single device doesn't need tabs
+        let tabs = (state.tabs.len() > 1)
+            .then(|| tab_bar(state.tabs.iter().map(|t| (&t.phone, t.offline)), selected_serial));
+
+        column![navigation_container]
+            .push_maybe(tabs)
+            .push(main_container)
             .width(Length::Fill)
             .align_x(Alignment::Center)
             .into()
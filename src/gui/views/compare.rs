@@ -0,0 +1,469 @@
+use crate::core::compare::{
+    CompareSide, PackageChange, Presence, UserChanges, compare_users, load_side, match_profile,
+};
+use crate::core::fleet::{DevicePlan, DeviceResult, journal_changes, plan_device, run_change};
+use crate::core::helpers::button_primary;
+use crate::core::save::{BackupFile, BackupTrigger, UserBackup, backup_phone, list_all_backups};
+use crate::core::sync::Phone;
+use crate::core::uad_lists::{PackageHashMap, PackageState};
+use crate::gui::{style, widgets::text};
+use iced::widget::{Space, button, column, container, pick_list, row, scrollable};
+use iced::{Alignment, Element, Length, Renderer, Task, Theme};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    A,
+    B,
+}
+
+/// Making device B match A
+#[derive(Debug, Clone)]
+enum Matching {
+    Planning,
+    Planned(DevicePlan),
+    /// The first `done` changes of `plan` were run
+    Applying {
+        plan: DevicePlan,
+        done: usize,
+    },
+    Done(DeviceResult),
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Compare {
+    backups: Vec<BackupFile>,
+    a: Option<CompareSide>,
+    b: Option<CompareSide>,
+    /// Package states of each side, `None` while loading
+    a_users: Option<Result<Vec<UserBackup>, String>>,
+    b_users: Option<Result<Vec<UserBackup>, String>>,
+    /// From B to A, once both sides are loaded
+    users: Vec<UserChanges>,
+    matching: Option<Matching>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    SideSelected(Side, CompareSide),
+    Reload,
+    SideLoaded(Side, CompareSide, Result<Vec<UserBackup>, String>),
+    /// Plan the changes making B match A
+    Match,
+    Planned(Result<DevicePlan, String>),
+    Apply,
+    SnapshotTaken(Result<bool, String>),
+    ChangeApplied(Result<(), String>),
+}
+
+impl Compare {
+    pub fn reload_backups(&mut self, backup_folder: &Path) {
+        self.backups = list_all_backups(backup_folder);
+    }
+
+    /// B is being planned or changed
+    #[must_use]
+    pub fn is_busy(&self) -> bool {
+        matches!(
+            self.matching,
+            Some(Matching::Planning | Matching::Applying { .. })
+        )
+    }
+
+    /// Packages of `serial` were changed to match A
+    #[must_use]
+    pub fn changed(&self, serial: &str) -> bool {
+        matches!(
+            &self.matching,
+            Some(Matching::Done(r)) if r.serial == serial && (r.applied > 0 || !r.failed.is_empty())
+        )
+    }
+
+    pub fn update(
+        &mut self,
+        uad_lists: &PackageHashMap,
+        expert_mode: bool,
+        msg: Message,
+    ) -> Task<Message> {
+        match msg {
+            Message::SideSelected(side, source) => {
+                if self.is_busy() {
+                    return Task::none();
+                }
+                match side {
+                    Side::A => self.a = Some(source.clone()),
+                    Side::B => self.b = Some(source.clone()),
+                }
+                self.load(side, source, uad_lists)
+            }
+            Message::Reload => {
+                if self.is_busy() {
+                    return Task::none();
+                }
+                let mut tasks = vec![];
+                if let Some(a) = self.a.clone() {
+                    tasks.push(self.load(Side::A, a, uad_lists));
+                }
+                if let Some(b) = self.b.clone() {
+                    tasks.push(self.load(Side::B, b, uad_lists));
+                }
+                Task::batch(tasks)
+            }
+            Message::SideLoaded(side, source, users) => {
+                // Another source was selected in the meantime
+                let (current, loaded) = match side {
+                    Side::A => (&self.a, &mut self.a_users),
+                    Side::B => (&self.b, &mut self.b_users),
+                };
+                if current.as_ref() == Some(&source) {
+                    if let Err(e) = &users {
+                        error!("[COMPARE] {source}: {e}");
+                    }
+                    *loaded = Some(users);
+                }
+                self.users = match (&self.a_users, &self.b_users) {
+                    (Some(Ok(a)), Some(Ok(b))) => compare_users(b, a, uad_lists),
+                    _ => vec![],
+                };
+                Task::none()
+            }
+            Message::Match => self.plan_match(uad_lists, expert_mode),
+            Message::Planned(plan) => {
+                self.matching = Some(match plan {
+                    Ok(plan) => Matching::Planned(plan),
+                    Err(e) => {
+                        error!("[COMPARE] Can't plan the changes: {e}");
+                        let phone = match &self.b {
+                            Some(CompareSide::Device(phone)) => phone.clone(),
+                            _ => Phone::default(),
+                        };
+                        Matching::Done(DeviceResult::failed(&phone, e))
+                    }
+                });
+                Task::none()
+            }
+            Message::Apply => {
+                let Some(Matching::Planned(plan)) = self.matching.take() else {
+                    return Task::none();
+                };
+                let task = Task::perform(
+                    backup_phone(
+                        plan.phone.clone(),
+                        plan.packages.clone(),
+                        BackupTrigger::BeforeApply,
+                    ),
+                    Message::SnapshotTaken,
+                );
+                self.matching = Some(Matching::Applying { plan, done: 0 });
+                task
+            }
+            Message::SnapshotTaken(result) => {
+                // Changes are still applied: the user asked for them
+                if let Err(e) = result {
+                    error!("[COMPARE] Snapshot before applying changes failed: {e}");
+                }
+                self.next_change(uad_lists)
+            }
+            Message::ChangeApplied(result) => {
+                if let Some(Matching::Applying { plan, done }) = &mut self.matching {
+                    if let Err(e) = &result {
+                        error!("[COMPARE] {}: {e}", plan.changes[*done]);
+                    }
+                    plan.changes[*done].error = result.err();
+                    *done += 1;
+                }
+                self.next_change(uad_lists)
+            }
+        }
+    }
+
+    /// Plan the changes making device B match A
+    fn plan_match(&mut self, uad_lists: &PackageHashMap, expert_mode: bool) -> Task<Message> {
+        let (Some(CompareSide::Device(phone)), Some(Ok(a_users)), Some(a)) =
+            (&self.b, &self.a_users, &self.a)
+        else {
+            return Task::none();
+        };
+        if self.is_busy() {
+            return Task::none();
+        }
+        self.matching = Some(Matching::Planning);
+        Task::perform(
+            plan_device(
+                phone.adb_id.clone(),
+                match_profile(format!("Match {a}"), a_users),
+                uad_lists.clone(),
+                expert_mode,
+            ),
+            Message::Planned,
+        )
+    }
+
+    /// Load the package states of `side` from `source`
+    fn load(
+        &mut self,
+        side: Side,
+        source: CompareSide,
+        uad_lists: &PackageHashMap,
+    ) -> Task<Message> {
+        match side {
+            Side::A => self.a_users = None,
+            Side::B => self.b_users = None,
+        }
+        // The plan no longer matches what is compared
+        if !self.is_busy() {
+            self.matching = None;
+        }
+        Task::perform(load_side(source.clone(), uad_lists.clone()), move |users| {
+            Message::SideLoaded(side, source.clone(), users)
+        })
+    }
+
+    /// Run the next change of B, or journal its changes and reload it if they were all run
+    fn next_change(&mut self, uad_lists: &PackageHashMap) -> Task<Message> {
+        let Some(Matching::Applying { plan, done }) = &self.matching else {
+            return Task::none();
+        };
+        let serial = plan.phone.adb_id.clone();
+        if let Some(change) = plan.changes.get(*done) {
+            return Task::perform(
+                run_change(serial, change.commands.clone()),
+                Message::ChangeApplied,
+            );
+        }
+        if let Err(e) = journal_changes(&serial, &plan.changes) {
+            error!("[COMPARE] {serial}: can't save the journal: {e}");
+        }
+        let result = DeviceResult::new(plan);
+        info!(
+            "[COMPARE] {serial}: {} change(s) applied, {} failed",
+            result.applied,
+            result.failed.len()
+        );
+        self.matching = Some(Matching::Done(result));
+        match self.b.clone() {
+            Some(b) => {
+                self.b_users = None;
+                Task::perform(load_side(b.clone(), uad_lists.clone()), move |users| {
+                    Message::SideLoaded(Side::B, b.clone(), users)
+                })
+            }
+            None => Task::none(),
+        }
+    }
+
+    pub fn view(&self, devices: &[Phone]) -> Element<'_, Message, Theme, Renderer> {
+        let busy = self.is_busy();
+        let mut sources: Vec<CompareSide> =
+            devices.iter().cloned().map(CompareSide::Device).collect();
+        sources.extend(
+            self.backups
+                .iter()
+                .filter(|b| b.error.is_none())
+                .map(|b| CompareSide::Backup(Box::new(b.clone()))),
+        );
+        let side_picklist = |side: Side, selected: &Option<CompareSide>| {
+            let picklist = pick_list(sources.clone(), selected.clone(), move |s| {
+                Message::SideSelected(side, s)
+            })
+            .placeholder("Pick a device or a backup...")
+            .padding(6)
+            .width(Length::Fill);
+            row![text(format!("{side:?}")).size(18), picklist]
+                .spacing(10)
+                .align_y(Alignment::Center)
+        };
+        let reload_btn = if (self.a.is_some() || self.b.is_some()) && !busy {
+            button_primary("Reload").on_press(Message::Reload)
+        } else {
+            button("Reload").padding([5, 10])
+        };
+        let sides = row![
+            side_picklist(Side::A, &self.a),
+            side_picklist(Side::B, &self.b),
+            reload_btn
+        ]
+        .spacing(20)
+        .align_y(Alignment::Center);
+
+        let users = match (&self.a, &self.b, &self.a_users, &self.b_users) {
+            (None, _, _, _) | (_, None, _, _) => {
+                return frame(
+                    sides,
+                    text("Pick the two sides to compare: a reference device (A) and another device or a backup (B).")
+                        .style(style::Text::Commentary.get_style()),
+                );
+            }
+            (_, _, Some(Err(e)), _) => {
+                return frame(
+                    sides,
+                    text(format!("Can't load A: {e}")).style(style::Text::Danger.get_style()),
+                );
+            }
+            (_, _, _, Some(Err(e))) => {
+                return frame(
+                    sides,
+                    text(format!("Can't load B: {e}")).style(style::Text::Danger.get_style()),
+                );
+            }
+            (_, _, Some(Ok(_)), Some(Ok(_))) => &self.users,
+            _ => return frame(sides, text("Loading...")),
+        };
+
+        let (only_a, only_b, differ) =
+            users
+                .iter()
+                .flat_map(|u| &u.changes)
+                .fold((0, 0, 0), |(a, b, d), c| match (c.to, c.from) {
+                    (Some(_), None) => (a + 1, b, d),
+                    (None, Some(_)) => (a, b + 1, d),
+                    _ => (a, b, d + 1),
+                });
+        let summary = text(format!(
+            "{only_a} package(s) only on A, {only_b} only on B, {differ} in a different state"
+        ));
+
+        column![
+            frame(sides, summary),
+            users_view(users),
+            self.matching_row()
+        ]
+        .spacing(10)
+        .padding([0, 10])
+        .height(Length::Fill)
+        .into()
+    }
+
+    fn matching_row(&self) -> Element<'_, Message, Theme, Renderer> {
+        let b_is_device = matches!(self.b, Some(CompareSide::Device(_)));
+        let loaded = matches!((&self.a_users, &self.b_users), (Some(Ok(_)), Some(Ok(_))));
+        let match_btn = if b_is_device && loaded && !self.is_busy() {
+            button_primary("Make B match A").on_press(Message::Match)
+        } else {
+            button("Make B match A").padding([5, 10])
+        };
+        let status = match &self.matching {
+            None if b_is_device => {
+                text("Plan the changes making B match A, then review them before applying")
+                    .style(style::Text::Commentary.get_style())
+            }
+            None => text("B must be a connected device to be changed")
+                .style(style::Text::Commentary.get_style()),
+            Some(Matching::Planning) => text("Planning..."),
+            Some(Matching::Planned(plan)) if plan.unsafe_skipped.is_empty() => {
+                text(format!("{} change(s) to apply", plan.changes.len()))
+            }
+            Some(Matching::Planned(plan)) => text(format!(
+                "{} change(s) to apply, {} Unsafe skipped (expert mode is off)",
+                plan.changes.len(),
+                plan.unsafe_skipped.len()
+            )),
+            Some(Matching::Applying { plan, done }) => {
+                text(format!("Applying {done}/{}...", plan.changes.len()))
+            }
+            Some(Matching::Done(r)) => match &r.error {
+                Some(e) => text(format!("Failed: {e}")).style(style::Text::Danger.get_style()),
+                None if r.failed.is_empty() => text(format!("Done: {} change(s)", r.applied))
+                    .style(style::Text::Ok.get_style()),
+                None => text(format!(
+                    "Done: {} change(s), {} failed",
+                    r.applied,
+                    r.failed.len()
+                ))
+                .style(style::Text::Danger.get_style()),
+            },
+        };
+        let apply_btn = match &self.matching {
+            Some(Matching::Planned(plan)) if !plan.changes.is_empty() => {
+                button_primary(text(format!("Apply {} change(s) to B", plan.changes.len())))
+                    .on_press(Message::Apply)
+            }
+            _ => button("Apply to B").padding([5, 10]),
+        };
+        container(
+            row![
+                match_btn,
+                status,
+                Space::new(Length::Fill, Length::Shrink),
+                apply_btn
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+        )
+        .padding(10)
+        .into()
+    }
+}
+
+fn frame<'a>(
+    sides: impl Into<Element<'a, Message, Theme, Renderer>>,
+    status: impl Into<Element<'a, Message, Theme, Renderer>>,
+) -> Element<'a, Message, Theme, Renderer> {
+    container(
+        column![
+            text("Compare devices").size(26),
+            sides.into(),
+            status.into()
+        ]
+        .spacing(10),
+    )
+    .padding(10)
+    .into()
+}
+
+/// Differing packages of every user
+fn users_view(users: &[UserChanges]) -> Element<'_, Message, Theme, Renderer> {
+    let user_rows = users.iter().fold(column![].spacing(6), |col, user| {
+        let title = text(format!("User {} ({})", user.user_id, user.kind)).size(18);
+        match user.presence {
+            Presence::Both if user.changes.is_empty() => col
+                .push(title)
+                .push(text("Same packages and states").style(style::Text::Commentary.get_style())),
+            Presence::Both => user
+                .changes
+                .iter()
+                .fold(col.push(title).push(header_row()), |col, c| {
+                    col.push(change_row(c))
+                }),
+            Presence::OnlyOld => col
+                .push(title)
+                .push(text("Only on B: not compared").style(style::Text::Commentary.get_style())),
+            Presence::OnlyNew => col
+                .push(title)
+                .push(text("Only on A: not compared").style(style::Text::Commentary.get_style())),
+        }
+    });
+    container(scrollable(user_rows.padding(10)).height(Length::Fill))
+        .height(Length::Fill)
+        .width(Length::Fill)
+        .style(style::Container::BorderedFrame.get_style())
+        .into()
+}
+
+fn state_text(state: Option<PackageState>) -> String {
+    state.map_or_else(|| "Not installed".to_string(), |s| s.to_string())
+}
+
+fn header_row() -> Element<'static, Message, Theme, Renderer> {
+    row![
+        text("Package").width(Length::Fill),
+        text("List").width(100),
+        text("Removal").width(100),
+        text("A").width(110),
+        text("B").width(110),
+    ]
+    .spacing(10)
+    .into()
+}
+
+fn change_row(change: &PackageChange) -> Element<'_, Message, Theme, Renderer> {
+    row![
+        text(&change.package).width(Length::Fill),
+        text(change.list.to_string()).width(100),
+        text(change.removal.to_string()).width(100),
+        text(state_text(change.to)).width(110),
+        text(state_text(change.from)).width(110),
+    ]
+    .spacing(10)
+    .into()
+}
@@ -0,0 +1,417 @@
+use crate::core::fleet::{
+    DevicePlan, DeviceResult, FleetReport, export_fleet_report, journal_changes, plan_device,
+    run_change,
+};
+use crate::core::helpers::button_primary;
+use crate::core::profile::{Profile, list_profiles};
+use crate::core::save::{BackupTrigger, backup_phone};
+use crate::core::sync::Phone;
+use crate::core::uad_lists::PackageHashMap;
+use crate::gui::{style, widgets::text};
+use iced::widget::{Space, button, checkbox, column, container, pick_list, row, scrollable};
+use iced::{Alignment, Element, Length, Renderer, Task, Theme};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::PathBuf;
+
+/// What is applied to the fleet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FleetSource {
+    /// The selection of the Apps view
+    Selection,
+    Profile(Profile),
+}
+
+impl fmt::Display for FleetSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Selection => f.write_str("Current selection"),
+            Self::Profile(p) => write!(f, "Profile: {p}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Progress {
+    Planning,
+    Planned(DevicePlan),
+    /// The first `done` changes of `plan` were run
+    Applying {
+        plan: DevicePlan,
+        done: usize,
+    },
+    Done(DeviceResult),
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Fleet {
+    /// Serials of the devices left out
+    excluded: BTreeSet<String>,
+    profiles: Vec<Profile>,
+    source: Option<FleetSource>,
+    /// Name of what was planned
+    planned: String,
+    /// Progress of each device, by serial
+    progress: BTreeMap<String, Progress>,
+    /// Where the last report was exported, or why it couldn't be
+    report_export: Option<Result<PathBuf, String>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    DeviceToggled(String, bool),
+    SourceSelected(FleetSource),
+    Plan,
+    Planned(String, Result<DevicePlan, String>),
+    Apply,
+    SnapshotTaken(String, Result<bool, String>),
+    ChangeApplied(String, Result<(), String>),
+    ExportReport,
+    ReportExported(Result<PathBuf, String>),
+}
+
+impl Fleet {
+    pub fn reload_profiles(&mut self) {
+        self.profiles = list_profiles();
+        if let Some(FleetSource::Profile(p)) = &self.source
+            && !self.profiles.contains(p)
+        {
+            self.source = None;
+        }
+    }
+
+    /// A device is being planned or changed
+    #[must_use]
+    pub fn is_busy(&self) -> bool {
+        self.progress
+            .values()
+            .any(|p| matches!(p, Progress::Planning | Progress::Applying { .. }))
+    }
+
+    /// Packages of `serial` were changed by the last apply
+    #[must_use]
+    pub fn changed(&self, serial: &str) -> bool {
+        matches!(
+            self.progress.get(serial),
+            Some(Progress::Done(r)) if r.applied > 0 || !r.failed.is_empty()
+        )
+    }
+
+    /// `selection` is the selection of the Apps view, as a profile
+    pub fn update(
+        &mut self,
+        devices: &[Phone],
+        selection: &Profile,
+        uad_lists: &PackageHashMap,
+        expert_mode: bool,
+        msg: Message,
+    ) -> Task<Message> {
+        match msg {
+            Message::DeviceToggled(serial, included) => {
+                if included {
+                    self.excluded.remove(&serial);
+                } else {
+                    self.excluded.insert(serial);
+                }
+                Task::none()
+            }
+            Message::SourceSelected(source) => {
+                self.source = Some(source);
+                Task::none()
+            }
+            Message::Plan => match &self.source {
+                Some(FleetSource::Profile(p)) => {
+                    let profile = p.clone();
+                    self.plan(devices, &profile, uad_lists, expert_mode)
+                }
+                Some(FleetSource::Selection) => {
+                    self.plan(devices, selection, uad_lists, expert_mode)
+                }
+                None => Task::none(),
+            },
+            Message::Planned(serial, plan) => {
+                let progress = match plan {
+                    Ok(plan) => Progress::Planned(plan),
+                    Err(e) => {
+                        warn!("[FLEET] {serial}: {e}");
+                        let phone = devices
+                            .iter()
+                            .find(|d| d.adb_id == serial)
+                            .cloned()
+                            .unwrap_or_else(|| Phone {
+                                adb_id: serial.clone(),
+                                ..Phone::default()
+                            });
+                        Progress::Done(DeviceResult::failed(&phone, e))
+                    }
+                };
+                self.progress.insert(serial, progress);
+                Task::none()
+            }
+            Message::Apply => self.apply(),
+            Message::SnapshotTaken(serial, result) => {
+                // Changes are still applied: the user asked for them
+                if let Err(e) = result {
+                    error!("[FLEET] {serial}: snapshot before applying changes failed: {e}");
+                }
+                self.next_change(serial)
+            }
+            Message::ChangeApplied(serial, result) => {
+                if let Some(Progress::Applying { plan, done }) = self.progress.get_mut(&serial) {
+                    if let Err(e) = &result {
+                        error!("[FLEET] {serial}: {}: {e}", plan.changes[*done]);
+                    }
+                    plan.changes[*done].error = result.err();
+                    *done += 1;
+                }
+                self.next_change(serial)
+            }
+            Message::ExportReport => Task::perform(
+                export_fleet_report(FleetReport::new(&self.planned, self.results())),
+                Message::ReportExported,
+            ),
+            Message::ReportExported(result) => {
+                if let Err(e) = &result {
+                    error!("[FLEET] Failed to export the report: {e}");
+                }
+                self.report_export = Some(result);
+                Task::none()
+            }
+        }
+    }
+
+    /// Plan `profile` on the included `devices`
+    fn plan(
+        &mut self,
+        devices: &[Phone],
+        profile: &Profile,
+        uad_lists: &PackageHashMap,
+        expert_mode: bool,
+    ) -> Task<Message> {
+        if self.is_busy() {
+            return Task::none();
+        }
+        self.planned.clone_from(&profile.name);
+        self.progress.clear();
+        self.report_export = None;
+        let mut tasks = vec![];
+        for phone in devices
+            .iter()
+            .filter(|d| !self.excluded.contains(&d.adb_id))
+        {
+            let serial = phone.adb_id.clone();
+            self.progress.insert(serial.clone(), Progress::Planning);
+            tasks.push(Task::perform(
+                plan_device(
+                    serial.clone(),
+                    profile.clone(),
+                    uad_lists.clone(),
+                    expert_mode,
+                ),
+                move |plan| Message::Planned(serial.clone(), plan),
+            ));
+        }
+        Task::batch(tasks)
+    }
+
+    /// Snapshot the planned devices, then change them
+    fn apply(&mut self) -> Task<Message> {
+        if self.is_busy() {
+            return Task::none();
+        }
+        let mut tasks = vec![];
+        for (serial, progress) in &mut self.progress {
+            let Progress::Planned(plan) = progress else {
+                continue;
+            };
+            if plan.changes.is_empty() {
+                *progress = Progress::Done(DeviceResult::new(plan));
+                continue;
+            }
+            let serial = serial.clone();
+            tasks.push(Task::perform(
+                backup_phone(
+                    plan.phone.clone(),
+                    plan.packages.clone(),
+                    BackupTrigger::BeforeApply,
+                ),
+                move |result| Message::SnapshotTaken(serial.clone(), result),
+            ));
+            *progress = Progress::Applying {
+                plan: plan.clone(),
+                done: 0,
+            };
+        }
+        Task::batch(tasks)
+    }
+
+    /// Run the next change of `serial`, or journal its changes if they were all run
+    fn next_change(&mut self, serial: String) -> Task<Message> {
+        let Some(Progress::Applying { plan, done }) = self.progress.get(&serial) else {
+            return Task::none();
+        };
+        if let Some(change) = plan.changes.get(*done) {
+            let commands = change.commands.clone();
+            return Task::perform(run_change(serial.clone(), commands), move |result| {
+                Message::ChangeApplied(serial.clone(), result)
+            });
+        }
+        if let Err(e) = journal_changes(&serial, &plan.changes) {
+            error!("[FLEET] {serial}: can't save the journal: {e}");
+        }
+        let result = DeviceResult::new(plan);
+        info!(
+            "[FLEET] {serial}: {} change(s) applied, {} failed",
+            result.applied,
+            result.failed.len()
+        );
+        self.progress.insert(serial, Progress::Done(result));
+        Task::none()
+    }
+
+    fn results(&self) -> Vec<DeviceResult> {
+        self.progress
+            .values()
+            .filter_map(|p| match p {
+                Progress::Done(r) => Some(r.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn view(&self, devices: &[Phone]) -> Element<'_, Message, Theme, Renderer> {
+        let busy = self.is_busy();
+
+        let mut sources = vec![FleetSource::Selection];
+        sources.extend(self.profiles.iter().cloned().map(FleetSource::Profile));
+        let source_picklist = pick_list(sources, self.source.clone(), Message::SourceSelected)
+            .placeholder("Apply...")
+            .padding(6);
+
+        let included = devices
+            .iter()
+            .filter(|d| !self.excluded.contains(&d.adb_id))
+            .count();
+        let plan_btn = if self.source.is_some() && included > 0 && !busy {
+            button_primary(text(format!("Plan on {included} device(s)"))).on_press(Message::Plan)
+        } else {
+            button(text(format!("Plan on {included} device(s)"))).padding([5, 10])
+        };
+
+        let to_apply = self
+            .progress
+            .values()
+            .filter(|p| matches!(p, Progress::Planned(_)))
+            .count();
+        let apply_btn = if to_apply > 0 && !busy {
+            button_primary(text(format!("Apply to {to_apply} device(s)"))).on_press(Message::Apply)
+        } else {
+            button(text(format!("Apply to {to_apply} device(s)"))).padding([5, 10])
+        };
+
+        let results = self.results();
+        let report_btn = if !results.is_empty() && !busy {
+            button_primary("Export report").on_press(Message::ExportReport)
+        } else {
+            button("Export report").padding([5, 10])
+        };
+
+        let controls = row![
+            source_picklist,
+            plan_btn,
+            apply_btn,
+            Space::new(Length::Fill, Length::Shrink),
+            report_btn,
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        let device_rows = devices.iter().fold(column![].spacing(6), |col, phone| {
+            let serial = phone.adb_id.clone();
+            let mut check = checkbox(
+                format!("{} ({})", phone.model, phone.adb_id),
+                !self.excluded.contains(&phone.adb_id),
+            );
+            if !busy {
+                check = check.on_toggle(move |b| Message::DeviceToggled(serial.clone(), b));
+            }
+            col.push(
+                row![
+                    check.width(Length::Fill),
+                    text(format!("SDK {}", phone.android_sdk)).width(70),
+                    progress_text(self.progress.get(&phone.adb_id)),
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center),
+            )
+        });
+        let devices_ctn = container(scrollable(device_rows.padding(10)).height(Length::Fill))
+            .height(Length::Fill)
+            .width(Length::Fill)
+            .style(style::Container::BorderedFrame.get_style());
+
+        let report = FleetReport::new(&self.planned, results);
+        let summary = if report.devices == 0 {
+            text("Pick what to apply and the devices, then plan and review the changes of each device before applying them.")
+                .style(style::Text::Commentary.get_style())
+        } else {
+            text(format!(
+                "{}: {}/{} device(s) without failure, {} change(s) applied, {} failed",
+                report.profile, report.succeeded, report.devices, report.applied, report.failed
+            ))
+        };
+        let export = match &self.report_export {
+            Some(Ok(path)) => text(format!("Report exported to {}", path.display()))
+                .style(style::Text::Ok.get_style()),
+            Some(Err(e)) => {
+                text(format!("Export failed: {e}")).style(style::Text::Danger.get_style())
+            }
+            None => text(""),
+        };
+
+        container(
+            column![
+                text("Fleet").size(26),
+                controls,
+                devices_ctn,
+                summary,
+                export
+            ]
+            .spacing(10),
+        )
+        .height(Length::Fill)
+        .padding(10)
+        .into()
+    }
+}
+
+fn progress_text(progress: Option<&Progress>) -> Element<'static, Message, Theme, Renderer> {
+    let status = match progress {
+        None => text("Not planned").style(style::Text::Commentary.get_style()),
+        Some(Progress::Planning) => text("Planning..."),
+        Some(Progress::Planned(plan)) if plan.unsafe_skipped.is_empty() => {
+            text(format!("{} change(s) to apply", plan.changes.len()))
+        }
+        Some(Progress::Planned(plan)) => text(format!(
+            "{} change(s) to apply, {} Unsafe skipped",
+            plan.changes.len(),
+            plan.unsafe_skipped.len()
+        )),
+        Some(Progress::Applying { plan, done }) => {
+            text(format!("Applying {done}/{}...", plan.changes.len()))
+        }
+        Some(Progress::Done(r)) => match &r.error {
+            Some(e) => text(format!("Failed: {e}")).style(style::Text::Danger.get_style()),
+            None if r.failed.is_empty() => {
+                text(format!("Done: {} change(s)", r.applied)).style(style::Text::Ok.get_style())
+            }
+            None => text(format!(
+                "Done: {} change(s), {} failed",
+                r.applied,
+                r.failed.len()
+            ))
+            .style(style::Text::Danger.get_style()),
+        },
+    };
+    container(status).width(320).into()
+}
@@ -1,10 +1,22 @@
-use crate::core::config::DeviceSettings;
+use crate::core::changelog::{ListChange, ListChangelog, previous_lists};
+use crate::core::config::{Config, DeviceSettings};
+use crate::core::contribution::{ContributionExport, export_contribution};
 use crate::core::helpers::button_primary;
-use crate::core::sync::{adb_shell_command, apply_pkg_state_commands, AdbError, Phone, User};
+use crate::core::journal::{Journal, JournalAction, JournalEntry};
+use crate::core::magisk::{export_module, ModuleExport};
+use crate::core::offline::{cache_device, resume_selection, OfflineDevice};
+use crate::core::ota::{KnownStates, OtaChanges};
+use crate::core::profile::{list_profiles, reconcile, Profile, ProfileChange, ProfilePlan};
+use crate::core::save::{backup_phone, device_users, BackupTrigger};
+use crate::core::script::{export_scripts, ScriptChange, ScriptExport};
+use crate::core::selection::{
+    match_selection, pick_selection_file, read_selection, ImportedSelection, SelectionMatch,
+};
+use crate::core::sync::{adb_shell_command, AdbError, Phone, User};
 use crate::core::uad_lists::{
-    load_debloat_lists, Opposite, PackageHashMap, PackageState, Removal, UadList, UadListState,
+    load_debloat_lists, ListSource, Opposite, PackageHashMap, PackageState, Removal, UadList, UadListState,
 };
-use crate::core::utils::{export_selection, fetch_packages, open_url, EXPORT_FILE_NAME, NAME};
+use crate::core::utils::{export_selection, fetch_phone_packages, open_url, Error, EXPORT_FILE_NAME, NAME};
 use crate::gui::style;
 use crate::gui::widgets::navigation_menu::ICONS;
 use std::path::PathBuf;
@@ -63,11 +75,37 @@ pub struct List {
     current_package_index: usize,
     is_adb_satisfied: bool,
     copy_confirmation: bool,
+    /// Changes brought by the last list update
+    list_changelog: Option<ListChangelog>,
+    changelog_modal: bool,
+    /// Result of the last export of unlisted packages, shown in a modal
+    contribution_export: Option<ContributionExport>,
+    /// Changes made on the selected device
+    journal: Journal,
+    profiles: Vec<Profile>,
+    selected_profile: Option<Profile>,
+    /// Changes needed to apply the selected profile, shown in a modal
+    profile_plan: Option<ProfilePlan>,
+    /// Outcome of the last import, shown in the selection modal
+    selection_import: Option<SelectionMatch>,
+    /// Result of the last export of the selection as scripts, shown in a modal
+    script_export: Option<ScriptExport>,
+    /// Result of the last export of the selection as a Magisk module, shown in a modal
+    module_export: Option<ModuleExport>,
+    /// What a system update brought back since the device was last loaded, shown in a modal
+    ota_changes: Option<OtaChanges>,
+    /// `profile_plan` re-applies the debloat undone by a system update
+    ota_redebloat: bool,
+    /// The device isn't connected: its packages come from a cache or a backup, and can't be changed
+    offline: Option<OfflineDevice>,
+    /// `(user_id, package, wanted state)` selected offline, to select again once the packages are loaded
+    pending_selection: Vec<(u16, String, PackageState)>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     LoadUadList(bool),
+    UadListLoaded((PackageHashMap, UadListState, Option<ListChangelog>)),
     LoadPhonePackages((PackageHashMap, UadListState)),
     RestoringDevice(Result<PackageInfo, AdbError>),
     ApplyFilters(Vec<Vec<PackageRow>>),
@@ -79,11 +117,13 @@ pub enum Message {
     RemovalSelected(Removal),
     ApplyActionOnSelection,
     List(usize, RowMessage),
-    ChangePackageState(Result<PackageInfo, AdbError>),
-    Nothing,
+    ChangePackageState(JournalEntry, Result<PackageInfo, AdbError>),
+    Undo,
+    Redo,
     ModalHide,
     ModalUserSelected(User),
     ModalValidate,
+    SnapshotTaken(Result<bool, String>),
     ClearSelectedPackages,
     ADBSatisfied(bool),
     UpdateFailed,
@@ -93,6 +133,20 @@ pub enum Message {
     DescriptionEdit(text_editor::Action),
     CopyError(String),
     HideCopyConfirmation,
+    ShowListChangelog,
+    ExportContribution,
+    ContributionExported(Result<ContributionExport, String>),
+    ProfileSelected(Profile),
+    ReviewProfile,
+    ApplyProfile,
+    ImportSelection,
+    SelectionFilePicked(Result<PathBuf, Error>),
+    ExportScripts,
+    ScriptsExported(Result<ScriptExport, String>),
+    ExportMagiskModule,
+    MagiskModuleExported(Result<ModuleExport, String>),
+    /// Review the re-application of the debloat undone by a system update
+    ReviewOtaRedebloat,
 }
 
 pub struct SummaryEntry {
@@ -112,6 +166,144 @@ impl From<Removal> for SummaryEntry {
 }
 
 impl List {
+    /// The selected packages of `phone`, as `(user_id, package, state once applied)`
+    #[must_use]
+    pub fn selection(&self, phone: &Phone, disable_mode: bool) -> Vec<(u16, String, PackageState)> {
+        self.selected_packages
+            .iter()
+            .filter_map(|&(i_user, index)| {
+                let user = phone.user_list.iter().find(|u| u.index == i_user)?;
+                let package = self.phone_packages.get(i_user)?.get(index)?;
+                Some((
+                    user.id,
+                    package.name.clone(),
+                    package.state.opposite(disable_mode),
+                ))
+            })
+            .collect()
+    }
+
+    /// Browse `device` instead of a connected one
+    pub fn set_offline(&mut self, device: OfflineDevice) {
+        self.offline = Some(device);
+    }
+
+    #[must_use]
+    pub fn is_offline(&self) -> bool {
+        self.offline.is_some()
+    }
+
+    /// The offline selection, as `(user_id, package, wanted state)`
+    #[must_use]
+    pub fn offline_selection(&self, disable_mode: bool) -> Vec<(u16, String, PackageState)> {
+        self.offline.as_ref().map_or_else(Vec::new, |device| {
+            self.selection(&device.phone, disable_mode)
+        })
+    }
+
+    /// Select `selection` once the packages are loaded
+    pub fn set_pending_selection(&mut self, selection: Vec<(u16, String, PackageState)>) {
+        self.pending_selection = selection;
+    }
+
+    /// Select the packages of `pending_selection` that still have to be changed to the wanted state
+    fn select_pending(&mut self, selected_device: &Phone, disable_mode: bool) {
+        let pending = std::mem::take(&mut self.pending_selection);
+        if pending.is_empty() {
+            return;
+        }
+        let resumed = resume_selection(&pending, selected_device, &self.phone_packages, disable_mode);
+        for &(i_user, index) in &resumed.selected {
+            self.phone_packages[i_user][index].selected = true;
+            if !self.selected_packages.contains(&(i_user, index)) {
+                self.selected_packages.push((i_user, index));
+            }
+        }
+        info!(
+            "[OFFLINE] {} package(s) selected offline are selected again",
+            resumed.selected.len()
+        );
+        for (label, names) in [
+            ("already in the wanted state", &resumed.already),
+            ("changed since, selecting them wouldn't give the wanted state", &resumed.changed),
+            ("gone", &resumed.missing),
+        ] {
+            if !names.is_empty() {
+                warn!("[OFFLINE] Not selected again, {label}: {}", names.join(", "));
+            }
+        }
+    }
+
+    /// Refuse to change a device browsed offline
+    fn refuse_offline(&mut self) -> bool {
+        if self.offline.is_some() {
+            self.error_modal = Some(
+                "This device is browsed offline, nothing can be changed on it.\n\
+                Connect it to apply the selection: it is kept, and selected again once the device is loaded."
+                    .to_string(),
+            );
+        }
+        self.offline.is_some()
+    }
+
+    /// Look for what a system update brought back since the packages were last loaded,
+    /// then remember their current states.
+    ///
+    /// If the update brought something back, the previous states are kept
+    /// until its re-debloat is applied or dismissed, so that it's offered again otherwise.
+    fn check_system_update(&mut self, settings: &mut Settings, selected_device: &Phone) {
+        let fingerprint = settings.device_fingerprint().to_string();
+        if fingerprint.is_empty() {
+            return;
+        }
+        let users = device_users(selected_device, &self.phone_packages);
+        if let Some(changes) =
+            settings
+                .device
+                .known_states
+                .detect(&self.journal, &fingerprint, &users, &self.uad_lists)
+        {
+            warn!(
+                "[OTA] System update detected ({} -> {}): {} package(s) brought back, {} new unlisted package(s)",
+                changes.previous_fingerprint,
+                changes.fingerprint,
+                changes.restored.len(),
+                changes.new_unlisted.len()
+            );
+            if !changes.is_empty() {
+                self.ota_changes = Some(changes);
+                return;
+            }
+        }
+        self.remember_states(settings, selected_device, &[]);
+    }
+
+    /// Remember the current package states of the device, with the `planned` changes made
+    fn remember_states(
+        &self,
+        settings: &mut Settings,
+        selected_device: &Phone,
+        planned: &[ProfileChange],
+    ) {
+        let fingerprint = settings.device_fingerprint().to_string();
+        if fingerprint.is_empty() {
+            return;
+        }
+        let mut users = device_users(selected_device, &self.phone_packages);
+        for change in planned {
+            if let Some(package) = users
+                .iter_mut()
+                .filter(|u| u.id == change.entry.user_id)
+                .flat_map(|u| u.packages.iter_mut())
+                .find(|p| p.name == change.entry.package)
+            {
+                package.state = change.entry.to;
+            }
+        }
+        settings.device.known_states = KnownStates::new(fingerprint, users);
+        Config::save_changes(settings, &selected_device.adb_id);
+    }
+
     #[allow(clippy::too_many_lines)]
     pub fn update(
         &mut self,
@@ -123,12 +315,29 @@ impl List {
         let i_user = self.selected_user.unwrap_or_default().index;
         match message {
             Message::ModalHide => {
+                // Dismissing the system update (or its re-debloat) accepts the current states
+                if self.ota_changes.is_some() || self.ota_redebloat {
+                    self.remember_states(settings, selected_device, &[]);
+                    self.ota_redebloat = false;
+                }
                 self.selection_modal = false;
                 self.error_modal = None;
                 self.export_modal = false;
+                self.changelog_modal = false;
+                self.contribution_export = None;
+                self.profile_plan = None;
+                self.selection_import = None;
+                self.script_export = None;
+                self.module_export = None;
+                self.ota_changes = None;
                 Task::none()
             }
             Message::ModalValidate => {
+                self.selection_import = None;
+                if self.refuse_offline() {
+                    self.selection_modal = false;
+                    return Task::none();
+                }
                 let mut commands = vec![];
                 self.selected_packages.sort_unstable();
                 self.selected_packages.dedup();
@@ -141,12 +350,26 @@ impl List {
                     ));
                 }
                 self.selection_modal = false;
-                Task::batch(commands)
+                if commands.is_empty() {
+                    return Task::none();
+                }
+                apply_after_snapshot(selected_device, &self.phone_packages, commands)
+            }
+            Message::SnapshotTaken(result) => {
+                match result {
+                    Ok(_) => {
+                        info!("[BACKUP] Snapshot taken before applying changes");
+                        settings.reload_backups(selected_device);
+                    }
+                    // Changes are still applied: the user asked for them
+                    Err(err) => error!("[BACKUP] Snapshot before applying changes failed: {err}"),
+                }
+                Task::none()
             }
             Message::RestoringDevice(output) => {
                 if let Ok(p) = output {
                     self.loading_state = LoadingState::RestoringDevice(
-                        self.phone_packages[i_user][p.index].name.clone(),
+                        self.phone_packages[p.i_user][p.index].name.clone(),
                     );
                 } else {
                     self.loading_state = LoadingState::RestoringDevice("Error [TODO]".to_string());
@@ -162,14 +385,38 @@ impl List {
                 info!("{:-^65}", "-");
                 self.loading_state = LoadingState::DownloadingList;
                 Task::perform(
-                    Self::init_apps_view(remote, selected_device.clone()),
-                    Message::LoadPhonePackages,
+                    Self::init_apps_view(
+                        remote,
+                        settings.general.list_sources.clone(),
+                        selected_device.clone(),
+                    ),
+                    Message::UadListLoaded,
+                )
+            }
+            Message::UadListLoaded((uad_list, list_state, changelog)) => {
+                if let Some(changelog) = changelog.filter(|c| !c.is_empty()) {
+                    info!("Debloat lists changed: {} change(s)", changelog.changes.len());
+                    self.list_changelog = Some(changelog);
+                    self.changelog_modal = true;
+                }
+                self.update(
+                    settings,
+                    selected_device,
+                    list_update_state,
+                    Message::LoadPhonePackages((uad_list, list_state)),
                 )
             }
+            Message::ShowListChangelog => {
+                self.changelog_modal = self.list_changelog.is_some();
+                Task::none()
+            }
             Message::LoadPhonePackages((uad_list, list_state)) => {
                 self.loading_state = LoadingState::LoadingPackages;
                 self.uad_lists.clone_from(&uad_list);
                 *list_update_state = list_state;
+                if let Some(device) = &self.offline {
+                    return Task::done(Message::ApplyFilters(device.packages(&uad_list)));
+                }
                 Task::perform(
                     Self::load_packages(
                         uad_list,
@@ -181,6 +428,8 @@ impl List {
             }
             Message::ApplyFilters(packages) => {
                 self.phone_packages = packages;
+                self.journal = Journal::load(&selected_device.adb_id);
+                self.profiles = list_profiles();
                 self.filtered_packages = (0..self.phone_packages[i_user].len()).collect();
                 self.selected_package_state = Some(PackageState::Enabled);
                 self.selected_removal = Some(Removal::Recommended);
@@ -188,6 +437,13 @@ impl List {
                 self.selected_user = Some(User::default());
                 Self::filter_package_lists(self);
                 self.loading_state = LoadingState::Ready;
+                if self.offline.is_none() {
+                    if let Err(e) = cache_device(selected_device, &self.phone_packages) {
+                        error!("[OFFLINE] Can't cache the packages of the device: {e}");
+                    }
+                    self.check_system_update(settings, selected_device);
+                    self.select_pending(selected_device, settings.device.disable_mode);
+                }
                 Task::none()
             }
             Message::ToggleAllSelected(selected) => {
@@ -274,13 +530,20 @@ impl List {
                         Task::none()
                     }
                     RowMessage::ActionPressed => {
+                        if self.refuse_offline() {
+                            return Task::none();
+                        }
                         self.phone_packages[i_user][i_package].selected = true;
-                        Task::batch(build_action_pkg_commands(
+                        let commands = build_action_pkg_commands(
                             &self.phone_packages,
                             selected_device,
                             &settings.device,
                             (i_user, i_package),
-                        ))
+                        );
+                        if commands.is_empty() {
+                            return Task::none();
+                        }
+                        apply_after_snapshot(selected_device, &self.phone_packages, commands)
                     }
                     RowMessage::PackagePressed => {
                         self.description = package.clone().description;
@@ -305,11 +568,16 @@ impl List {
                 Self::filter_package_lists(self);
                 Task::none()
             }
-            Message::ChangePackageState(res) => {
+            Message::ChangePackageState(entry, res) => {
+                let to = entry.to;
+                self.journal.record(entry);
+                if let Err(e) = self.journal.save(&selected_device.adb_id) {
+                    error!("[JOURNAL] Can't save the journal: {e}");
+                }
                 match res {
                     Ok(p) => {
                         let package = &mut self.phone_packages[p.i_user][p.index];
-                        package.state = package.state.opposite(settings.device.disable_mode);
+                        package.state = to;
                         package.selected = false;
                         self.selected_packages
                             .retain(|&x| x.1 != p.index && x.0 != p.i_user);
@@ -321,6 +589,20 @@ impl List {
                 }
                 Task::none()
             }
+            Message::Undo => {
+                if self.refuse_offline() {
+                    return Task::none();
+                }
+                let entry = self.journal.plan_undo(selected_device);
+                self.run_journal_entry(selected_device, entry)
+            }
+            Message::Redo => {
+                if self.refuse_offline() {
+                    return Task::none();
+                }
+                let entry = self.journal.plan_redo(selected_device);
+                self.run_journal_entry(selected_device, entry)
+            }
             Message::ModalUserSelected(user) => {
                 self.selected_user = Some(user);
                 self.update(
@@ -350,6 +632,68 @@ impl List {
                 export_selection(self.phone_packages[i_user].clone()),
                 Message::SelectionExported,
             ),
+            Message::ImportSelection => {
+                Task::perform(pick_selection_file(), Message::SelectionFilePicked)
+            }
+            Message::SelectionFilePicked(Ok(path)) => {
+                let selection = match read_selection(&path) {
+                    Ok(selection) => selection,
+                    Err(err) => {
+                        self.error_modal = Some(format!("Can't import {}: {err}", path.display()));
+                        return Task::none();
+                    }
+                };
+                let user = self.selected_user.unwrap_or_default();
+                let result = match_selection(
+                    path,
+                    &selection,
+                    user,
+                    &self.phone_packages[user.index],
+                    settings.general.expert_mode,
+                    settings.device.disable_mode,
+                );
+                // Selecting toggles the state: the states of a profile are reviewed and applied as they are
+                if let ImportedSelection::Profile(profile) = &selection {
+                    info!(
+                        "[IMPORT] {}: {} change(s) to review",
+                        result.path.display(),
+                        result.matched.len()
+                    );
+                    self.profile_plan = Some(result.plan(
+                        &profile.name,
+                        selected_device,
+                        user,
+                        &self.phone_packages[user.index],
+                    ));
+                    self.selection_import = Some(result);
+                    return Task::none();
+                }
+                // The imported selection replaces the current one
+                for (u, i) in std::mem::take(&mut self.selected_packages) {
+                    if let Some(package) = self.phone_packages.get_mut(u).and_then(|p| p.get_mut(i)) {
+                        package.selected = false;
+                    }
+                }
+                for &(i, _) in &result.matched {
+                    #[expect(unused_must_use, reason = "side-effect")]
+                    self.update(
+                        settings,
+                        selected_device,
+                        list_update_state,
+                        Message::List(i, RowMessage::ToggleSelection(true)),
+                    );
+                }
+                self.all_selected = false;
+                info!(
+                    "[IMPORT] {}: {} package(s) selected",
+                    result.path.display(),
+                    result.matched.len()
+                );
+                self.selection_import = Some(result);
+                self.selection_modal = true;
+                Task::none()
+            }
+            Message::SelectionFilePicked(Err(Error::DialogClosed)) => Task::none(),
             Message::SelectionExported(export) => {
                 match export {
                     Ok(_) => self.export_modal = true,
@@ -357,7 +701,83 @@ impl List {
                 }
                 Task::none()
             }
-            Message::Nothing => Task::none(),
+            Message::ExportScripts => {
+                let mut selection = self.selected_packages.clone();
+                selection.sort_unstable();
+                selection.dedup();
+                let changes = selection
+                    .into_iter()
+                    .flat_map(|s| {
+                        selection_changes(&self.phone_packages, selected_device, &settings.device, s)
+                    })
+                    .filter(|(_, entry)| !entry.commands.is_empty())
+                    .map(|(p_info, entry)| ScriptChange {
+                        description: self.phone_packages[p_info.i_user][p_info.index]
+                            .description
+                            .clone(),
+                        entry,
+                    })
+                    .collect();
+                Task::perform(
+                    export_scripts(selected_device.clone(), changes),
+                    Message::ScriptsExported,
+                )
+            }
+            Message::ScriptsExported(export) => {
+                match export {
+                    Ok(export) => self.script_export = Some(export),
+                    Err(err) => {
+                        error!("Failed to export the selection as scripts: {err}");
+                        self.error_modal =
+                            Some(format!("Failed to export the selection as scripts: {err}"));
+                    }
+                }
+                Task::none()
+            }
+            Message::ExportMagiskModule => {
+                // The APKs paths are queried from the device
+                if self.refuse_offline() {
+                    return Task::none();
+                }
+                let mut packages: Vec<String> = self
+                    .selected_packages
+                    .iter()
+                    .map(|&(u, i)| self.phone_packages[u][i].name.clone())
+                    .collect();
+                packages.sort_unstable();
+                packages.dedup();
+                Task::perform(
+                    export_module(selected_device.clone(), packages),
+                    Message::MagiskModuleExported,
+                )
+            }
+            Message::MagiskModuleExported(export) => {
+                match export {
+                    Ok(export) => self.module_export = Some(export),
+                    Err(err) => {
+                        error!("Failed to export the Magisk module: {err}");
+                        self.error_modal = Some(format!("Failed to export the Magisk module: {err}"));
+                    }
+                }
+                Task::none()
+            }
+            Message::ExportContribution => Task::perform(
+                export_contribution(
+                    selected_device.clone(),
+                    self.phone_packages[i_user].clone(),
+                ),
+                Message::ContributionExported,
+            ),
+            Message::ContributionExported(export) => {
+                match export {
+                    Ok(export) => self.contribution_export = Some(export),
+                    Err(err) => {
+                        error!("Failed to export unlisted packages: {err}");
+                        self.error_modal = Some(format!("Failed to export unlisted packages: {err}"));
+                    }
+                }
+                Task::none()
+            }
             Message::DescriptionEdit(action) => {
                 match action {
                     text_editor::Action::Edit(_) => {
@@ -384,6 +804,73 @@ impl List {
                 self.copy_confirmation = false;
                 Task::none()
             }
+            Message::ProfileSelected(profile) => {
+                self.selected_profile = Some(profile);
+                Task::none()
+            }
+            Message::ReviewProfile => {
+                // Profiles may have been edited since they were listed
+                self.profiles = list_profiles();
+                let Some(profile) = self.selected_profile.as_ref().and_then(|selected| {
+                    self.profiles.iter().find(|p| p.name == selected.name)
+                }) else {
+                    self.error_modal = Some("The selected profile can't be loaded anymore".into());
+                    return Task::none();
+                };
+                self.selected_profile = Some(profile.clone());
+                self.profile_plan = Some(reconcile(
+                    profile,
+                    selected_device,
+                    &self.phone_packages,
+                    settings.general.expert_mode,
+                ));
+                Task::none()
+            }
+            Message::ReviewOtaRedebloat => {
+                let Some(changes) = self.ota_changes.take() else {
+                    return Task::none();
+                };
+                self.ota_redebloat = true;
+                self.profile_plan = Some(reconcile(
+                    &changes.previous_debloat(),
+                    selected_device,
+                    &self.phone_packages,
+                    settings.general.expert_mode,
+                ));
+                Task::none()
+            }
+            Message::ApplyProfile => {
+                let Some(plan) = self.profile_plan.take() else {
+                    return Task::none();
+                };
+                self.selection_import = None;
+                if self.refuse_offline() {
+                    return Task::none();
+                }
+                if self.ota_redebloat {
+                    self.ota_redebloat = false;
+                    self.remember_states(settings, selected_device, &plan.changes);
+                }
+                if plan.changes.is_empty() {
+                    return Task::none();
+                }
+                info!("[PROFILE] Applying {}: {} change(s)", plan.profile, plan.changes.len());
+                let commands: Vec<_> = plan
+                    .changes
+                    .into_iter()
+                    .map(|change| {
+                        let p_info = PackageInfo {
+                            i_user: change.i_user,
+                            index: change.index,
+                            removal: self.phone_packages[change.i_user][change.index]
+                                .removal
+                                .to_string(),
+                        };
+                        journal_task(selected_device.adb_id.clone(), change.entry, p_info)
+                    })
+                    .collect();
+                apply_after_snapshot(selected_device, &self.phone_packages, commands)
+            }
         }
     }
 
@@ -556,9 +1043,125 @@ impl List {
         // lock
         let export_selection = export_selection;
 
+        let mut export_scripts = button(text("Export as scripts")).padding([5, 10]);
+        if !self.selected_packages.is_empty() {
+            export_scripts = export_scripts
+                .on_press(Message::ExportScripts)
+                .style(style::Button::Primary.get_style());
+        }
+        let export_scripts = tooltip(
+            export_scripts,
+            "Export the commands applying the selection to this device, and reverting it,\n\
+                as scripts to run without UAD-ng",
+            tooltip::Position::Top,
+        )
+        .gap(4)
+        .padding(10)
+        .style(style::Container::Tooltip.get_style());
+
+        let mut export_module = button(text("Magisk module")).padding([5, 10]);
+        if !self.selected_packages.is_empty() {
+            export_module = export_module
+                .on_press(Message::ExportMagiskModule)
+                .style(style::Button::Primary.get_style());
+        }
+        let export_module = tooltip(
+            export_module,
+            "For rooted devices: export a Magisk module removing the selected system packages,\n\
+                without changing the system partition, even after a factory-reset",
+            tooltip::Position::Top,
+        )
+        .gap(4)
+        .padding(10)
+        .style(style::Container::Tooltip.get_style());
+
+        let import_selection = tooltip(
+            button(text("Import selection"))
+                .padding([5, 10])
+                .on_press(Message::ImportSelection),
+            "Select the packages of a list (one per line), a CSV export or a profile",
+            tooltip::Position::Top,
+        )
+        .gap(4)
+        .padding(10)
+        .style(style::Container::Tooltip.get_style());
+
+        let list_changes = match &self.list_changelog {
+            Some(changelog) => row![
+                button_primary(text(format!(
+                    "What changed in the lists ({})",
+                    changelog.changes.len()
+                )))
+                .on_press(Message::ShowListChangelog)
+            ],
+            None => row![],
+        };
+
+        let unlisted = self
+            .phone_packages
+            .get(self.selected_user.unwrap_or_default().index)
+            .map_or(0, |rows| {
+                rows.iter()
+                    .filter(|p| p.uad_list == UadList::Unlisted)
+                    .count()
+            });
+        let mut export_unlisted =
+            button(text(format!("Export unlisted packages ({unlisted})"))).padding([5, 10]);
+        if unlisted > 0 {
+            export_unlisted = export_unlisted
+                .on_press(Message::ExportContribution)
+                .style(style::Button::Primary.get_style());
+        }
+        let export_unlisted = tooltip(
+            export_unlisted,
+            "Export packages unknown to the lists as draft entries,\n\
+                to help contributing them upstream",
+            tooltip::Position::Top,
+        )
+        .gap(4)
+        .padding(10)
+        .style(style::Container::Tooltip.get_style());
+
+        let undo = journal_button(
+            "Undo",
+            self.journal.undo_target(),
+            Message::Undo,
+        );
+        let redo = journal_button(
+            "Redo",
+            self.journal.redo_target(),
+            Message::Redo,
+        );
+
+        let profile_picklist = pick_list(
+            self.profiles.as_slice(),
+            self.selected_profile.clone(),
+            Message::ProfileSelected,
+        )
+        .placeholder(if self.profiles.is_empty() {
+            "No profiles"
+        } else {
+            "Profile..."
+        });
+        let mut review_profile = button(text("Review profile")).padding([5, 10]);
+        if self.selected_profile.is_some() {
+            review_profile = review_profile
+                .on_press(Message::ReviewProfile)
+                .style(style::Button::Primary.get_style());
+        }
+
         let action_row = row![
             export_selection,
+            export_scripts,
+            export_module,
+            import_selection,
+            export_unlisted,
+            list_changes,
+            undo,
+            redo,
             Space::new(Length::Fill, Length::Shrink),
+            profile_picklist,
+            review_profile,
             review_selection
         ]
         .width(Length::Fill)
@@ -607,6 +1210,25 @@ impl List {
         .spacing(10)
         .align_x(Alignment::Center);
 
+        let content = match &self.offline {
+            Some(device) => column![
+                container(
+                    text(format!(
+                        "Offline: {}. Nothing can be changed until the device is connected, the selection is kept until then.",
+                        device.origin
+                    ))
+                    .style(style::Text::Commentary.get_style())
+                )
+                .padding(10)
+                .width(Length::Fill)
+                .style(style::Container::BorderedFrame.get_style()),
+                content
+            ]
+            .width(Length::Fill)
+            .spacing(10),
+            None => content,
+        };
+
         if self.selection_modal {
             return Modal::new(
                 content.padding(10),
@@ -652,6 +1274,50 @@ impl List {
                 .into();
         }
 
+        if let Some(changes) = &self.ota_changes {
+            return Modal::new(content.padding(10), ota_view(changes))
+                .on_blur(Message::ModalHide)
+                .into();
+        }
+
+        if let Some(plan) = &self.profile_plan {
+            return Modal::new(content.padding(10), profile_plan_view(plan, self.selection_import.as_ref(), selected_device))
+                .on_blur(Message::ModalHide)
+                .into();
+        }
+
+        if let Some(export) = &self.module_export {
+            return Modal::new(content.padding(10), module_view(export))
+                .on_blur(Message::ModalHide)
+                .into();
+        }
+
+        if let Some(export) = &self.script_export {
+            return Modal::new(content.padding(10), scripts_view(export))
+                .on_blur(Message::ModalHide)
+                .into();
+        }
+
+        if let Some(export) = &self.contribution_export {
+            return Modal::new(content.padding(10), contribution_view(export))
+                .on_blur(Message::ModalHide)
+                .into();
+        }
+
+        if self.changelog_modal
+            && let Some(changelog) = &self.list_changelog
+        {
+            return Modal::new(
+                content.padding(10),
+                changelog_view(
+                    changelog,
+                    &self.phone_packages[self.selected_user.unwrap_or_default().index],
+                ),
+            )
+            .on_blur(Message::ModalHide)
+            .into();
+        }
+
         if let Some(err) = &self.error_modal {
             error_view(err, content, self.copy_confirmation).into()
         } else {
@@ -826,12 +1492,18 @@ impl List {
         .max_height(150)
         .padding([0, 10]);
 
+        let import_report = self
+            .selection_import
+            .as_ref()
+            .map_or_else(|| column![], import_report_view);
+
         container(
             if device.user_list.iter().filter(|&u| !u.protected).count() > 1
                 && settings.device.multi_user_mode
             {
                 column![
                     title_ctn,
+                    import_report,
                     users_ctn,
                     row![explaination_ctn].padding([0, 10]),
                     container(recap_view).padding(10),
@@ -843,6 +1515,7 @@ impl List {
             } else if !settings.device.multi_user_mode {
                 column![
                     title_ctn,
+                    import_report,
                     users_ctn,
                     container(recap_view).padding(10),
                     selected_pkgs_ctn,
@@ -853,6 +1526,7 @@ impl List {
             } else {
                 column![
                     title_ctn,
+                    import_report,
                     container(recap_view).padding(10),
                     selected_pkgs_ctn,
                     modal_btn_row,
@@ -867,6 +1541,43 @@ impl List {
         .style(style::Container::Background.get_style())
         .into()
     }
+    /// Run an undo/redo planned by the journal,
+    /// provided that the package is still in the state the journal expects
+    fn run_journal_entry(&mut self, device: &Phone, entry: Option<JournalEntry>) -> Task<Message> {
+        let Some(entry) = entry else {
+            return Task::none();
+        };
+        let Some(i_user) = device
+            .user_list
+            .iter()
+            .find(|u| u.id == entry.user_id)
+            .map(|u| u.index)
+        else {
+            return Task::none();
+        };
+        let Some((index, package)) = self
+            .phone_packages
+            .get(i_user)
+            .and_then(|pkgs| pkgs.iter().enumerate().find(|(_, p)| p.name == entry.package))
+        else {
+            self.error_modal = Some(format!("Can't {} {entry}: package not found", entry.action));
+            return Task::none();
+        };
+        if package.state != entry.from {
+            self.error_modal = Some(format!(
+                "Can't {} {entry}: the package is {} now",
+                entry.action, package.state
+            ));
+            return Task::none();
+        }
+        let p_info = PackageInfo {
+            i_user,
+            index,
+            removal: entry.action.to_string().to_uppercase(),
+        };
+        journal_task(device.adb_id.clone(), entry, p_info)
+    }
+
     fn filter_package_lists(&mut self) {
         let list_filter: UadList = self.selected_list.expect("UAD-list type must be selected");
         let package_filter: PackageState = self
@@ -899,32 +1610,35 @@ impl List {
         device_serial: S,
         user_list: Vec<User>,
     ) -> Vec<Vec<PackageRow>> {
-        let serial = device_serial.as_ref();
-        if user_list.len() <= 1 {
-            vec![fetch_packages(&uad_list, serial, None)]
-        } else {
-            user_list
-                .iter()
-                .map(|user| fetch_packages(&uad_list, serial, Some(user.id)))
-                .collect()
-        }
+        fetch_phone_packages(&uad_list, device_serial.as_ref(), &user_list)
     }
 
     #[expect(clippy::unused_async, reason = "1 call-site")]
-    async fn init_apps_view(remote: bool, phone: Phone) -> (PackageHashMap, UadListState) {
-        let uad_lists = load_debloat_lists(remote);
+    async fn init_apps_view(
+        remote: bool,
+        sources: Vec<ListSource>,
+        phone: Phone,
+    ) -> (PackageHashMap, UadListState, Option<ListChangelog>) {
+        let previous = if remote {
+            previous_lists(&sources)
+        } else {
+            None
+        };
+        let uad_lists = load_debloat_lists(&sources, remote);
         match uad_lists {
             Ok(list) => {
                 if phone.adb_id.is_empty() {
                     warn!("AppsView ready but no phone found");
                 }
-                (list, UadListState::Done)
+                let changelog = previous.map(|p| ListChangelog::new(&p, &list));
+                (list, UadListState::Done, changelog)
             }
-            Err(local_list) => {
+            Err((local_list, e)) => {
                 error!(
-                    "Error loading remote debloat list for the phone. Fallback to embedded (and outdated) list"
+                    "Error loading some debloat list sources ({e}). Fallback to cached or embedded (and outdated) lists"
                 );
-                (local_list, UadListState::Failed)
+                let changelog = previous.map(|p| ListChangelog::new(&p, &local_list));
+                (local_list, UadListState::Failed(e), changelog)
             }
         }
     }
@@ -991,6 +1705,451 @@ fn error_view<'a>(
     Modal::new(content, ctn).on_blur(Message::ModalHide)
 }
 
+/// Summary of an export of unlisted packages
+fn contribution_view(export: &ContributionExport) -> Element<'_, Message, Theme, Renderer> {
+    let title = container(row![text("Unlisted packages exported").size(24)].align_y(Alignment::Center))
+        .style(style::Container::Frame.get_style())
+        .padding([10, 0])
+        .center_y(Length::Shrink)
+        .center_x(Length::Fill);
+
+    let coverage = &export.coverage;
+    let text_box = column![
+        text(format!(
+            "The lists cover {} of the {} packages of this user ({:.1}%).",
+            coverage.listed(),
+            coverage.total,
+            coverage.percent()
+        )),
+        text(format!(
+            "Draft entries for the {} others, and a detailed report, were exported in the same directory where {NAME} is located. \
+            Please review them before submitting them upstream.",
+            coverage.unlisted()
+        )),
+    ]
+    .spacing(10)
+    .padding(20);
+
+    let files = column![
+        text(export.fragment_file.display().to_string()).style(style::Text::Commentary.get_style()),
+        text(export.report_file.display().to_string()).style(style::Text::Commentary.get_style()),
+    ]
+    .padding(20);
+
+    let modal_btn_row = row![
+        Space::new(Length::Fill, Length::Shrink),
+        button(text("Close").width(Length::Shrink))
+            .width(Length::Shrink)
+            .on_press(Message::ModalHide),
+        Space::new(Length::Fill, Length::Shrink),
+    ];
+
+    container(column![title, text_box, files, modal_btn_row])
+        .height(Length::Shrink)
+        .width(500)
+        .padding(10)
+        .style(style::Container::Frame.get_style())
+        .into()
+}
+
+fn module_view(export: &ModuleExport) -> Element<'_, Message, Theme, Renderer> {
+    let title = container(row![text("Magisk module exported").size(24)].align_y(Alignment::Center))
+        .style(style::Container::Frame.get_style())
+        .padding([10, 0])
+        .center_y(Length::Shrink)
+        .center_x(Length::Fill);
+
+    let replaced = export
+        .plan
+        .replaced
+        .iter()
+        .fold(column![].spacing(2), |col, (target, package)| {
+            col.push(text(format!("{package}: {target}")).style(style::Text::Commentary.get_style()))
+        });
+    let skipped = export
+        .plan
+        .skipped
+        .iter()
+        .fold(column![].spacing(2), |col, (package, reason)| {
+            col.push(text(format!("{package}: {reason}")).style(style::Text::Danger.get_style()))
+        });
+
+    let text_box = column![
+        text(format!(
+            "The module removing {} package(s) was exported in the same directory where {NAME} is located. \
+            Install it from the Magisk app, then reboot.",
+            export.plan.replaced.len()
+        )),
+        text(export.path.display().to_string()).style(style::Text::Commentary.get_style()),
+        scrollable(column![replaced, skipped].spacing(10).width(Length::Fill)).height(Length::Shrink),
+    ]
+    .push_maybe((!export.plan.skipped.is_empty()).then(|| {
+        text(format!(
+            "{} package(s) can't be removed systemlessly: uninstall them with adb instead.",
+            export.plan.skipped.len()
+        ))
+    }))
+    .spacing(10)
+    .padding(20);
+
+    let modal_btn_row = row![
+        Space::new(Length::Fill, Length::Shrink),
+        button(text("Close").width(Length::Shrink))
+            .width(Length::Shrink)
+            .on_press(Message::ModalHide),
+        Space::new(Length::Fill, Length::Shrink),
+    ];
+
+    container(column![title, text_box, modal_btn_row])
+        .max_height(600)
+        .width(600)
+        .padding(10)
+        .style(style::Container::Frame.get_style())
+        .into()
+}
+
+fn scripts_view(export: &ScriptExport) -> Element<'_, Message, Theme, Renderer> {
+    let title = container(row![text("Selection exported as scripts").size(24)].align_y(Alignment::Center))
+        .style(style::Container::Frame.get_style())
+        .padding([10, 0])
+        .center_y(Length::Shrink)
+        .center_x(Length::Fill);
+
+    let text_box = column![
+        text(format!(
+            "The {} change(s) of the selection, and their revert, were exported in the same directory where {NAME} is located.",
+            export.changes
+        )),
+        text("The shell scripts run on the device, the batch ones on a Windows computer. \
+            Their commands depend on the Android version and on the users of this device: only run them on a matching one.")
+            .style(style::Text::Commentary.get_style()),
+    ]
+    .spacing(10)
+    .padding(20);
+
+    let files = [
+        &export.apply_sh,
+        &export.revert_sh,
+        &export.apply_bat,
+        &export.revert_bat,
+    ]
+    .into_iter()
+    .fold(column![].padding(20), |col, path| {
+        col.push(text(path.display().to_string()).style(style::Text::Commentary.get_style()))
+    });
+
+    let modal_btn_row = row![
+        Space::new(Length::Fill, Length::Shrink),
+        button(text("Close").width(Length::Shrink))
+            .width(Length::Shrink)
+            .on_press(Message::ModalHide),
+        Space::new(Length::Fill, Length::Shrink),
+    ];
+
+    container(column![title, text_box, files, modal_btn_row])
+        .height(Length::Shrink)
+        .width(500)
+        .padding(10)
+        .style(style::Container::Frame.get_style())
+        .into()
+}
+
+/// What an import matched, and what it couldn't
+fn import_report_view(import: &SelectionMatch) -> Column<'_, Message, Theme, Renderer> {
+    let problem = |label: &str, names: &[String]| {
+        (!names.is_empty()).then(|| {
+            text(format!("{label} ({}): {}", names.len(), names.join(", ")))
+                .style(style::Text::Commentary.get_style())
+        })
+    };
+    let report = column![text(format!(
+        "{} package(s) matched from {}",
+        import.matched.len(),
+        import.path.display()
+    ))]
+    .push_maybe(problem("Not found on this user", &import.unknown))
+    .push_maybe(problem("Already removed", &import.already_removed))
+    .push_maybe(problem("Disabled, selecting would enable them", &import.not_selectable))
+    .push_maybe(problem("Unsafe, expert mode is needed", &import.unsafe_skipped))
+    .spacing(6);
+    column![
+        container(report)
+            .padding(10)
+            .width(Length::Fill)
+            .style(style::Container::BorderedFrame.get_style())
+    ]
+    .padding([0, 10])
+}
+
+/// What a system update brought back, and the offer to re-apply the debloat it undid
+fn ota_view(changes: &OtaChanges) -> Element<'_, Message, Theme, Renderer> {
+    let title = container(row![text("System update detected").size(24)].align_y(Alignment::Center))
+        .style(style::Container::Frame.get_style())
+        .padding([10, 0])
+        .center_y(Length::Shrink)
+        .center_x(Length::Fill);
+
+    let builds = text(format!(
+        "The build of the device changed since it was last loaded:\n{}\n-> {}",
+        changes.previous_fingerprint, changes.fingerprint
+    ))
+    .style(style::Text::Commentary.get_style());
+
+    let restored = changes.restored.iter().fold(column![].spacing(6), |rows, r| {
+        rows.push(
+            row![
+                text(format!("{} (user {})", r.package, r.user_id)).width(Length::FillPortion(3)),
+                text(r.was.to_string()).width(Length::FillPortion(1)),
+                text(r.now.to_string())
+                    .width(Length::FillPortion(1))
+                    .style(style::Text::Danger.get_style()),
+            ]
+            .spacing(10),
+        )
+    });
+    let new_unlisted = changes.new_unlisted.iter().fold(column![].spacing(6), |rows, (user_id, package)| {
+        rows.push(text(format!("{package} (user {user_id})")))
+    });
+    let sections = column![
+        text(format!("{} package(s) re-enabled or reinstalled by the update", changes.restored.len())).size(18),
+        container(restored)
+            .padding(10)
+            .width(Length::Fill)
+            .style(style::Container::BorderedFrame.get_style()),
+        text(format!("{} new package(s) unknown to the lists", changes.new_unlisted.len())).size(18),
+        container(new_unlisted)
+            .padding(10)
+            .width(Length::Fill)
+            .style(style::Container::BorderedFrame.get_style()),
+    ]
+    .spacing(6);
+
+    let mut review = button(text("Review re-debloat"));
+    if !changes.restored.is_empty() {
+        review = review
+            .on_press(Message::ReviewOtaRedebloat)
+            .style(style::Button::Primary.get_style());
+    }
+    let modal_btn_row = row![
+        button(text("Close")).on_press(Message::ModalHide),
+        horizontal_space(),
+        review,
+    ];
+
+    container(
+        column![
+            title,
+            builds,
+            scrollable(container(sections).padding(10).width(Length::Fill))
+                .height(Length::Fill)
+                .style(style::Scrollable::Description.get_style()),
+            modal_btn_row,
+        ]
+        .spacing(10)
+        .padding(10),
+    )
+    .width(900)
+    .height(600)
+    .style(style::Container::Background.get_style())
+    .into()
+}
+
+/// Changes needed to apply a profile, grouped by user, after the report of its `import`
+fn profile_plan_view<'a>(
+    plan: &'a ProfilePlan,
+    import: Option<&'a SelectionMatch>,
+    device: &Phone,
+) -> Element<'a, Message, Theme, Renderer> {
+    let title = container(
+        row![text(format!("Apply profile \"{}\"", plan.profile)).size(24)]
+            .align_y(Alignment::Center),
+    )
+    .style(style::Container::Frame.get_style())
+    .padding([10, 0])
+    .center_y(Length::Shrink)
+    .center_x(Length::Fill);
+
+    let users = device.user_list.iter().fold(column![].spacing(10), |users, user| {
+        let changes: Vec<_> = plan
+            .changes
+            .iter()
+            .filter(|c| c.entry.user_id == user.id)
+            .collect();
+        if changes.is_empty() {
+            return users;
+        }
+        let count = changes.len();
+        let rows = changes.into_iter().fold(column![].spacing(6), |rows, change| {
+            let to_style = if change.entry.to == PackageState::Enabled {
+                style::Text::Ok.get_style()
+            } else {
+                style::Text::Danger.get_style()
+            };
+            rows.push(
+                row![
+                    text(change.entry.package.clone()).width(Length::FillPortion(3)),
+                    text(change.entry.from.to_string()).width(Length::FillPortion(1)),
+                    text(change.entry.to.to_string())
+                        .width(Length::FillPortion(1))
+                        .style(to_style),
+                ]
+                .spacing(10),
+            )
+        });
+        users.push(
+            column![
+                text(format!("{user} ({count} changes)")).size(18),
+                container(rows)
+                    .padding(10)
+                    .width(Length::Fill)
+                    .style(style::Container::BorderedFrame.get_style()),
+            ]
+            .spacing(6),
+        )
+    });
+
+    let summary = if plan.changes.is_empty() {
+        text("The device already matches this profile")
+    } else {
+        text(format!(
+            "{} change(s) are needed. A snapshot of the device is taken before applying them.",
+            plan.changes.len()
+        ))
+    };
+    let skipped = if plan.unsafe_skipped.is_empty() {
+        column![]
+    } else {
+        column![text(format!(
+            "{} Unsafe package(s) left as they are, enable expert mode to change them: {}",
+            plan.unsafe_skipped.len(),
+            plan.unsafe_skipped.join(", ")
+        ))
+        .style(style::Text::Danger.get_style())]
+    };
+
+    let mut apply = button(text("Apply"));
+    if !plan.changes.is_empty() {
+        apply = apply
+            .on_press(Message::ApplyProfile)
+            .style(style::Button::Primary.get_style());
+    }
+    let modal_btn_row = row![
+        button(text("Cancel")).on_press(Message::ModalHide),
+        horizontal_space(),
+        apply,
+    ];
+
+    container(
+        column![
+            title,
+            import.map_or_else(|| column![], import_report_view),
+            summary,
+            skipped,
+            scrollable(container(users).padding(10).width(Length::Fill))
+                .height(Length::Fill)
+                .style(style::Scrollable::Description.get_style()),
+            modal_btn_row,
+        ]
+        .spacing(10)
+        .padding(10),
+    )
+    .width(900)
+    .height(600)
+    .style(style::Container::Background.get_style())
+    .into()
+}
+
+/// "What changed in the lists" dialog.
+/// Changes that concern packages of the selected user are highlighted.
+fn changelog_view<'a>(
+    changelog: &'a ListChangelog,
+    packages: &'a [PackageRow],
+) -> Element<'a, Message, Theme, Renderer> {
+    let change_row = |change: &ListChange| {
+        let on_device = packages.iter().find(|p| p.name == change.package);
+        // A package that was debloated and is now marked as `Unsafe`
+        // is the main thing users need to notice
+        let state_style = |state| {
+            if change.promoted_to_unsafe() && state != PackageState::Enabled {
+                style::Text::Danger.get_style()
+            } else {
+                style::Text::Ok.get_style()
+            }
+        };
+        let device_state = match on_device.map(|p| p.state) {
+            Some(PackageState::Uninstalled) => text("Removed on device")
+                .style(state_style(PackageState::Uninstalled)),
+            Some(state) => text(format!("{state} on device")).style(state_style(state)),
+            None => text("Not on device").style(style::Text::Commentary.get_style()),
+        };
+        row![
+            text(change.package.clone())
+                .width(Length::FillPortion(3))
+                .style(if on_device.is_some() {
+                    style::Text::Default.get_style()
+                } else {
+                    style::Text::Commentary.get_style()
+                }),
+            text(change.kind.to_string()).width(Length::FillPortion(2)),
+            device_state.width(Length::FillPortion(1)),
+        ]
+        .spacing(10)
+    };
+
+    let unsafe_changes: Vec<&ListChange> = changelog.promoted_to_unsafe().collect();
+    let unsafe_section = if unsafe_changes.is_empty() {
+        column![]
+    } else {
+        column![
+            text("Now marked as Unsafe").style(style::Text::Danger.get_style()),
+            container(
+                unsafe_changes
+                    .into_iter()
+                    .fold(column![].spacing(6), |col, c| col.push(change_row(c)))
+            )
+            .padding(10)
+            .width(Length::Fill)
+            .style(style::Container::BorderedFrame.get_style()),
+        ]
+        .spacing(6)
+    };
+
+    let other_changes = changelog
+        .others()
+        .fold(column![].spacing(6), |col, c| col.push(change_row(c)));
+
+    let title_ctn = container(
+        row![text("What changed in the lists").size(24)].align_y(Alignment::Center),
+    )
+    .style(style::Container::Frame.get_style())
+    .padding([10, 0])
+    .center_y(Length::Shrink)
+    .center_x(Length::Fill);
+
+    let modal_btn_row = row![
+        horizontal_space(),
+        button(text("Close")).on_press(Message::ModalHide),
+        horizontal_space(),
+    ];
+
+    container(
+        column![
+            title_ctn,
+            unsafe_section,
+            scrollable(container(other_changes).padding(10).width(Length::Fill))
+                .height(Length::Fill)
+                .style(style::Scrollable::Description.get_style()),
+            modal_btn_row,
+        ]
+        .spacing(10)
+        .padding(10),
+    )
+    .width(900)
+    .height(600)
+    .style(style::Container::Background.get_style())
+    .into()
+}
+
 fn waiting_view<'a>(
     displayed_text: &(impl ToString + ?Sized),
     btn: Option<button::Button<'a, Message, Theme, Renderer>>,
@@ -1019,10 +2178,36 @@ fn build_action_pkg_commands(
     settings: &DeviceSettings,
     selection: (usize, usize),
 ) -> Vec<Task<Message>> {
+    selection_changes(packages, device, settings, selection)
+        .into_iter()
+        .map(|(p_info, entry)| journal_task(device.adb_id.clone(), entry, p_info))
+        .collect()
+}
+
+/// Take a [`BackupTrigger::BeforeApply`] snapshot of the device, then run `commands`
+fn apply_after_snapshot(
+    device: &Phone,
+    packages: &[Vec<PackageRow>],
+    commands: Vec<Task<Message>>,
+) -> Task<Message> {
+    Task::perform(
+        backup_phone(device.clone(), packages.to_vec(), BackupTrigger::BeforeApply),
+        Message::SnapshotTaken,
+    )
+    .chain(Task::batch(commands))
+}
+
+/// Changes applying the `selection` to every concerned user
+fn selection_changes(
+    packages: &[Vec<PackageRow>],
+    device: &Phone,
+    settings: &DeviceSettings,
+    selection: (usize, usize),
+) -> Vec<(PackageInfo, JournalEntry)> {
     let pkg = &packages[selection.0][selection.1];
     let wanted_state = pkg.state.opposite(settings.disable_mode);
 
-    let mut commands = vec![];
+    let mut changes = vec![];
     for u in device.user_list.iter().filter(|&&u| {
         !u.protected
             && packages
@@ -1037,32 +2222,69 @@ fn build_action_pkg_commands(
             u_pkg.state.opposite(settings.disable_mode)
         };
 
-        let actions = apply_pkg_state_commands(&u_pkg.into(), wanted_state, *u, device);
-        for (j, action) in actions.into_iter().enumerate() {
-            let p_info = PackageInfo {
-                i_user: u.index,
-                index: selection.1,
-                removal: pkg.removal.to_string(),
-            };
-            // In the end there is only one package state change
-            // even if we run multiple adb commands
-            commands.push(Task::perform(
-                adb_shell_command(
-                    // this is typically small,
-                    // so it's fine.
-                    device.adb_id.clone(),
-                    action,
-                    p_info,
-                ),
-                if j == 0 {
-                    Message::ChangePackageState
-                } else {
-                    |_| Message::Nothing
-                },
-            ));
-        }
+        let Some(entry) = JournalEntry::plan(
+            device,
+            &u_pkg.name,
+            u.id,
+            u_pkg.state,
+            wanted_state,
+            JournalAction::Change,
+        ) else {
+            continue;
+        };
+        let p_info = PackageInfo {
+            i_user: u.index,
+            index: selection.1,
+            removal: pkg.removal.to_string(),
+        };
+        changes.push((p_info, entry));
     }
-    commands
+    changes
+}
+
+/// Undo/redo button, with a tooltip describing the change it would revert or make again
+fn journal_button<'a>(
+    label: &'a str,
+    target: Option<&JournalEntry>,
+    message: Message,
+) -> Element<'a, Message, Theme, Renderer> {
+    match target {
+        Some(entry) => tooltip(
+            button_primary(text(label)).on_press(message),
+            text(entry.to_string()),
+            tooltip::Position::Top,
+        )
+        .gap(4)
+        .padding(10)
+        .style(style::Container::Tooltip.get_style())
+        .into(),
+        None => button(text(label)).padding([5, 10]).into(),
+    }
+}
+
+/// Run the commands of `entry` one after the other.
+///
+/// In the end there is only one package state change even if we run multiple adb commands:
+/// the 1st one changes the state, the following ones only clean up.
+fn journal_task(serial: String, mut entry: JournalEntry, p_info: PackageInfo) -> Task<Message> {
+    Task::perform(
+        async move {
+            let mut result = Ok(p_info.clone());
+            for (j, action) in entry.commands.clone().into_iter().enumerate() {
+                match adb_shell_command(&serial, action, p_info.clone()).await {
+                    Ok(_) => {}
+                    Err(AdbError::Generic(err)) if j == 0 => {
+                        entry.error = Some(err.clone());
+                        result = Err(AdbError::Generic(err));
+                        break;
+                    }
+                    Err(AdbError::Generic(err)) => warn!("{err}"),
+                }
+            }
+            (entry, result)
+        },
+        |(entry, result)| Message::ChangePackageState(entry, result),
+    )
 }
 
 fn recap<'a>(settings: &Settings, recap: &SummaryEntry) -> Element<'a, Message, Theme, Renderer> {
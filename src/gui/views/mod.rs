@@ -1,3 +1,5 @@
 pub mod about;
+pub mod compare;
+pub mod fleet;
 pub mod list;
 pub mod settings;
@@ -1,11 +1,25 @@
 use crate::core::utils::string_to_theme;
 use crate::core::{
+    archive::{archive_phone, reinstall, ArchiveReport},
+    compare::{compare_users, CompareSource, Presence, UserChanges},
     config::{BackupSettings, Config, DeviceSettings, GeneralSettings},
+    fleet::journal_changes,
     helpers::button_primary,
-    save::{backup_phone, list_available_backup_user, list_available_backups, restore_backup},
-    sync::{adb_shell_command, get_android_sdk, supports_multi_user, AdbError, Phone, User},
+    journal::JournalEntry,
+    ota::KnownStates,
+    report::{export_report, DeviceReport, ReportFormat},
+    save::{
+        backup_phone, dry_run, dry_run_text, export_dry_run, extract_reinstalls, list_all_backups, list_available_backups, restore_backup, restore_commands,
+        BackupFile, BackupScope, BackupTrigger, Compatibility, ReinstallItem, RestorePlan,
+        PlannedRestore, RestoreReport, RetentionUnit, SnapshotRetention,
+    },
+    sync::{
+        adb_shell_command, get_android_sdk, get_device_fingerprint, supports_multi_user, AdbError,
+        Phone, User,
+    },
+    uad_lists::{ListSource, ListSourceKind, PackageHashMap, PackageState},
     utils::{
-        export_packages, generate_backup_name, open_folder, open_url, DisplayablePath, Error,
+        export_packages, generate_backup_name, open_folder, open_url, Error,
         NAME,
     },
 };
@@ -17,13 +31,47 @@ use crate::gui::{
     widgets::package_row::PackageRow,
     widgets::text,
 };
-use iced::widget::{button, checkbox, column, container, pick_list, row, scrollable, Space};
-use iced::{alignment, Alignment, Element, Length, Renderer, Theme};
+use iced::widget::{
+    button, checkbox, column, container, pick_list, row, scrollable, text_input, tooltip, Column,
+    Space,
+};
+use iced::{alignment, padding, Alignment, Element, Length, Renderer, Theme};
 use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
 
 #[derive(Debug, Clone)]
 pub enum PopUpModal {
     ExportUninstalled,
+    /// Packages to restore are in `Settings::restore_plan`
+    Restore,
+    /// See `Settings::restore_report`
+    RestoreReport,
+    /// See `Settings::comparison`
+    Compare,
+}
+
+/// Two states of a device, and how they differ
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    old: CompareSource,
+    new: CompareSource,
+    users: Result<Vec<UserChanges>, String>,
+}
+
+impl Comparison {
+    fn new(
+        old: CompareSource,
+        new: CompareSource,
+        phone: &Phone,
+        packages: &[Vec<PackageRow>],
+        uad_lists: &PackageHashMap,
+    ) -> Self {
+        let users = old.users(phone, packages).and_then(|old_users| {
+            new.users(phone, packages)
+                .map(|new_users| compare_users(&old_users, &new_users, uad_lists))
+        });
+        Self { old, new, users }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -32,6 +80,22 @@ pub struct Settings {
     pub device: DeviceSettings,
     is_loading: bool,
     modal: Option<PopUpModal>,
+    list_source_input: String,
+    /// Differences with the selected backup, being reviewed before restoring
+    restore_plan: RestorePlan,
+    /// Outcome of the ongoing (or last) restore
+    restore_report: Option<RestoreReport>,
+    /// `ro.build.fingerprint` of the selected device, to find backups of the same build
+    device_fingerprint: String,
+    /// Backups being compared
+    comparison: Option<Comparison>,
+    /// Show the commands of the restore instead of its packages
+    show_restore_commands: bool,
+    /// Where the restore plan was exported, or why it couldn't be
+    dry_run_export: Option<Result<PathBuf, String>>,
+    report_format: ReportFormat,
+    /// Where the last report was exported, or why it couldn't be
+    report_export: Option<Result<PathBuf, String>>,
 }
 
 impl Default for Settings {
@@ -41,6 +105,15 @@ impl Default for Settings {
             device: DeviceSettings::default(),
             is_loading: false,
             modal: None,
+            list_source_input: String::new(),
+            restore_plan: RestorePlan::default(),
+            restore_report: None,
+            device_fingerprint: String::new(),
+            comparison: None,
+            show_restore_commands: false,
+            dry_run_export: None,
+            report_format: ReportFormat::default(),
+            report_export: None,
         }
     }
 }
@@ -53,19 +126,82 @@ pub enum Message {
     MultiUserMode(bool),
     ApplyTheme(Theme),
     UrlPressed(PathBuf),
-    BackupSelected(DisplayablePath),
+    BackupSelected(BackupFile),
+    BackupScopeSelected(BackupScope),
     BackupDevice,
     RestoreDevice,
-    RestoringDevice(Result<PackageInfo, AdbError>),
+    RestorePlanned(Result<RestorePlan, String>),
+    CompareBackups,
+    CompareOldSelected(CompareSource),
+    CompareNewSelected(CompareSource),
+    RestoreItemToggled(usize, bool),
+    ReinstallItemToggled(usize, bool),
+    ShowRestoreCommands(bool),
+    ExportDryRun,
+    DryRunExported(Result<PathBuf, String>),
+    ArchiveDevice,
+    DeviceArchived(Result<ArchiveReport, String>),
+    PackageReinstalled(JournalEntry, Result<(), String>),
+    RestoreUserToggled(u16, bool),
+    ConfirmRestore,
+    RestoringDevice(JournalEntry, Result<PackageInfo, AdbError>),
     DeviceBackedUp(Result<bool, String>),
     ChooseBackUpFolder,
     FolderChosen(Result<PathBuf, Error>),
     ExportPackages,
     PackagesExported(Result<bool, String>),
+    ReportFormatSelected(ReportFormat),
+    ExportReport,
+    ReportExported(Result<Option<PathBuf>, String>),
     ModalHide,
+    ListSourceToggled(usize, bool),
+    ListSourcePriority(usize, i32),
+    ListSourceRemoved(usize),
+    ListSourceChecksum(usize, String),
+    ListSourceInput(String),
+    ListSourceAdded,
+    SnapshotRetentionChanged(SnapshotRetention),
 }
 
 impl Settings {
+    /// Fill `backup` with the backups matching its scope, and select the most recent one
+    fn load_backups(&self, backup: &mut BackupSettings, phone: &Phone) {
+        backup.backups = match backup.scope {
+            BackupScope::ThisDevice => {
+                list_available_backups(&self.general.backup_folder.join(&phone.adb_id))
+            }
+            scope => list_all_backups(&self.general.backup_folder)
+                .into_iter()
+                .filter(|b| scope.matches(b, phone, &self.device_fingerprint))
+                .collect(),
+        };
+        backup.selected = backup.backups.first().cloned();
+    }
+
+    /// Journal a finished change of the restore, so that it can be undone, and report it
+    fn record_restored(&mut self, phone: &Phone, mut entry: JournalEntry, result: Result<(), String>) {
+        entry.error = result.clone().err();
+        if let Err(e) = journal_changes(&phone.adb_id, std::slice::from_ref(&entry)) {
+            error!("[JOURNAL] Can't save the journal of {}: {e}", phone.adb_id);
+        }
+        if let Some(report) = &mut self.restore_report {
+            report.record(format!("{} (user {})", entry.package, entry.user_id), result);
+        }
+    }
+
+    /// `ro.build.fingerprint` of the selected device
+    #[must_use]
+    pub fn device_fingerprint(&self) -> &str {
+        &self.device_fingerprint
+    }
+
+    /// Reload the backups of `phone`, e.g. after a snapshot was taken
+    pub fn reload_backups(&mut self, phone: &Phone) {
+        let mut backup = std::mem::take(&mut self.device.backup);
+        self.load_backups(&mut backup, phone);
+        self.device.backup = backup;
+    }
+
     #[allow(clippy::too_many_lines)]
     pub fn update(
         &mut self,
@@ -74,10 +210,68 @@ impl Settings {
         nb_running_async_adb_commands: &mut u32,
         msg: Message,
         selected_user: Option<User>,
+        uad_lists: &PackageHashMap,
     ) -> iced::Task<Message> {
         match msg {
             Message::ModalHide => {
                 self.modal = None;
+                self.restore_plan = RestorePlan::default();
+                self.restore_report = None;
+                self.comparison = None;
+                self.show_restore_commands = false;
+                self.dry_run_export = None;
+                iced::Task::none()
+            }
+            Message::ShowRestoreCommands(toggled) => {
+                self.show_restore_commands = toggled;
+                iced::Task::none()
+            }
+            Message::ExportDryRun => {
+                let backup = self
+                    .device
+                    .backup
+                    .selected
+                    .as_ref()
+                    .map_or_else(String::new, ToString::to_string);
+                let text = dry_run_text(phone, &backup, &dry_run(phone, &self.restore_plan));
+                iced::Task::perform(export_dry_run(text), Message::DryRunExported)
+            }
+            Message::DryRunExported(result) => {
+                if let Err(e) = &result {
+                    error!("[RESTORE] Failed to export the restore plan: {e}");
+                }
+                self.dry_run_export = Some(result);
+                iced::Task::none()
+            }
+            Message::CompareBackups => {
+                let old = self
+                    .device
+                    .backup
+                    .selected
+                    .clone()
+                    .map_or(CompareSource::Device, |b| CompareSource::Backup(Box::new(b)));
+                self.comparison = Some(Comparison::new(
+                    old,
+                    CompareSource::Device,
+                    phone,
+                    packages,
+                    uad_lists,
+                ));
+                self.modal = Some(PopUpModal::Compare);
+                iced::Task::none()
+            }
+            Message::CompareOldSelected(old) => {
+                if let Some(comparison) = self.comparison.take() {
+                    self.comparison =
+                        Some(Comparison::new(old, comparison.new, phone, packages, uad_lists));
+                }
+                iced::Task::none()
+            }
+            Message::CompareNewSelected(new) => {
+                if let Some(comparison) = self.comparison.take() {
+                    self.comparison =
+                        Some(Comparison::new(comparison.old, new, phone, packages, uad_lists));
+                }
                 iced::Task::none()
             }
             Message::ExpertMode(toggled) => {
@@ -110,16 +304,83 @@ impl Settings {
                 open_url(url);
                 iced::Task::none()
             }
+            Message::ListSourceToggled(i, toggled) => {
+                if let Some(source) = self.general.list_sources.get_mut(i) {
+                    source.enabled = toggled;
+                    Config::save_changes(self, &phone.adb_id);
+                }
+                iced::Task::none()
+            }
+            Message::ListSourcePriority(i, delta) => {
+                if let Some(source) = self.general.list_sources.get_mut(i) {
+                    source.priority = source.priority.saturating_add(delta);
+                    Config::save_changes(self, &phone.adb_id);
+                }
+                iced::Task::none()
+            }
+            Message::SnapshotRetentionChanged(retention) => {
+                self.general.snapshot_retention = retention;
+                Config::save_changes(self, &phone.adb_id);
+                iced::Task::none()
+            }
+            Message::ListSourceRemoved(i) => {
+                // The upstream list can only be disabled
+                if self.general.list_sources.get(i).is_some_and(|s| !s.is_upstream()) {
+                    self.general.list_sources.remove(i);
+                    Config::save_changes(self, &phone.adb_id);
+                }
+                iced::Task::none()
+            }
+            Message::ListSourceChecksum(i, input) => {
+                if let Some(source) = self.general.list_sources.get_mut(i) {
+                    let input = input.trim();
+                    source.checksum_url = (!input.is_empty()).then(|| input.to_string());
+                    Config::save_changes(self, &phone.adb_id);
+                }
+                iced::Task::none()
+            }
+            Message::ListSourceInput(input) => {
+                self.list_source_input = input;
+                iced::Task::none()
+            }
+            Message::ListSourceAdded => {
+                let location = self.list_source_input.trim();
+                if !location.is_empty() {
+                    // New sources override the existing ones by default
+                    let priority = self
+                        .general
+                        .list_sources
+                        .iter()
+                        .map(|s| s.priority)
+                        .max()
+                        .unwrap_or_default()
+                        .saturating_add(1);
+                    self.general
+                        .list_sources
+                        .push(ListSource::new(location, priority));
+                    self.list_source_input.clear();
+                    Config::save_changes(self, &phone.adb_id);
+                }
+                iced::Task::none()
+            }
             Message::LoadDeviceSettings => {
-                let backups =
-                    list_available_backups(&self.general.backup_folder.join(&phone.adb_id));
-                let backup = BackupSettings {
-                    backups: backups.clone(),
-                    selected: backups.first().cloned(),
+                let scope = if self.device.device_id == phone.adb_id {
+                    self.device.backup.scope
+                } else {
+                    BackupScope::default()
+                };
+                self.device_fingerprint = if phone.adb_id.is_empty() {
+                    String::new()
+                } else {
+                    get_device_fingerprint(&phone.adb_id)
+                };
+                self.report_export = None;
+                let mut backup = BackupSettings {
                     users: phone.user_list.clone(),
-                    selected_user: phone.user_list.first().copied(),
-                    backup_state: String::default(),
+                    scope,
+                    ..BackupSettings::default()
                 };
+                self.load_backups(&mut backup, phone);
                 match Config::load_configuration_file()
                     .devices
                     .iter()
@@ -134,33 +395,34 @@ impl Settings {
                             device_id: phone.adb_id.clone(),
                             multi_user_mode: supports_multi_user(phone),
                             disable_mode: false,
+                            known_states: KnownStates::default(),
                             backup,
                         }
                     }
                 }
                 iced::Task::none()
             }
-            Message::BackupSelected(d_path) => {
-                self.device.backup.selected = Some(d_path.clone());
-                self.device.backup.users = list_available_backup_user(d_path);
+            Message::BackupScopeSelected(scope) => {
+                let mut backup = std::mem::take(&mut self.device.backup);
+                backup.scope = scope;
+                self.load_backups(&mut backup, phone);
+                self.device.backup = backup;
+                iced::Task::none()
+            }
+            Message::BackupSelected(backup) => {
+                self.device.backup.users.clone_from(&backup.users);
+                self.device.backup.selected = Some(backup);
                 iced::Task::none()
             }
             Message::BackupDevice => iced::Task::perform(
-                backup_phone(
-                    phone.user_list.clone(),
-                    self.device.device_id.clone(),
-                    packages.to_vec(),
-                ),
+                backup_phone(phone.clone(), packages.to_vec(), BackupTrigger::Manual),
                 Message::DeviceBackedUp,
             ),
             Message::DeviceBackedUp(is_backed_up) => {
                 match is_backed_up {
                     Ok(_) => {
                         info!("[BACKUP] Backup successfully created");
-                        self.device.backup.backups = list_available_backups(
-                            &self.general.backup_folder.join(phone.adb_id.clone()),
-                        );
-                        self.device.backup.selected = self.device.backup.backups.first().cloned();
+                        self.reload_backups(phone);
                     }
                     Err(err) => {
                         error!("[BACKUP FAILED] Backup creation failed: {err:?}");
@@ -168,27 +430,20 @@ impl Settings {
                 }
                 iced::Task::none()
             }
-            Message::RestoreDevice => match restore_backup(phone, packages, &self.device) {
-                Ok(r_packages) => {
-                    let mut commands = vec![];
-                    *nb_running_async_adb_commands = 0;
-                    for p in &r_packages {
-                        let p_info = PackageInfo {
-                            i_user: 0,
-                            index: p.index,
-                            removal: "RESTORE".to_string(),
-                        };
-                        for command in p.commands.clone() {
-                            *nb_running_async_adb_commands += 1;
-                            commands.push(iced::Task::perform(
-                                // This is "safe" thanks to serde:
-                                // https://github.com/Universal-Debloater-Alliance/universal-android-debloater-next-generation/issues/760
-                                adb_shell_command(phone.adb_id.clone(), command, p_info.clone()),
-                                Message::RestoringDevice,
-                            ));
-                        }
-                    }
-                    if r_packages.is_empty() {
+            Message::RestoreDevice => {
+                // Reading an archive takes a while
+                self.device.backup.backup_state = "Reading the backup...".to_string();
+                let phone = phone.clone();
+                let packages = packages.to_vec();
+                let device = self.device.clone();
+                iced::Task::perform(
+                    async move { restore_backup(&phone, &packages, &device) },
+                    Message::RestorePlanned,
+                )
+            }
+            Message::RestorePlanned(plan) => {
+                match plan {
+                    Ok(plan) if plan.is_empty() => {
                         if get_android_sdk(&phone.adb_id) == 0 {
                             self.device.backup.backup_state = "Device is not connected".to_string();
                         } else {
@@ -196,20 +451,179 @@ impl Settings {
                                 "Device state is already restored".to_string();
                         }
                     }
-                    info!(
-                        "[RESTORE] Restoring backup {}",
-                        self.device.backup.selected.as_ref().unwrap()
-                    );
-                    iced::Task::batch(commands)
+                    Ok(plan) => {
+                        self.device.backup.backup_state.clear();
+                        self.restore_plan = plan;
+                        self.modal = Some(PopUpModal::Restore);
+                    }
+                    Err(e) => {
+                        self.device.backup.backup_state.clone_from(&e);
+                        error!("{} - {}", self.device.backup.selected.as_ref().unwrap(), e);
+                    }
                 }
-                Err(e) => {
-                    self.device.backup.backup_state.clone_from(&e);
-                    error!("{} - {}", self.device.backup.selected.as_ref().unwrap(), e);
-                    iced::Task::none()
+                iced::Task::none()
+            }
+            Message::RestoreItemToggled(i, selected) => {
+                if let Some(item) = self.restore_plan.items.get_mut(i) {
+                    item.selected = selected;
                 }
-            },
-            // Trigger an action in mod.rs (Message::SettingsAction(msg))
-            Message::RestoringDevice(_) => iced::Task::none(),
+                iced::Task::none()
+            }
+            Message::ReinstallItemToggled(i, selected) => {
+                if let Some(item) = self.restore_plan.reinstalls.get_mut(i) {
+                    item.selected = selected;
+                }
+                iced::Task::none()
+            }
+            Message::RestoreUserToggled(user_id, selected) => {
+                for item in self
+                    .restore_plan
+                    .items
+                    .iter_mut()
+                    .filter(|i| i.user.id == user_id)
+                {
+                    item.selected = selected;
+                }
+                for item in self
+                    .restore_plan
+                    .reinstalls
+                    .iter_mut()
+                    .filter(|i| i.user.id == user_id)
+                {
+                    item.selected = selected;
+                }
+                iced::Task::none()
+            }
+            Message::ArchiveDevice => {
+                let mut selected: Vec<String> = packages
+                    .iter()
+                    .flatten()
+                    .filter(|p| p.selected)
+                    .map(|p| p.name.clone())
+                    .collect();
+                selected.sort_unstable();
+                selected.dedup();
+                self.device.backup.backup_state = format!("Pulling {} APK(s)...", selected.len());
+                iced::Task::perform(
+                    archive_phone(phone.clone(), packages.to_vec(), selected),
+                    Message::DeviceArchived,
+                )
+            }
+            Message::DeviceArchived(result) => {
+                match result {
+                    Ok(report) => {
+                        info!(
+                            "[ARCHIVE] {} package(s) archived into {}",
+                            report.archived.len(),
+                            report.path.display()
+                        );
+                        self.device.backup.backup_state = if report.failed.is_empty() {
+                            String::new()
+                        } else {
+                            format!(
+                                "The APKs of {} package(s) couldn't be pulled, see the logs",
+                                report.failed.len()
+                            )
+                        };
+                        self.reload_backups(phone);
+                    }
+                    Err(err) => {
+                        error!("[ARCHIVE FAILED] Archive creation failed: {err}");
+                        self.device.backup.backup_state = format!("Archive failed: {err}");
+                    }
+                }
+                iced::Task::none()
+            }
+            // Also triggers an action in mod.rs (Message::SettingsAction(msg))
+            Message::PackageReinstalled(entry, result) => {
+                self.record_restored(phone, entry, result);
+                iced::Task::none()
+            }
+            Message::ConfirmRestore => {
+                let plan = std::mem::take(&mut self.restore_plan);
+                let r_packages = restore_commands(phone, &plan.items);
+                for skipped in &plan.skipped {
+                    warn!("[RESTORE] Skipped {skipped}");
+                }
+                let reinstalls: Vec<ReinstallItem> =
+                    plan.reinstalls.into_iter().filter(|r| r.selected).collect();
+                // Extracted once, by the first reinstall to run
+                let extracted = Arc::new(OnceLock::new());
+                self.restore_report = Some(RestoreReport {
+                    skipped: plan.skipped,
+                    pending: r_packages.len() + reinstalls.len(),
+                    ..RestoreReport::default()
+                });
+                self.modal = Some(PopUpModal::RestoreReport);
+
+                let mut commands = vec![];
+                *nb_running_async_adb_commands = 0;
+                for p in r_packages {
+                    let p_info = PackageInfo {
+                        i_user: p.i_user,
+                        index: p.index,
+                        removal: "RESTORE".to_string(),
+                    };
+                    let serial = phone.adb_id.clone();
+                    let entry = p.entry;
+                    let p_commands = entry.commands.clone();
+                    *nb_running_async_adb_commands += 1;
+                    // Commands of a package are sequential:
+                    // the following ones are pointless if the 1st one failed
+                    commands.push(iced::Task::perform(
+                        async move {
+                            for command in p_commands {
+                                // This is "safe" thanks to serde:
+                                // https://github.com/Universal-Debloater-Alliance/universal-android-debloater-next-generation/issues/760
+                                adb_shell_command(&serial, command, p_info.clone()).await?;
+                            }
+                            Ok(p_info)
+                        },
+                        move |result| Message::RestoringDevice(entry.clone(), result),
+                    ));
+                }
+                for item in &reinstalls {
+                    let serial = phone.adb_id.clone();
+                    let user_id = supports_multi_user(phone).then_some(item.user.id);
+                    let entry = item.entry(phone);
+                    let (item, extracted) = (item.clone(), Arc::clone(&extracted));
+                    let device = phone.clone();
+                    let (archive, all) = (plan.archive.clone(), reinstalls.clone());
+                    *nb_running_async_adb_commands += 1;
+                    commands.push(iced::Task::perform(
+                        async move {
+                            let archive = archive.ok_or("the backup isn't an archive")?;
+                            extracted
+                                .get_or_init(|| extract_reinstalls(&archive, &device, &all))
+                                .clone()?;
+                            reinstall(&serial, &item.package, user_id, &item.apks, item.wanted)
+                        },
+                        move |result| Message::PackageReinstalled(entry.clone(), result),
+                    ));
+                }
+                info!(
+                    "[RESTORE] Restoring {} packages of backup {}",
+                    commands.len(),
+                    self.device.backup.selected.as_ref().unwrap()
+                );
+                if commands.is_empty() {
+                    return iced::Task::none();
+                }
+                iced::Task::perform(
+                    backup_phone(phone.clone(), packages.to_vec(), BackupTrigger::BeforeRestore),
+                    Message::DeviceBackedUp,
+                )
+                .chain(iced::Task::batch(commands))
+            }
+            // Also triggers an action in mod.rs (Message::SettingsAction(msg))
+            Message::RestoringDevice(entry, result) => {
+                self.record_restored(
+                    phone,
+                    entry,
+                    result.map(|_| ()).map_err(|AdbError::Generic(e)| e),
+                );
+                iced::Task::none()
+            }
             Message::FolderChosen(result) => {
                 self.is_loading = false;
 
@@ -224,6 +638,7 @@ impl Settings {
                             nb_running_async_adb_commands,
                             Message::LoadDeviceSettings,
                             selected_user,
+                            uad_lists,
                         );
                     }
                 }
@@ -248,6 +663,32 @@ impl Settings {
                 }
                 iced::Task::none()
             }
+            Message::ReportFormatSelected(format) => {
+                self.report_format = format;
+                iced::Task::none()
+            }
+            Message::ExportReport => iced::Task::perform(
+                export_report(
+                    DeviceReport::new(phone, packages, &self.device_fingerprint),
+                    self.report_format,
+                ),
+                Message::ReportExported,
+            ),
+            Message::ReportExported(result) => {
+                match result {
+                    Ok(Some(path)) => {
+                        info!("[REPORT] Device report exported to {}", path.display());
+                        self.report_export = Some(Ok(path));
+                    }
+                    // The dialog was closed
+                    Ok(None) => {}
+                    Err(e) => {
+                        error!("[REPORT] Failed to export the device report: {e}");
+                        self.report_export = Some(Err(e));
+                    }
+                }
+                iced::Task::none()
+            }
         }
     }
 
@@ -294,12 +735,43 @@ impl Settings {
         .spacing(10)
         .align_y(Alignment::Center);
 
+        let retention = self.general.snapshot_retention;
+        let snapshot_retention_row = row![
+            "Keep automatic snapshots for the",
+            button_primary(text("-")).on_press(Message::SnapshotRetentionChanged(
+                SnapshotRetention {
+                    keep: retention.keep.saturating_sub(1).max(1),
+                    ..retention
+                }
+            )),
+            text(retention.keep),
+            button_primary(text("+")).on_press(Message::SnapshotRetentionChanged(
+                SnapshotRetention {
+                    keep: retention.keep.saturating_add(1),
+                    ..retention
+                }
+            )),
+            pick_list(RetentionUnit::ALL, Some(retention.unit), move |unit| {
+                Message::SnapshotRetentionChanged(SnapshotRetention { unit, ..retention })
+            }),
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        let snapshot_retention_descr = text(
+            "A snapshot of the device is taken before applying or restoring changes. \
+            The most recent one is always kept, manual backups are never deleted.",
+        )
+        .style(style::Text::Commentary.get_style());
+
         let general_ctn = container(
             column![
                 expert_mode_checkbox,
                 expert_mode_descr,
                 choose_backup_row,
                 choose_backup_descr,
+                snapshot_retention_row,
+                snapshot_retention_descr,
             ]
             .spacing(10),
         )
@@ -308,6 +780,76 @@ impl Settings {
         .height(Length::Shrink)
         .style(style::Container::Frame.get_style());
 
+        let list_sources = self.general.list_sources.iter().enumerate().fold(
+            column![].spacing(6),
+            |col, (i, source)| {
+                let remove_btn = if source.is_upstream() {
+                    button(text("Remove")).padding([5, 10])
+                } else {
+                    button_primary(text("Remove")).on_press(Message::ListSourceRemoved(i))
+                };
+                // Only downloaded lists can be checked
+                let checksum_input = (source.kind == ListSourceKind::Url).then(|| {
+                    text_input(
+                        if source.is_upstream() {
+                            "No SHA-256 published upstream"
+                        } else {
+                            "URL of its SHA-256 (optional)"
+                        },
+                        source.checksum_url.as_deref().unwrap_or_default(),
+                    )
+                    .on_input(move |input| Message::ListSourceChecksum(i, input))
+                    .padding([5, 10])
+                    .width(250)
+                });
+                col.push(
+                    row![
+                        checkbox("", source.enabled)
+                            .on_toggle(move |toggled| Message::ListSourceToggled(i, toggled))
+                            .style(style::CheckBox::SettingsEnabled.get_style()),
+                        text(source.kind.to_string()).width(100),
+                        text(source.location.clone()).width(Length::Fill),
+                    ]
+                    .push_maybe(checksum_input)
+                    .push(text(format!("Priority: {}", source.priority)))
+                    .push(button_primary(text("+")).on_press(Message::ListSourcePriority(i, 1)))
+                    .push(button_primary(text("-")).on_press(Message::ListSourcePriority(i, -1)))
+                    .push(remove_btn)
+                    .spacing(10)
+                    .align_y(Alignment::Center),
+                )
+            },
+        );
+
+        let add_list_source_row = row![
+            text_input(
+                "URL, path to a list file or to a git checkout...",
+                &self.list_source_input
+            )
+            .on_input(Message::ListSourceInput)
+            .on_submit(Message::ListSourceAdded)
+            .padding([5, 10]),
+            button_primary(text("Add")).on_press(Message::ListSourceAdded),
+        ]
+        .spacing(10)
+        .align_y(Alignment::Center);
+
+        let list_sources_descr = text(
+            "Packages defined by several sources use the entry of the source with the highest priority. \
+            A downloaded list is only used if it matches the SHA-256 published at the URL next to it, if any. \
+            The upstream list publishes none: it is only checked to be a valid list, unless you set one. \
+            Changes are applied on the next list update.",
+        )
+        .style(style::Text::Commentary.get_style());
+
+        let list_sources_ctn = container(
+            column![list_sources, add_list_source_row, list_sources_descr].spacing(10),
+        )
+        .padding(10)
+        .width(Length::Fill)
+        .height(Length::Shrink)
+        .style(style::Container::Frame.get_style());
+
         let warning_ctn = container(
             row![
                 text("The following settings only affect the currently selected device:")
@@ -439,27 +981,72 @@ impl Settings {
 
         let export_btn = button_primary("Export").on_press(Message::ExportPackages);
 
+        let nb_selected = {
+            let mut selected: Vec<&str> = apps_view
+                .phone_packages
+                .iter()
+                .flatten()
+                .filter(|p| p.selected)
+                .map(|p| p.name.as_str())
+                .collect();
+            selected.sort_unstable();
+            selected.dedup();
+            selected.len()
+        };
+        let archive_btn = {
+            let label = text(format!("Backup with APKs ({nb_selected})"));
+            let btn = if nb_selected == 0 {
+                button(label).padding([5, 10])
+            } else {
+                button_primary(label).on_press(Message::ArchiveDevice)
+            };
+            tooltip(
+                btn,
+                "Also save the APKs of the packages selected in the list,\n\
+                    so they can be reinstalled even if they are removed from the device",
+                tooltip::Position::Top,
+            )
+            .gap(4)
+            .padding(10)
+            .style(style::Container::Tooltip.get_style())
+        };
+
         let backup_row = row![
             backup_btn,
             "Backup the current state of the phone",
+            archive_btn,
             Space::new(Length::Fill, Length::Shrink),
             locate_backup_btn,
         ]
         .spacing(10)
         .align_y(Alignment::Center);
 
-        let restore_row = if self.device.backup.backups.is_empty() {
-            row![]
-        } else {
-            row![
-                restore_btn(true),
-                "Restore the state of the device",
-                Space::new(Length::Fill, Length::Shrink),
-                text(self.device.backup.backup_state.clone()).style(style::Text::Danger.get_style()),
-                backup_pick_list,
+        let scope_pick_list = pick_list(
+            BackupScope::ALL,
+            Some(self.device.backup.scope),
+            Message::BackupScopeSelected,
+        )
+        .padding(6);
+
+        let restore_row = {
+            let details = self.device.backup.selected.as_ref().map_or_else(String::new, BackupFile::details);
+            column![
+                row![
+                    restore_btn(!self.device.backup.backups.is_empty()),
+                    "Restore the state of the device",
+                    Space::new(Length::Fill, Length::Shrink),
+                    text(self.device.backup.backup_state.clone()).style(style::Text::Danger.get_style()),
+                    scope_pick_list,
+                    backup_pick_list,
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center),
+                row![
+                    Space::new(Length::Fill, Length::Shrink),
+                    text(details).style(style::Text::Commentary.get_style()),
+                ],
             ]
-            .spacing(10)
-            .align_y(Alignment::Center)
+            .spacing(4)
         };
 
         let no_device_ctn = || {
@@ -475,6 +1062,8 @@ impl Settings {
                 theme_ctn,
                 text("General").size(26),
                 general_ctn,
+                text("Package lists").size(26),
+                list_sources_ctn,
                 text("Current device").size(26),
                 no_device_ctn(),
                 text("Backup / Restore").size(26),
@@ -483,6 +1072,18 @@ impl Settings {
             .width(Length::Fill)
             .spacing(20)
         } else {
+            let compare_btn = if self.device.backup.backups.is_empty() {
+                button("Compare").padding([5, 10]).width(77)
+            } else {
+                button_primary("Compare").on_press(Message::CompareBackups).width(77)
+            };
+            let compare_row = row![
+                compare_btn,
+                "Compare a backup with another one or with the current state of the device",
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center);
+
             let export_row = row![
                 export_btn,
                 "Export uninstalled packages with their description",
@@ -495,8 +1096,33 @@ impl Settings {
             .spacing(10)
             .align_y(Alignment::Center);
 
-            let backup_restore_ctn =
-                container(column![backup_row, restore_row, export_row].spacing(10))
+            let report_row = {
+                let result = match &self.report_export {
+                    Some(Ok(path)) => text(format!("Exported to {}", path.display()))
+                        .style(style::Text::Ok.get_style()),
+                    Some(Err(e)) => text(format!("Export failed: {e}"))
+                        .style(style::Text::Danger.get_style()),
+                    None => text(""),
+                };
+                row![
+                    button_primary("Report").on_press(Message::ExportReport),
+                    "Export a report of the packages of every user",
+                    Space::new(Length::Fill, Length::Shrink),
+                    result,
+                    pick_list(
+                        ReportFormat::ALL,
+                        Some(self.report_format),
+                        Message::ReportFormatSelected,
+                    )
+                    .padding(6),
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center)
+            };
+
+            let backup_restore_ctn = container(
+                column![backup_row, restore_row, compare_row, export_row, report_row].spacing(10),
+            )
                     .padding(10)
                     .width(Length::Fill)
                     .height(Length::Shrink)
@@ -507,6 +1133,8 @@ impl Settings {
                 theme_ctn,
                 text("General").size(26),
                 general_ctn,
+                text("Package lists").size(26),
+                list_sources_ctn,
                 text("Current device").size(26),
                 warning_ctn,
                 device_specific_ctn,
@@ -552,6 +1180,32 @@ impl Settings {
                 .into();
         }
 
+        if let Some(PopUpModal::Restore) = self.modal {
+            let steps = self
+                .show_restore_commands
+                .then(|| dry_run(phone, &self.restore_plan));
+            let dialog = restore_view(&self.restore_plan, steps, self.dry_run_export.as_ref());
+            return Modal::new(content.padding(10), dialog)
+                .on_blur(Message::ModalHide)
+                .into();
+        }
+
+        if let (Some(PopUpModal::Compare), Some(comparison)) = (&self.modal, &self.comparison) {
+            let mut sources = vec![CompareSource::Device];
+            sources.extend(
+                self.device.backup.backups.iter().map(|b| CompareSource::Backup(Box::new(b.clone()))),
+            );
+            return Modal::new(content.padding(10), compare_view(comparison, sources))
+                .on_blur(Message::ModalHide)
+                .into();
+        }
+
+        if let (Some(PopUpModal::RestoreReport), Some(report)) = (&self.modal, &self.restore_report) {
+            return Modal::new(content.padding(10), restore_report_view(report))
+                .on_blur(Message::ModalHide)
+                .into();
+        }
+
         container(scrollable(content))
             .padding(10)
             .width(Length::Fill)
@@ -559,3 +1213,372 @@ impl Settings {
             .into()
     }
 }
+
+/// Packages that differ from the selected backup, grouped by user,
+/// each of them can be excluded from the restore.
+///
+/// With `steps`, the commands of the selected packages are shown instead.
+fn restore_view<'a>(
+    plan: &'a RestorePlan,
+    steps: Option<Vec<PlannedRestore>>,
+    export: Option<&Result<PathBuf, String>>,
+) -> Element<'a, Message, Theme, Renderer> {
+    let items = &plan.items;
+    let title = container(row![text("Restore backup").size(24)].align_y(Alignment::Center))
+        .style(style::Container::Frame.get_style())
+        .padding([10, 0])
+        .center_y(Length::Shrink)
+        .center_x(Length::Fill);
+
+    let groups = restore_groups(plan);
+
+    let nb_selected = items.iter().filter(|i| i.selected).count()
+        + plan.reinstalls.iter().filter(|r| r.selected).count();
+    let restore_btn = button_primary(text(format!("Restore {nb_selected} packages")));
+    let export_status = match export {
+        Some(Ok(path)) => text(format!("Plan exported to {}", path.display()))
+            .style(style::Text::Ok.get_style()),
+        Some(Err(e)) => text(format!("Can't export the plan: {e}")).style(style::Text::Danger.get_style()),
+        None => text(""),
+    };
+    let modal_btn_row = row![
+        button(text("Cancel")).on_press(Message::ModalHide),
+        Space::new(Length::Fill, Length::Shrink),
+        export_status,
+        button_primary(text("Export plan")).on_press(Message::ExportDryRun),
+        if nb_selected == 0 {
+            restore_btn
+        } else {
+            restore_btn.on_press(Message::ConfirmRestore)
+        },
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center)
+    .padding([10, 0]);
+
+    let show_commands = checkbox("Show the commands (dry run)", steps.is_some())
+        .on_toggle(Message::ShowRestoreCommands);
+    let listing = steps.map_or(groups, dry_run_view);
+
+    let skipped = if plan.skipped.is_empty() {
+        text("")
+    } else {
+        text(format!(
+            "{} items of the backup can't be restored on this device, they will be listed in the report.",
+            plan.skipped.len()
+        ))
+        .style(style::Text::Danger.get_style())
+    };
+
+    let compatibility = plan
+        .compatibility
+        .as_ref()
+        .map_or_else(|| column![], compatibility_view);
+
+    container(column![
+        title,
+        compatibility,
+        text("Only the ticked packages will be restored to their state in the backup.")
+            .style(style::Text::Commentary.get_style()),
+        skipped,
+        show_commands,
+        scrollable(listing.padding(padding::right(15))).height(400),
+        modal_btn_row,
+    ]
+    .spacing(10))
+    .height(Length::Shrink)
+    .width(600)
+    .padding(10)
+    .style(style::Container::Frame.get_style())
+    .into()
+}
+
+/// Packages of the restore grouped by user, with a checkbox each
+fn restore_groups(plan: &RestorePlan) -> Column<'_, Message, Theme, Renderer> {
+    let mut users: Vec<User> = plan.items
+        .iter()
+        .map(|i| i.user)
+        .chain(plan.reinstalls.iter().map(|r| r.user))
+        .collect();
+    users.sort_unstable_by_key(|u| u.index);
+    users.dedup();
+
+    let mut groups = column![].spacing(10);
+    for user in users {
+        let user_items = plan.items.iter().enumerate().filter(|(_, i)| i.user == user);
+        let user_reinstalls = plan
+            .reinstalls
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.user == user);
+        let all_selected = user_items.clone().all(|(_, i)| i.selected)
+            && user_reinstalls.clone().all(|(_, r)| r.selected);
+        let mut group = column![
+            checkbox(format!("{user} ({})", user.kind), all_selected)
+                .on_toggle(move |toggled| Message::RestoreUserToggled(user.id, toggled))
+                .text_size(18)
+        ]
+        .spacing(4);
+        for (i, item) in user_items {
+            group = group.push(
+                row![
+                    checkbox(item.package.name.clone(), item.selected)
+                        .on_toggle(move |toggled| Message::RestoreItemToggled(i, toggled)),
+                    Space::new(Length::Fill, Length::Shrink),
+                    text(format!("{} \u{2192} {}", item.package.state, item.wanted))
+                        .style(style::Text::Commentary.get_style()),
+                ]
+                .padding(padding::left(20))
+                .spacing(10),
+            );
+        }
+        for (i, item) in user_reinstalls {
+            group = group.push(
+                row![
+                    checkbox(item.package.clone(), item.selected)
+                        .on_toggle(move |toggled| Message::ReinstallItemToggled(i, toggled)),
+                    Space::new(Length::Fill, Length::Shrink),
+                    text(format!("reinstall from the archive \u{2192} {}", item.wanted))
+                        .style(style::Text::Commentary.get_style()),
+                ]
+                .padding(padding::left(20))
+                .spacing(10),
+            );
+        }
+        groups = groups.push(group);
+    }
+    groups
+}
+
+/// Commands of a restore, grouped by package
+fn dry_run_view<'a>(steps: Vec<PlannedRestore>) -> Column<'a, Message, Theme, Renderer> {
+    steps.into_iter().fold(column![].spacing(10), |col, step| {
+        let from = step
+            .from
+            .map_or_else(|| "not installed".to_string(), |s| s.to_string());
+        let header = text(format!(
+            "user {}: {} ({from} \u{2192} {})",
+            step.user_id, step.package, step.to
+        ));
+        col.push(step.commands.into_iter().fold(
+            column![header].spacing(2),
+            |cmds, command| {
+                cmds.push(
+                    text(command)
+                        .size(13)
+                        .style(style::Text::Commentary.get_style()),
+                )
+            },
+        ))
+    })
+}
+
+/// Preview of restoring the backup of another device
+fn compatibility_view(compatibility: &Compatibility) -> Column<'_, Message, Theme, Renderer> {
+    let source = match &compatibility.metadata {
+        Some(m) => format!(
+            "This backup comes from another device: {} {} ({}), Android SDK {}",
+            m.brand, m.model, compatibility.device_id, m.android_sdk
+        ),
+        None => format!(
+            "This backup comes from another device ({})",
+            compatibility.device_id
+        ),
+    };
+    let mapping = compatibility
+        .user_mapping
+        .iter()
+        .map(|(from, to)| match to {
+            Some(to) => format!("backup user {from} \u{2192} user {to}"),
+            None => format!("backup user {from} \u{2192} not restored"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    compatibility
+        .warnings
+        .iter()
+        .fold(
+            column![
+                text(source),
+                text(format!("Users: {mapping}")).style(style::Text::Commentary.get_style()),
+            ],
+            |col, w| col.push(text(w).style(style::Text::Danger.get_style())),
+        )
+        .spacing(4)
+}
+
+/// Progress, then outcome, of a restore
+fn restore_report_view(report: &RestoreReport) -> Element<'_, Message, Theme, Renderer> {
+    let title = if report.is_done() {
+        "Restore finished".to_string()
+    } else {
+        format!("Restoring... ({} left)", report.pending)
+    };
+    let title = container(row![text(title).size(24)].align_y(Alignment::Center))
+        .style(style::Container::Frame.get_style())
+        .padding([10, 0])
+        .center_y(Length::Shrink)
+        .center_x(Length::Fill);
+
+    let mut sections = column![].spacing(15);
+    for (name, lines, style) in [
+        (
+            "Restored",
+            report.restored.clone(),
+            &style::Text::Ok,
+        ),
+        (
+            "Failed",
+            report
+                .failed
+                .iter()
+                .map(|(p, e)| format!("{p}: {e}"))
+                .collect(),
+            &style::Text::Danger,
+        ),
+        (
+            "Skipped",
+            report.skipped.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            &style::Text::Commentary,
+        ),
+    ] {
+        let header = text(format!("{name} ({})", lines.len())).size(18);
+        sections = sections.push(
+            lines
+                .into_iter()
+                .fold(column![header].spacing(4), |col, line| {
+                    col.push(text(line).style(style.get_style()))
+                }),
+        );
+    }
+
+    let modal_btn_row = row![
+        Space::new(Length::Fill, Length::Shrink),
+        button(text("Close").width(Length::Shrink))
+            .width(Length::Shrink)
+            .on_press(Message::ModalHide),
+        Space::new(Length::Fill, Length::Shrink),
+    ];
+
+    container(
+        column![
+            title,
+            scrollable(sections.padding(padding::right(15))).height(400),
+            modal_btn_row,
+        ]
+        .spacing(10),
+    )
+    .height(Length::Shrink)
+    .width(600)
+    .padding(10)
+    .style(style::Container::Frame.get_style())
+    .into()
+}
+
+/// Packages whose state differs between the two sides, grouped by user
+fn compare_view(
+    comparison: &Comparison,
+    sources: Vec<CompareSource>,
+) -> Element<'_, Message, Theme, Renderer> {
+    let title = container(row![text("Compare backups").size(24)].align_y(Alignment::Center))
+        .style(style::Container::Frame.get_style())
+        .padding([10, 0])
+        .center_y(Length::Shrink)
+        .center_x(Length::Fill);
+
+    let sources_row = row![
+        pick_list(
+            sources.clone(),
+            Some(comparison.old.clone()),
+            Message::CompareOldSelected
+        )
+        .padding(6),
+        text("->"),
+        pick_list(sources, Some(comparison.new.clone()), Message::CompareNewSelected).padding(6),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+
+    let users = match &comparison.users {
+        Ok(users) => users,
+        Err(e) => {
+            return container(
+                column![
+                    title,
+                    sources_row,
+                    text(format!("Can't read the backup: {e}")).style(style::Text::Danger.get_style()),
+                    compare_close_row(),
+                ]
+                .spacing(10),
+            )
+            .width(800)
+            .padding(10)
+            .style(style::Container::Frame.get_style())
+            .into();
+        }
+    };
+
+    let sections = users.iter().fold(column![].spacing(15), |col, user| {
+        let header = text(format!("User {} ({})", user.user_id, user.kind)).size(18);
+        let section = match user.presence {
+            Presence::OnlyOld => column![
+                header,
+                text("Only in the first one").style(style::Text::Commentary.get_style())
+            ],
+            Presence::OnlyNew => column![
+                header,
+                text("Only in the second one").style(style::Text::Commentary.get_style())
+            ],
+            Presence::Both if user.changes.is_empty() => column![
+                header,
+                text("No change").style(style::Text::Commentary.get_style())
+            ],
+            Presence::Both => user.changes.iter().fold(column![header], |rows, change| {
+                let state = |s: Option<PackageState>| s.map_or_else(|| "Not installed".to_string(), |s| s.to_string());
+                let direction_style = if change.to == Some(PackageState::Enabled) {
+                    &style::Text::Ok
+                } else {
+                    &style::Text::Danger
+                };
+                rows.push(
+                    row![
+                        text(change.package.clone()).width(Length::Fill),
+                        text(change.list.to_string()).width(90),
+                        text(change.removal.to_string()).width(100),
+                        text(format!("{} -> {}", state(change.from), state(change.to)))
+                            .width(220)
+                            .style(direction_style.get_style()),
+                    ]
+                    .spacing(10),
+                )
+            }),
+        };
+        col.push(section.spacing(4))
+    });
+
+    container(
+        column![
+            title,
+            sources_row,
+            scrollable(sections.padding(padding::right(15))).height(400),
+            compare_close_row(),
+        ]
+        .spacing(10),
+    )
+    .height(Length::Shrink)
+    .width(800)
+    .padding(10)
+    .style(style::Container::Frame.get_style())
+    .into()
+}
+
+fn compare_close_row<'a>() -> Element<'a, Message, Theme, Renderer> {
+    row![
+        Space::new(Length::Fill, Length::Shrink),
+        button(text("Close").width(Length::Shrink))
+            .width(Length::Shrink)
+            .on_press(Message::ModalHide),
+        Space::new(Length::Fill, Length::Shrink),
+    ]
+    .into()
+}
@@ -1,4 +1,5 @@
 use crate::core::helpers::button_primary;
+use crate::core::offline::OfflineSource;
 pub use crate::core::sync::Phone;
 use crate::core::update::{SelfUpdateState, SelfUpdateStatus};
 pub use crate::gui::views::about::Message as AboutMessage;
@@ -17,6 +18,7 @@ pub fn nav_menu<'a>(
     device_list: &'a [Phone],
     selected_device: Option<Phone>,
     apps_view: &AppsView,
+    offline_sources: &'a [OfflineSource],
     self_update_state: &SelfUpdateState,
 ) -> Element<'a, Message, Theme, Renderer> {
     let apps_refresh_btn = button_primary(
@@ -31,7 +33,9 @@ pub fn nav_menu<'a>(
         .style(style::Container::Tooltip.get_style())
         .gap(4);
 
-    let reboot_btn = button_primary("Reboot").on_press(Message::RebootButtonPressed);
+    // A device browsed offline can't be rebooted
+    let reboot_btn = button_primary("Reboot")
+        .on_press_maybe((!apps_view.is_offline()).then_some(Message::RebootButtonPressed));
 
     let uad_version_text = if let Some(r) = &self_update_state.latest_release {
         match self_update_state.status {
@@ -59,6 +63,22 @@ pub fn nav_menu<'a>(
 
     let apps_btn = button_primary("Apps").on_press(Message::AppsPress);
 
+    let fleet_btn = tooltip(
+        button_primary("Fleet").on_press(Message::FleetPressed),
+        "Apply a profile or the selection to several devices",
+        tooltip::Position::Bottom,
+    )
+    .style(style::Container::Tooltip.get_style())
+    .gap(4);
+
+    let compare_btn = tooltip(
+        button_primary("Compare").on_press(Message::ComparePressed),
+        "Compare two devices, or a device and a backup",
+        tooltip::Position::Bottom,
+    )
+    .style(style::Container::Tooltip.get_style())
+    .gap(4);
+
     let about_btn = button_primary("About").on_press(Message::AboutPressed);
 
     let settings_btn = button_primary(
@@ -74,40 +94,86 @@ pub fn nav_menu<'a>(
         _ => text("No devices/emulators found"),
     };
 
-    let row = match selected_device {
-        Some(phone) => row![
-            reboot_btn,
-            apps_refresh_tooltip,
-            pick_list(device_list, Some(phone), Message::DeviceSelected,),
-            Space::new(Length::Fill, Length::Shrink),
-            uad_version_text,
-            update_btn,
-            apps_btn,
-            about_btn,
-            settings_btn,
-        ]
-        .width(Length::Fill)
-        .align_y(Alignment::Center)
-        .spacing(10),
-        None => row![
-            reboot_btn,
-            apps_refresh_tooltip,
-            device_list_text,
-            Space::new(Length::Fill, Length::Shrink),
-            uad_version_text,
-            update_btn,
-            apps_btn,
-            about_btn,
-            settings_btn,
-        ]
-        .width(Length::Fill)
-        .align_y(Alignment::Center)
-        .spacing(10),
+    let devices: Element<'a, Message, Theme, Renderer> = match selected_device {
+        Some(phone) => pick_list(device_list, Some(phone), Message::DeviceSelected).into(),
+        None => device_list_text.into(),
     };
 
+    let row = row![
+        reboot_btn,
+        apps_refresh_tooltip,
+        devices,
+        offline_picker(offline_sources),
+        Space::new(Length::Fill, Length::Shrink),
+        uad_version_text,
+        update_btn,
+        apps_btn,
+        fleet_btn,
+        compare_btn,
+        about_btn,
+        settings_btn,
+    ]
+    .width(Length::Fill)
+    .align_y(Alignment::Center)
+    .spacing(10);
+
     container(row)
         .width(Length::Fill)
         .padding(10)
         .style(style::Container::Frame.get_style())
         .into()
 }
+
+fn offline_picker(offline_sources: &[OfflineSource]) -> Element<'_, Message, Theme, Renderer> {
+    tooltip(
+        pick_list(offline_sources, None::<OfflineSource>, Message::OpenOffline)
+            .placeholder("Offline..."),
+        "Browse a device seen before, or a backup, while it isn't connected",
+        tooltip::Position::Bottom,
+    )
+    .style(style::Container::Tooltip.get_style())
+    .gap(4)
+    .into()
+}
+
+/// One tab per opened device, with whether it is browsed offline,
+/// `selected` being the serial of the selected one
+pub fn tab_bar<'a>(
+    phones: impl Iterator<Item = (&'a Phone, bool)>,
+    selected: &str,
+) -> Element<'a, Message, Theme, Renderer> {
+    let tabs = phones.map(|(phone, offline)| {
+        let style = if phone.adb_id == selected {
+            &style::Button::SelectedPackage
+        } else {
+            &style::Button::NormalPackage
+        };
+        row![
+            button(if offline {
+                text(format!("{} (offline)", phone.model))
+            } else {
+                text(&phone.model)
+            })
+                .on_press(Message::TabSelected(phone.adb_id.clone()))
+                .padding([5, 10])
+                .style(style.get_style()),
+            tooltip(
+                button(text("\u{00D7}"))
+                    .on_press(Message::TabClosed(phone.adb_id.clone()))
+                    .padding([5, 8])
+                    .style(style.get_style()),
+                "Close this device",
+                tooltip::Position::Bottom,
+            )
+            .style(style::Container::Tooltip.get_style())
+            .gap(4),
+        ]
+        .into()
+    });
+
+    container(row(tabs).spacing(10).align_y(Alignment::Center))
+        .width(Length::Fill)
+        .padding([5, 10])
+        .style(style::Container::Frame.get_style())
+        .into()
+}
@@ -46,6 +46,12 @@ impl PackageRow {
         }
     }
 
+    /// Unselected row without description, for tests
+    #[cfg(test)]
+    pub fn test(name: &str, state: PackageState, uad_list: UadList, removal: Removal) -> Self {
+        Self::new(name, state, "", uad_list, removal, false, false)
+    }
+
     pub fn update(&mut self, _message: &Message) -> Task<Message> {
         Task::none()
     }
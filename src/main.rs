@@ -8,9 +8,11 @@ use fern::{
     colors::{Color, ColoredLevelConfig},
 };
 use log::Record;
+use std::process::ExitCode;
 use std::sync::LazyLock;
 use std::{fmt::Arguments, fs::OpenOptions, path::PathBuf};
 
+mod cli;
 mod core;
 mod gui;
 
@@ -19,7 +21,18 @@ static CONFIG_DIR: LazyLock<PathBuf> =
 static CACHE_DIR: LazyLock<PathBuf> =
     LazyLock::new(|| setup_uad_dir(&dirs::cache_dir().expect("Can't detect cache dir")));
 
-fn main() -> iced::Result {
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        #[cfg(target_os = "windows")]
+        {
+            attach_windows_console();
+        }
+        if let Some(code) = cli::run(&args) {
+            return code;
+        }
+    }
+
     // Safety: This function is safe to call in a single-threaded program.
     // The exact requirement is: you must ensure that there are no other threads concurrently writing or
     // reading(!) the environment through functions or global variables other than the ones in this module.
@@ -30,7 +43,13 @@ fn main() -> iced::Result {
     }
 
     setup_logger().expect("setup logging");
-    gui::UadGui::start()
+    match gui::UadGui::start() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!("{e}");
+            ExitCode::FAILURE
+        }
+    }
 }
 
 /// Sets up logging to a new file in `CACHE_DIR"/uadng.log"`